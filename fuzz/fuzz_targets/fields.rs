@@ -0,0 +1,10 @@
+#![no_main]
+
+use dbus_marshal::{Fields, unmarshal::Reader};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(fields) = Reader::new(data).read::<Fields>() {
+        for _ in fields.iter() {}
+    }
+});
@@ -0,0 +1,31 @@
+#![no_main]
+
+use dbus_marshal::{
+    Signature,
+    unmarshal::{Iter, Token},
+};
+use libfuzzer_sys::fuzz_target;
+
+/// Walks every token, descending into arrays.
+fn walk(iter: Iter<'_>) {
+    for token in iter {
+        match token {
+            Ok(Token::Array(array)) => walk(array.iter()),
+            Ok(_) => {}
+            Err(_) => return,
+        }
+    }
+}
+
+// The first byte is the length of the signature, which precedes the body.
+fuzz_target!(|data: &[u8]| {
+    let Some((&len, rest)) = data.split_first() else {
+        return;
+    };
+    let Some((signature, body)) = rest.split_at_checked(len as usize) else {
+        return;
+    };
+    if let Ok(iter) = Iter::new(Signature::from_bytes(signature), body) {
+        walk(iter);
+    }
+});
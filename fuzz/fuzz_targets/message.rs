@@ -0,0 +1,15 @@
+#![no_main]
+
+use dbus_marshal::MessageIterator;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    for message in MessageIterator::new(data).quarantined() {
+        let Ok(message) = message else {
+            continue;
+        };
+        let _ = message.arg_count();
+        let _ = message.for_each_string(|_, _| {});
+        let _ = format!("{:?}", message.redacted(&[]));
+    }
+});
@@ -0,0 +1,12 @@
+#![no_main]
+
+use dbus_marshal::signature::complete_type_len;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let mut rest = data;
+    while let Some(len) = complete_type_len(rest) {
+        assert!(len > 0 && len <= rest.len());
+        rest = &rest[len..];
+    }
+});
@@ -0,0 +1,50 @@
+//! Writes seed inputs for every fuzz target into `corpus/`, built with the marshal API so they
+//! start out valid. Run with `cargo run --bin seeds` from this directory.
+
+use std::{fs, num::NonZeroU32, path::Path};
+
+use dbus_marshal::{
+    Endian, Entry, Fields, Flags, Header, InterfaceName, MemberName, Message, MessageType,
+    ObjectPath, Signature, Struct, Variant, marshal, multiple_new,
+};
+
+fn write(target: &str, name: &str, data: &[u8]) {
+    let dir = Path::new("corpus").join(target);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join(name), data).unwrap();
+}
+
+fn main() {
+    let signature = Signature::from_str("ua{sv}(so)");
+    let fields = Fields::empty()
+        .path(ObjectPath::from_str("/org/example"))
+        .interface(InterfaceName::from_static("org.example.Seed"))
+        .member(MemberName::from_static("Changed"))
+        .signature(signature);
+    let arguments = multiple_new!(
+        7u32,
+        &[Entry("a", Variant(1u8)), Entry("b", Variant(2u8))][..],
+        Struct(multiple_new!("d", ObjectPath::from_str("/e")))
+    );
+    let message = marshal::marshal(&Message {
+        header: Header {
+            endian: Endian::NATIVE,
+            message_type: MessageType::Signal,
+            flags: Flags::empty(),
+            serial: NonZeroU32::new(1).unwrap(),
+            fields,
+        },
+        arguments,
+    });
+    write("message", "signal", &message);
+    write("message", "two", &[&message[..], &message[..]].concat());
+
+    let body = marshal::marshal(arguments);
+    let iter = [&[signature.len() as u8][..], signature.as_bytes(), &body].concat();
+    write("iter", "body", &iter);
+
+    write("signature", "nested", signature.as_bytes());
+    write("signature", "deep", b"a(a{sa(v)}aay)");
+
+    write("fields", "fields", &marshal::marshal(&fields));
+}
@@ -0,0 +1,173 @@
+//! [`proptest`] strategies generating signatures and values of them, for property tests of code
+//! built on this crate.
+
+use alloc::{borrow::ToOwned, boxed::Box, string::String, vec, vec::Vec};
+
+use proptest::{collection, prelude::*, sample};
+
+use crate::{signature::complete_type_len, strings, unmarshal::Value};
+
+const BASIC: &[u8] = b"ybnqiuxtdhsog";
+
+/// A generated value, owning the strings a [`Value`] borrows.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArbitraryValue {
+    /// A value of a fixed-size basic type.
+    Fixed(Value<'static>),
+    String(Box<strings::String>),
+    Object(Box<strings::ObjectPath>),
+    Signature(Box<strings::Signature>),
+    Array(Box<strings::Signature>, Vec<ArbitraryValue>),
+    Struct(Vec<ArbitraryValue>),
+    Entry(Box<(ArbitraryValue, ArbitraryValue)>),
+    Variant(Box<strings::Signature>, Box<ArbitraryValue>),
+}
+
+impl ArbitraryValue {
+    /// The value borrowing from `self`, e.g. to marshal it.
+    pub fn value(&self) -> Value<'_> {
+        match self {
+            ArbitraryValue::Fixed(x) => x.clone(),
+            ArbitraryValue::String(x) => Value::String(x),
+            ArbitraryValue::Object(x) => Value::Object(x),
+            ArbitraryValue::Signature(x) => Value::Signature(x),
+            ArbitraryValue::Array(element, xs) => {
+                Value::Array(element, xs.iter().map(Self::value).collect())
+            }
+            ArbitraryValue::Struct(xs) => Value::Struct(xs.iter().map(Self::value).collect()),
+            ArbitraryValue::Entry(entry) => {
+                Value::Entry(Box::new((entry.0.value(), entry.1.value())))
+            }
+            ArbitraryValue::Variant(signature, x) => Value::Variant(signature, Box::new(x.value())),
+        }
+    }
+}
+
+fn owned_signature(bytes: &[u8]) -> Box<strings::Signature> {
+    strings::Signature::from_bytes(bytes).to_owned()
+}
+
+/// Single complete types with containers nested up to `depth` deep. Variants count as a level
+/// of nesting, so they only appear when `depth` is at least 1.
+pub fn signature(depth: u32) -> BoxedStrategy<Vec<u8>> {
+    let codes = if depth == 0 {
+        BASIC.to_vec()
+    } else {
+        [BASIC, b"v"].concat()
+    };
+    sample::select(codes)
+        .prop_map(|x| vec![x])
+        .prop_recursive(depth, 64, 4, |inner| {
+            prop_oneof![
+                inner.clone().prop_map(|x| [&b"a"[..], &x].concat()),
+                (sample::select(BASIC), inner.clone()).prop_map(|(key, value)| [
+                    &b"a{"[..],
+                    &[key],
+                    &value,
+                    b"}"
+                ]
+                .concat()),
+                collection::vec(inner, 1..=4)
+                    .prop_map(|xs| [&b"("[..], &xs.concat(), b")"].concat()),
+            ]
+        })
+        .boxed()
+}
+
+/// Values of the single complete type `signature`, whose variants hold types nested at most
+/// `depth` deep. Arrays get up to four elements.
+pub fn value_of(signature: &[u8], depth: u32) -> BoxedStrategy<ArbitraryValue> {
+    let fixed = |x: Value<'static>| ArbitraryValue::Fixed(x);
+    match signature[0] {
+        b'y' => any::<u8>().prop_map(move |x| fixed(Value::U8(x))).boxed(),
+        b'b' => any::<bool>()
+            .prop_map(move |x| fixed(Value::Bool(x)))
+            .boxed(),
+        b'n' => any::<i16>().prop_map(move |x| fixed(Value::I16(x))).boxed(),
+        b'q' => any::<u16>().prop_map(move |x| fixed(Value::U16(x))).boxed(),
+        b'i' => any::<i32>().prop_map(move |x| fixed(Value::I32(x))).boxed(),
+        b'u' => any::<u32>().prop_map(move |x| fixed(Value::U32(x))).boxed(),
+        b'x' => any::<i64>().prop_map(move |x| fixed(Value::I64(x))).boxed(),
+        b't' => any::<u64>().prop_map(move |x| fixed(Value::U64(x))).boxed(),
+        b'd' => any::<f64>().prop_map(move |x| fixed(Value::F64(x))).boxed(),
+        b'h' => any::<u32>()
+            .prop_map(move |x| fixed(Value::UnixFd(x)))
+            .boxed(),
+        b's' => "[^\0]{0,16}"
+            .prop_map(|x: String| ArbitraryValue::String(strings::String::from_str(&x).to_owned()))
+            .boxed(),
+        b'o' => "/|(/[A-Za-z0-9_]{1,8}){1,3}"
+            .prop_map(|x: String| {
+                ArbitraryValue::Object(strings::ObjectPath::from_str(&x).to_owned())
+            })
+            .boxed(),
+        b'g' => collection::vec(self::signature(1), 0..=3)
+            .prop_map(|xs| ArbitraryValue::Signature(owned_signature(&xs.concat())))
+            .boxed(),
+        b'v' => {
+            let inner = depth.saturating_sub(1);
+            self::signature(inner)
+                .prop_flat_map(move |x| {
+                    let signature = owned_signature(&x);
+                    value_of(&x, inner).prop_map(move |value| {
+                        ArbitraryValue::Variant(signature.clone(), Box::new(value))
+                    })
+                })
+                .boxed()
+        }
+        b'a' => {
+            let element = owned_signature(&signature[1..]);
+            collection::vec(value_of(&signature[1..], depth), 0..=4)
+                .prop_map(move |xs| ArbitraryValue::Array(element.clone(), xs))
+                .boxed()
+        }
+        b'(' => fields(&signature[1..signature.len() - 1], depth)
+            .prop_map(ArbitraryValue::Struct)
+            .boxed(),
+        b'{' => fields(&signature[1..signature.len() - 1], depth)
+            .prop_map(|mut xs| {
+                let value = xs.pop().unwrap();
+                let key = xs.pop().unwrap();
+                ArbitraryValue::Entry(Box::new((key, value)))
+            })
+            .boxed(),
+        code => panic!("'{}' is not a type code", code as char),
+    }
+}
+
+/// Values of each complete type in `signature`, in order.
+fn fields(mut signature: &[u8], depth: u32) -> Vec<BoxedStrategy<ArbitraryValue>> {
+    let mut fields = Vec::new();
+    while !signature.is_empty() {
+        let len = complete_type_len(signature).expect("invalid signature");
+        fields.push(value_of(&signature[..len], depth));
+        signature = &signature[len..];
+    }
+    fields
+}
+
+/// A signature with containers nested up to `depth` deep, and a value of it.
+pub fn typed_value(depth: u32) -> impl Strategy<Value = (Box<strings::Signature>, ArbitraryValue)> {
+    signature(depth).prop_flat_map(move |x| {
+        let signature = owned_signature(&x);
+        value_of(&x, depth).prop_map(move |value| (signature.clone(), value))
+    })
+}
+
+#[cfg(test)]
+proptest! {
+    /// Heavy: run with `cargo test --features proptest -- --ignored`.
+    #[test]
+    #[ignore]
+    fn test_round_trip((signature, value) in typed_value(3)) {
+        use crate::{marshal::marshal, unmarshal::Reader};
+
+        let value = value.value();
+        prop_assert_eq!(value.check(&signature), Ok(()));
+        let bytes = marshal(&value);
+        let mut r = Reader::new(&bytes);
+        let read = r.read_dynamic(&signature).unwrap();
+        prop_assert!(r.remaining().is_empty());
+        prop_assert_eq!(&*marshal(&read), &*bytes);
+    }
+}
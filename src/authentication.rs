@@ -1,8 +1,15 @@
-use core::fmt::Debug;
+#[cfg(feature = "alloc")]
+use alloc::{collections::VecDeque, vec::Vec};
+use core::{
+    fmt::{self, Debug, Display},
+    str::FromStr,
+};
 
 use arrayvec::ArrayVec;
 use thiserror::Error;
 
+use crate::util::Digits;
+
 #[derive(Debug, Error)]
 pub enum Error<IoError: Debug> {
     #[error("authentication failed")]
@@ -22,86 +29,301 @@ pub trait Io {
     ) -> impl Future<Output = Result<(), Self::Error>>;
 }
 
-const fn digits(mut x: u32) -> u32 {
-    let mut res = 0;
-    while x != 0 {
-        x /= 10;
-        res += 1;
+/// The SASL mechanism the connection authenticated with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Mechanism {
+    External,
+}
+
+/// What [`authenticate`] asks the server for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Options {
+    /// Whether to negotiate passing unix file descriptors.
+    pub unix_fd: bool,
+}
+
+impl Options {
+    pub const fn new() -> Self {
+        Self { unix_fd: true }
+    }
+    pub const fn with_unix_fd(self, unix_fd: bool) -> Self {
+        Self { unix_fd }
     }
-    res
 }
 
-struct DigitIter {
-    n: u32,
-    base: u32,
+impl Default for Options {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
-impl DigitIter {
-    const fn new(n: u32) -> Self {
-        Self {
-            n,
-            base: 10u32.pow(digits(n) - 1),
+/// What the handshake settled on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Negotiated {
+    /// Whether the server agreed to unix file descriptor passing; false if it was not asked.
+    pub unix_fd: bool,
+    /// The GUID the server sent with `OK`.
+    pub server_guid: Guid,
+    pub mechanism: Mechanism,
+}
+
+const fn hex_value(x: u8) -> Option<u8> {
+    Some(match x {
+        b'0'..=b'9' => x - b'0',
+        b'a'..=b'f' => x - b'a' + 10,
+        b'A'..=b'F' => x - b'A' + 10,
+        _ => None?,
+    })
+}
+
+/// The GUID identifying a server, sent with `OK` during authentication and given as the `guid`
+/// key of addresses, where it is written as 32 hex digits.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Guid(pub [u8; 16]);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+#[error("invalid guid")]
+pub struct InvalidGuid;
+
+impl Guid {
+    /// Parses exactly 32 hex digits, in either case.
+    pub const fn from_hex(hex: &[u8]) -> Result<Self, InvalidGuid> {
+        if hex.len() != 32 {
+            Err(InvalidGuid)?
         }
-    }
-    const fn next(&mut self) -> Option<u32> {
-        if self.base == 0 {
-            None?
+        let mut res = [0; 16];
+        let mut i = 0;
+        while i < 16 {
+            let (Some(high), Some(low)) = (hex_value(hex[2 * i]), hex_value(hex[2 * i + 1])) else {
+                Err(InvalidGuid)?
+            };
+            res[i] = high << 4 | low;
+            i += 1;
         }
-        let res = self.n / self.base;
-        self.n -= res * self.base;
-        self.base /= 10;
-        Some(res)
+        Ok(Self(res))
+    }
+    pub const fn as_bytes(&self) -> &[u8; 16] {
+        &self.0
     }
 }
 
-impl Iterator for DigitIter {
-    type Item = u32;
-    fn next(&mut self) -> Option<Self::Item> {
-        self.next()
+impl FromStr for Guid {
+    type Err = InvalidGuid;
+
+    fn from_str(s: &str) -> Result<Self, InvalidGuid> {
+        Self::from_hex(s.as_bytes())
     }
 }
 
-const fn hex_to_ascii(hex: u8) -> u8 {
-    if hex < 10 {
-        b'0' + hex
-    } else {
-        b'a' + hex - 10
+impl Display for Guid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.iter().try_for_each(|x| write!(f, "{x:02x}"))
     }
 }
 
-const fn to_ascii(digit: u8) -> [u8; 2] {
-    let ascii = digit as u8 + b'0';
-    [ascii / 16, ascii % 16].map(hex_to_ascii)
+impl Debug for Guid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Guid({self})")
+    }
 }
 
-static_assertions::const_assert_eq!(to_ascii(1), *b"31");
+/// Compares two secrets, such as cookies or nonces, in time that depends only on their lengths,
+/// so that a peer cannot learn how many leading bytes it guessed right.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let diff = a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y));
+    core::hint::black_box(diff) == 0
+}
 
-#[test]
-fn test_digit_iter() {
-    let mut iter = DigitIter::new(1000);
-    assert_eq!(iter.next(), Some(1));
-    assert_eq!(iter.next(), Some(0));
-    assert_eq!(iter.next(), Some(0));
-    assert_eq!(iter.next(), Some(0));
-    assert_eq!(iter.next(), None);
+/// Parses the GUID of an `OK <guid>` line.
+fn parse_ok(line: &[u8]) -> Option<Guid> {
+    let hex = line.strip_prefix(b"OK ")?.get(..32)?;
+    Guid::from_hex(hex).ok()
 }
 
-pub async fn authenticate<T: Io>(io: &mut T, uid: u32) -> Result<(), Error<T::Error>> {
+pub async fn authenticate<T: Io>(
+    io: &mut T,
+    uid: u32,
+    options: Options,
+) -> Result<Negotiated, Error<T::Error>> {
     let mut buf: ArrayVec<u8, 128> = ArrayVec::new();
     buf.try_extend_from_slice(b"\x00AUTH EXTERNAL ").ok();
-    for digit in DigitIter::new(uid) {
-        buf.try_extend_from_slice(&to_ascii(digit as _)).ok();
+    for &digit in Digits::decimal(uid as _).as_bytes() {
+        buf.try_extend_from_slice(Digits::hex(digit as _).as_bytes())
+            .ok();
     }
     buf.try_extend_from_slice(b"\r\n").ok();
     io.write(buf).await?;
-    if !io.read().await?.as_ref().starts_with(b"OK") {
-        Err(Error::AuthenticationFailed)?
+    let server_guid = parse_ok(io.read().await?.as_ref()).ok_or(Error::AuthenticationFailed)?;
+
+    let unix_fd = if options.unix_fd {
+        io.write(b"NEGOTIATE_UNIX_FD\r\nBEGIN\r\n").await?;
+        let reply = io.read().await?;
+        match reply.as_ref() {
+            x if x.starts_with(b"AGREE_UNIX_FD\r\n") => true,
+            x if x.starts_with(b"ERROR") => false,
+            _ => Err(Error::NegotiationFailed)?,
+        }
+    } else {
+        io.write(b"BEGIN\r\n").await?;
+        false
+    };
+
+    Ok(Negotiated {
+        unix_fd,
+        server_guid,
+        mechanism: Mechanism::External,
+    })
+}
+
+/// An in-memory [`Io`] that answers reads from a script and records writes, for testing
+/// connection logic without a bus.
+///
+/// Each read yields the next scripted response, such as an authentication line or a marshalled
+/// message, and fails with [`LoopbackError::Exhausted`] once the script is done.
+#[cfg(feature = "alloc")]
+#[derive(Debug, Default, Clone)]
+pub struct LoopbackIo {
+    responses: VecDeque<Vec<u8>>,
+    written: Vec<Vec<u8>>,
+}
+
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum LoopbackError {
+    #[error("no scripted response left")]
+    Exhausted,
+}
+
+#[cfg(feature = "alloc")]
+impl LoopbackIo {
+    pub const fn new() -> Self {
+        Self {
+            responses: VecDeque::new(),
+            written: Vec::new(),
+        }
+    }
+    /// Queues `data` as the answer to the next unanswered read.
+    pub fn respond(mut self, data: impl AsRef<[u8]>) -> Self {
+        self.push_response(data);
+        self
+    }
+    /// Queues the marshalled `value`, typically a `&Message`, as a response.
+    pub fn respond_marshalled(self, value: impl crate::marshal::Marshal) -> Self {
+        self.respond(crate::marshal::marshal(value))
+    }
+    pub fn push_response(&mut self, data: impl AsRef<[u8]>) {
+        self.responses.push_back(data.as_ref().to_vec());
+    }
+    /// Everything written so far, one entry per write.
+    pub fn written(&self) -> &[Vec<u8>] {
+        &self.written
     }
+    /// The number of responses not read yet.
+    pub fn pending(&self) -> usize {
+        self.responses.len()
+    }
+}
 
-    io.write(b"NEGOTIATE_UNIX_FD\r\nBEGIN\r\n").await?;
-    if !io.read().await?.as_ref().starts_with(b"AGREE_UNIX_FD\r\n") {
-        Err(Error::AuthenticationFailed)?
+#[cfg(feature = "alloc")]
+impl Io for LoopbackIo {
+    type Error = LoopbackError;
+
+    fn read(&mut self) -> impl Future<Output = Result<impl AsRef<[u8]>, LoopbackError>> {
+        let res = self.responses.pop_front().ok_or(LoopbackError::Exhausted);
+        async { res }
+    }
+    fn write(
+        &mut self,
+        data: impl AsRef<[u8]> + 'static,
+    ) -> impl Future<Output = Result<(), LoopbackError>> {
+        self.written.push(data.as_ref().to_vec());
+        async { Ok(()) }
     }
+}
+
+#[test]
+fn test_guid() {
+    let hex = "00112233445566778899AABBCCDDEEFF";
+    let guid: Guid = hex.parse().unwrap();
+    assert_eq!(guid.as_bytes()[15], 0xff);
+    assert_eq!(
+        format!("{guid:?}"),
+        "Guid(00112233445566778899aabbccddeeff)"
+    );
+    assert_eq!(Guid::from_hex(&hex.as_bytes()[1..]), Err(InvalidGuid));
+    assert_eq!(Guid::from_hex(&[b'0'; 32]), Ok(Guid([0; 16])));
+    assert_eq!(Guid::from_hex(&[b'g'; 32]), Err(InvalidGuid));
+}
+
+#[test]
+fn test_constant_time_eq() {
+    assert!(constant_time_eq(b"cookie", b"cookie"));
+    assert!(!constant_time_eq(b"cookie", b"cookiE"));
+    assert!(!constant_time_eq(b"cookie", b"cook"));
+    assert!(constant_time_eq(b"", b""));
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_loopback() {
+    use core::{
+        pin::pin,
+        task::{Context, Poll, Waker},
+    };
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = pin!(future);
+        let Poll::Ready(x) = future
+            .as_mut()
+            .poll(&mut Context::from_waker(Waker::noop()))
+        else {
+            panic!("loopback futures are always ready")
+        };
+        x
+    }
+
+    let guid = b"OK 0123456789abcdef0123456789ABCDEF\r\n";
+    let mut io = LoopbackIo::new()
+        .respond(guid)
+        .respond(b"AGREE_UNIX_FD\r\n");
+    let negotiated = block_on(authenticate(&mut io, 1000, Options::new())).unwrap();
+    assert_eq!(
+        negotiated,
+        Negotiated {
+            unix_fd: true,
+            server_guid: "0123456789abcdef0123456789abcdef".parse().unwrap(),
+            mechanism: Mechanism::External,
+        }
+    );
+    assert_eq!(
+        io.written(),
+        [
+            &b"\x00AUTH EXTERNAL 31303030\r\n"[..],
+            b"NEGOTIATE_UNIX_FD\r\nBEGIN\r\n"
+        ]
+    );
+    assert_eq!(io.pending(), 0);
+
+    let mut io = LoopbackIo::new().respond(guid).respond(b"ERROR\r\n");
+    let negotiated = block_on(authenticate(&mut io, 1000, Options::new())).unwrap();
+    assert!(!negotiated.unix_fd);
+
+    let mut io = LoopbackIo::new().respond(guid);
+    let options = Options::new().with_unix_fd(false);
+    let negotiated = block_on(authenticate(&mut io, 1000, options)).unwrap();
+    assert!(!negotiated.unix_fd);
+    assert_eq!(io.written()[1], b"BEGIN\r\n");
 
-    Ok(())
+    let mut io = LoopbackIo::new().respond(b"REJECTED EXTERNAL\r\n");
+    assert!(matches!(
+        block_on(authenticate(&mut io, 1000, Options::new())),
+        Err(Error::AuthenticationFailed)
+    ));
+    let mut io = LoopbackIo::new();
+    assert!(matches!(
+        block_on(authenticate(&mut io, 1000, Options::new())),
+        Err(Error::Io(LoopbackError::Exhausted))
+    ));
 }
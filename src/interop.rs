@@ -0,0 +1,105 @@
+//! Cross-checks this crate's encoding against [`zvariant`].
+
+use alloc::{boxed::Box, vec::Vec};
+use std::collections::HashMap;
+
+use serde::Serialize;
+use zvariant::{DynamicType, NATIVE_ENDIAN, serialized::Context};
+
+use crate::{
+    Entry, Variant,
+    marshal::{self, Marshal},
+    strings,
+};
+
+/// A case of the matrix whose bytes differ between the two encoders.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch {
+    pub case: &'static str,
+    pub ours: Box<[u8]>,
+    pub zvariant: Vec<u8>,
+}
+
+/// Marshals `ours` with this crate and `theirs` with zvariant, both in native byte order, and
+/// compares the bytes. The two should be the same value in each library's representation.
+pub fn compare<T, U>(case: &'static str, ours: T, theirs: &U) -> Result<(), Mismatch>
+where
+    T: Marshal,
+    U: Serialize + DynamicType + ?Sized,
+{
+    let ours = marshal::marshal(ours);
+    let theirs = zvariant::to_bytes(Context::new_dbus(NATIVE_ENDIAN, 0), theirs)
+        .unwrap_or_else(|e| panic!("zvariant failed to encode {case}: {e}"));
+    if *ours != *theirs.bytes() {
+        Err(Mismatch {
+            case,
+            ours,
+            zvariant: theirs.bytes().to_vec(),
+        })?
+    }
+    Ok(())
+}
+
+/// Compares a fixed matrix of values covering every basic type, containers and the alignment
+/// cases between them, returning every mismatch.
+pub fn run_matrix() -> Vec<Mismatch> {
+    let path = "/org/example/Object";
+    let results = [
+        compare("y", 0xa5u8, &0xa5u8),
+        compare("b", true, &true),
+        compare("n", -2i16, &-2i16),
+        compare("q", 2u16, &2u16),
+        compare("i", -3i32, &-3i32),
+        compare("u", 3u32, &3u32),
+        compare("x", -4i64, &-4i64),
+        compare("t", 4u64, &4u64),
+        compare("d", 0.5f64, &0.5f64),
+        compare("s", "hello", "hello"),
+        compare(
+            "o",
+            strings::ObjectPath::from_str(path),
+            &zvariant::ObjectPath::try_from(path).unwrap(),
+        ),
+        compare(
+            "g",
+            strings::Signature::from_str("a{sv}"),
+            &zvariant::Signature::try_from("a{sv}").unwrap(),
+        ),
+        compare("at", &[1u64, 2][..], &[1u64, 2][..]),
+        compare("empty at", &[0u64; 0][..], &[0u64; 0][..]),
+        compare("ay", &[1u8, 2, 3][..], &[1u8, 2, 3][..]),
+        compare("as", &["a", "bc"][..], &["a", "bc"][..]),
+        compare(
+            "(ynt)",
+            crate::struct_new!(1u8, 2i16, 3u64),
+            &(1u8, 2i16, 3u64),
+        ),
+        compare(
+            "a(yu)",
+            &[crate::struct_new!(1u8, 2u32), crate::struct_new!(3u8, 4u32)][..],
+            &[(1u8, 2u32), (3u8, 4u32)][..],
+        ),
+        compare(
+            "a{su}",
+            &[Entry("k", 1u32)][..],
+            &HashMap::from([("k", 1u32)]),
+        ),
+        compare("v", Variant(5u32), &zvariant::Value::U32(5)),
+        compare(
+            "(yv)",
+            crate::struct_new!(1u8, Variant(2u64)),
+            &(1u8, zvariant::Value::U64(2)),
+        ),
+        compare(
+            "a{sv}",
+            &[Entry("k", Variant("v"))][..],
+            &HashMap::from([("k", zvariant::Value::from("v"))]),
+        ),
+    ];
+    results.into_iter().filter_map(Result::err).collect()
+}
+
+#[test]
+fn test_matrix() {
+    assert_eq!(run_matrix(), []);
+}
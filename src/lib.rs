@@ -1,24 +1,51 @@
+//! Marshalling and unmarshalling of D-Bus messages, usable without `std` or, with the `alloc`
+//! feature off, without allocating.
+//!
+//! # Toolchain
+//!
+//! The crate builds on nightly Rust only, and there is no feature flag for a stable build. The
+//! unstable features it enables carry the compile-time checks of its public API, so turning them
+//! off would take a runtime signature representation and non-const builders, which is a redesign
+//! rather than a flag:
+//!
+//! - `const_trait_impl`, `const_cmp`, `const_convert`, `const_try` and `const_destruct`: the
+//!   `const` trait impls and bounds that validate names such as
+//!   [`MemberName::from_static`], build [`Fields`] and compare signatures in const context, so
+//!   that an invalid literal or a duplicate [`method_table!`] entry fails compilation.
+//! - `freeze`: the `Freeze` bound on signature data, which lets
+//!   [`MultiSignature::SIGNATURE`](signature::MultiSignature::SIGNATURE) be promoted to a
+//!   `'static` for generic types.
+//! - `cast_maybe_uninit`: pointer casts for marshalling into uninitialised buffers.
+//! - `str_as_str`: `as_slice` on boxed slices, in tests.
 #![cfg_attr(not(test), no_std)]
 #![feature(
     cast_maybe_uninit,
-    const_array,
     const_cmp,
     const_convert,
     const_destruct,
     const_trait_impl,
     const_try,
+    freeze,
     str_as_str
 )]
 
 #[cfg(any(feature = "alloc", test))]
 pub extern crate alloc;
+#[cfg(all(any(feature = "interop", feature = "std"), not(test)))]
+extern crate std;
 
 use core::fmt::{self, Debug};
 
+#[cfg(feature = "proptest")]
+pub mod arbitrary;
 pub mod authentication;
+#[cfg(feature = "interop")]
+pub mod interop;
 pub mod marshal;
+pub mod metrics;
 pub mod signature;
 pub mod unmarshal;
+pub mod util;
 
 pub use message::*;
 pub use strings::*;
@@ -28,14 +55,7 @@ mod message;
 mod strings;
 mod types;
 
-const fn aligned(size: usize, align: usize) -> usize {
-    (size + align - 1) & !(align - 1)
-}
-
-#[allow(dead_code)]
-const fn align_padding(size: usize, align: usize) -> usize {
-    aligned(size, align) - size
-}
+use util::{align_padding, aligned};
 
 #[allow(dead_code)]
 fn show_bytes(xs: &[u8]) -> impl Debug {
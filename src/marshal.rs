@@ -1,15 +1,30 @@
 use core::{
+    fmt,
     mem::{self, MaybeUninit},
     num::NonZero,
 };
 
+use thiserror::Error;
+
 use crate::{
-    marshal::writer::*,
-    signature::{Node as _, Signature, SignatureProxy},
+    signature::{MultiSignature, Signature, SignatureProxy},
     strings,
     types::*,
 };
 
+/// The longest array the specification allows, in bytes.
+pub const MAX_ARRAY_LEN: usize = 1 << 26;
+/// The longest message the specification allows, in bytes, header included.
+pub const MAX_MESSAGE_LEN: usize = 1 << 27;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum Error {
+    #[error("buffer too small for {needed} bytes")]
+    BufferTooSmall { needed: usize },
+    #[error("length of {len} bytes exceeds the limit of {max}")]
+    TooLong { len: usize, max: usize },
+}
+
 pub trait Marshal: Clone {
     fn marshal<W: Write + ?Sized>(self, w: &mut W);
 }
@@ -25,7 +40,22 @@ macro_rules! impl_marshal {
     };
 }
 
-impl_marshal!(u8, i16, u16, i32, u32, i64, u64, f64);
+impl_marshal!(u8, i16, u16, i32, u32, i64, u64);
+
+/// The quiet NaN written in place of any NaN when [`Write::canonical_floats`] is set.
+pub const CANONICAL_NAN: f64 = f64::from_bits(0x7ff8_0000_0000_0000);
+
+impl Marshal for f64 {
+    fn marshal<W: Write + ?Sized>(self, w: &mut W) {
+        let x = if self.is_nan() && w.canonical_floats() {
+            CANONICAL_NAN
+        } else {
+            self
+        };
+        w.align_to(8);
+        w.write_bytes(&x.to_ne_bytes());
+    }
+}
 
 macro_rules! impl_non_zero {
     ($($t: ty),* $(,)?) => {
@@ -39,6 +69,109 @@ macro_rules! impl_non_zero {
 
 impl_non_zero!(u8, i16, u16, i32, u32, i64, u64);
 
+impl Marshal for UnixFd {
+    fn marshal<W: Write + ?Sized>(self, w: &mut W) {
+        w.note_unix_fd(self.0);
+        w.write(self.0)
+    }
+}
+
+impl Marshal for F32AsDouble {
+    fn marshal<W: Write + ?Sized>(self, w: &mut W) {
+        w.write(self.0 as f64)
+    }
+}
+
+macro_rules! impl_fixed_point {
+    ($($t: ty),* $(,)?) => {
+        $(impl<const SCALE: u32> Marshal for FixedPoint<$t, SCALE> {
+            fn marshal<W: Write + ?Sized>(self, w: &mut W) {
+                w.write(self.0 as f64 / SCALE as f64)
+            }
+        })*
+    };
+}
+
+impl_fixed_point!(i16, u16, i32, u32);
+
+impl<T: Clone, C: Codec<T>> Marshal for With<T, C>
+where
+    C::Wire: Marshal,
+{
+    fn marshal<W: Write + ?Sized>(self, w: &mut W) {
+        w.write(C::encode(&self.0))
+    }
+}
+
+#[cfg(feature = "uuid")]
+impl Marshal for UuidBytes {
+    fn marshal<W: Write + ?Sized>(self, w: &mut W) {
+        w.write(&self.0.as_bytes()[..])
+    }
+}
+
+#[cfg(feature = "uuid")]
+impl Marshal for UuidString {
+    fn marshal<W: Write + ?Sized>(self, w: &mut W) {
+        let mut buf = uuid::Uuid::encode_buffer();
+        w.write(&*self.0.hyphenated().encode_lower(&mut buf))
+    }
+}
+
+/// Writes the `Display` form of `value`, which must fit in 64 bytes, as a string.
+#[cfg(feature = "net")]
+fn write_display<W: Write + ?Sized>(w: &mut W, value: impl core::fmt::Display) {
+    use core::fmt::Write as _;
+
+    let mut buf = arrayvec::ArrayString::<64>::new();
+    write!(buf, "{value}").unwrap();
+    write_string_like(w, buf.as_bytes())
+}
+
+#[cfg(feature = "net")]
+fn write_ip_bytes<W: Write + ?Sized>(w: &mut W, addr: core::net::IpAddr) {
+    w.align_to(8);
+    match addr {
+        core::net::IpAddr::V4(addr) => {
+            w.write(AF_INET);
+            w.write(&addr.octets()[..]);
+        }
+        core::net::IpAddr::V6(addr) => {
+            w.write(AF_INET6);
+            w.write(&addr.octets()[..]);
+        }
+    }
+}
+
+#[cfg(feature = "net")]
+impl Marshal for IpAddrString {
+    fn marshal<W: Write + ?Sized>(self, w: &mut W) {
+        write_display(w, self.0)
+    }
+}
+
+#[cfg(feature = "net")]
+impl Marshal for IpAddrBytes {
+    fn marshal<W: Write + ?Sized>(self, w: &mut W) {
+        write_ip_bytes(w, self.0)
+    }
+}
+
+#[cfg(feature = "net")]
+impl Marshal for SocketAddrString {
+    fn marshal<W: Write + ?Sized>(self, w: &mut W) {
+        write_display(w, self.0)
+    }
+}
+
+#[cfg(feature = "net")]
+impl Marshal for SocketAddrBytes {
+    fn marshal<W: Write + ?Sized>(self, w: &mut W) {
+        write_ip_bytes(w, self.0.ip());
+        w.write(self.0.port())
+    }
+}
+
 impl<T: Marshal> Marshal for &T {
     fn marshal<W: Write + ?Sized>(self, w: &mut W) {
         w.write(self.clone())
@@ -74,6 +207,25 @@ impl Marshal for &strings::String {
     }
 }
 
+macro_rules! impl_name {
+    ($($t:ty),* $(,)?) => {
+        $(impl Marshal for &$t {
+            fn marshal<W: Write + ?Sized>(self, w: &mut W) {
+                write_string_like(w, self.as_bytes())
+            }
+        })*
+    };
+}
+
+impl_name!(
+    strings::BusName,
+    strings::UniqueName,
+    strings::WellKnownName,
+    strings::InterfaceName,
+    strings::MemberName,
+    strings::ErrorName,
+);
+
 impl Marshal for &strings::Signature {
     fn marshal<W: Write + ?Sized>(self, w: &mut W) {
         w.write_byte(self.as_bytes().len() as _);
@@ -90,7 +242,7 @@ impl Marshal for &strings::ObjectPath {
 
 impl<T: Marshal + Signature> Marshal for Variant<T> {
     fn marshal<W: Write + ?Sized>(self, w: &mut W) {
-        w.write(T::DATA.signature());
+        w.write(T::SIGNATURE);
         w.write(self.0)
     }
 }
@@ -120,21 +272,123 @@ impl<T: Marshal + StructConstructor> Marshal for Struct<T> {
     }
 }
 
-fn marshal_array_elements<T: Marshal, W: Write + ?Sized>(arr: &[T], w: &mut W) {
+fn marshal_array_elements<T, W: Write + ?Sized>(arr: &[T], w: &mut W)
+where
+    for<'b> &'b T: Marshal,
+{
     if let [x, xs @ ..] = arr {
         w.write(x);
-        marshal_array_elements(xs, w)
+        marshal_array_elements::<T, W>(xs, w)
     }
 }
 
-impl<T: Signature + Marshal> Marshal for &[T] {
+impl<T: Signature> Marshal for &[T]
+where
+    for<'b> &'b T: Marshal,
+{
     fn marshal<W: Write + ?Sized>(self, w: &mut W) {
-        let insert_pos = w.skip_aligned(4);
+        let insert_pos = w.skip_aligned::<u32>();
         w.align_to(T::ALIGNMENT);
         let begin = w.position();
-        marshal_array_elements(self, w);
+        marshal_array_elements::<T, W>(self, w);
         let len = w.position() - begin;
-        w.insert(len as u32, insert_pos);
+        w.insert_len(len, MAX_ARRAY_LEN, insert_pos);
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T: Signature> Marshal for &alloc::vec::Vec<T>
+where
+    for<'b> &'b T: Marshal,
+{
+    fn marshal<W: Write + ?Sized>(self, w: &mut W) {
+        w.write(self.as_slice())
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T: Signature> Marshal for &alloc::boxed::Box<[T]>
+where
+    for<'b> &'b T: Marshal,
+{
+    fn marshal<W: Write + ?Sized>(self, w: &mut W) {
+        w.write(&**self)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<K: Signature + Marshal, V: Signature + Marshal> Marshal
+    for &alloc::collections::BTreeMap<K, V>
+{
+    fn marshal<W: Write + ?Sized>(self, w: &mut W) {
+        w.write(Array(self.iter().map(|(k, v)| Entry(k, v))))
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K: Signature + Marshal, V: Signature + Marshal, S> Marshal
+    for &std::collections::HashMap<K, V, S>
+{
+    fn marshal<W: Write + ?Sized>(self, w: &mut W) {
+        w.write(Array(self.iter().map(|(k, v)| Entry(k, v))))
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Marshal for &crate::unmarshal::Value<'_> {
+    fn marshal<W: Write + ?Sized>(self, w: &mut W) {
+        use crate::{signature::code_alignment, unmarshal::Value};
+        match self {
+            Value::U8(x) => w.write(*x),
+            Value::Bool(x) => w.write(*x),
+            Value::I16(x) => w.write(*x),
+            Value::U16(x) => w.write(*x),
+            Value::I32(x) => w.write(*x),
+            Value::U32(x) => w.write(*x),
+            Value::UnixFd(x) => w.write(UnixFd(*x)),
+            Value::I64(x) => w.write(*x),
+            Value::U64(x) => w.write(*x),
+            Value::F64(x) => w.write(*x),
+            Value::String(x) => w.write(*x),
+            Value::Object(x) => w.write(*x),
+            Value::Signature(x) => w.write(*x),
+            Value::Array(element, values) => {
+                let alignment = element.first().and_then(|&x| code_alignment(x));
+                #[cfg(feature = "extensions")]
+                let alignment = alignment.or(match values.first() {
+                    Some(Value::Extension(extension, _)) => Some(extension.alignment),
+                    _ => None,
+                });
+                let insert_pos = w.skip_aligned::<u32>();
+                w.align_to(alignment.unwrap_or(1));
+                let begin = w.position();
+                for x in values {
+                    w.write(x);
+                }
+                let len = w.position() - begin;
+                w.insert_len(len, MAX_ARRAY_LEN, insert_pos);
+            }
+            Value::Struct(values) => {
+                w.align_to(8);
+                for x in values {
+                    w.write(x);
+                }
+            }
+            Value::Entry(entry) => {
+                w.align_to(8);
+                w.write(&entry.0);
+                w.write(&entry.1);
+            }
+            Value::Variant(signature, value) => {
+                w.write(*signature);
+                w.write(&**value);
+            }
+            #[cfg(feature = "extensions")]
+            Value::Extension(extension, bytes) => {
+                w.align_to(extension.alignment);
+                w.write_bytes(bytes);
+            }
+        }
     }
 }
 
@@ -155,14 +409,14 @@ where
     T: Marshal + Signature,
 {
     fn marshal<W: Write + ?Sized>(self, w: &mut W) {
-        let insert_pos = w.skip_aligned(4);
+        let insert_pos = w.skip_aligned::<u32>();
         w.align_to(T::ALIGNMENT);
         let begin = w.position();
         for x in self.0 {
             w.write(x);
         }
         let len = w.position() - begin;
-        w.insert(len as u32, insert_pos);
+        w.insert_len(len, MAX_ARRAY_LEN, insert_pos);
     }
 }
 
@@ -178,32 +432,196 @@ pub unsafe fn write_unchecked<Value: Marshal>(value: Value, ptr: *mut u8) {
     value.marshal(&mut writer);
 }
 
+/// Like [`calc_size`], but fails instead of panicking when a length exceeds the limits of the
+/// specification, such as [`MAX_ARRAY_LEN`].
+pub fn try_calc_size<Value: Marshal>(value: Value) -> Result<usize, Error> {
+    let mut measure = Measure::new();
+    value.marshal(&mut measure);
+    measure.finish()
+}
+
+/// The marshalled size shared by every value of `T`, or `None` if the size depends on the value,
+/// as with strings, arrays and variants. Sizes are counted from an 8-aligned offset, such as
+/// the start of a message body.
+pub const fn fixed_size<T: MultiSignature + ?Sized>() -> Option<usize> {
+    let signature = crate::signature::signature_bytes::<T>();
+    let mut size = 0;
+    let mut i = 0;
+    while i < signature.len() {
+        let (align, len) = match signature[i] {
+            b'y' => (1, 1),
+            b'n' | b'q' => (2, 2),
+            b'b' | b'i' | b'u' | b'h' => (4, 4),
+            b'x' | b't' | b'd' => (8, 8),
+            b'(' => (8, 0),
+            b')' => (1, 0),
+            _ => return None,
+        };
+        size = crate::aligned(size, align) + len;
+        i += 1;
+    }
+    Some(size)
+}
+
 pub fn write<Value: Marshal>(
     value: Value,
     buf: &mut [MaybeUninit<u8>],
-) -> Result<(&mut [u8], &mut [MaybeUninit<u8>]), ()> {
-    let size = calc_size(value.clone());
-    let (write, remaining) = buf.split_at_mut_checked(size).ok_or(())?;
+) -> Result<(&mut [u8], &mut [MaybeUninit<u8>]), Error> {
+    let size = try_calc_size(value.clone())?;
+    let (write, remaining) = buf
+        .split_at_mut_checked(size)
+        .ok_or(Error::BufferTooSmall { needed: size })?;
     unsafe {
-        write_unchecked(value, write.as_mut_ptr().cast_init());
+        let mut writer = Span::with_len(write.as_mut_ptr().cast_init(), size);
+        value.marshal(&mut writer);
         let write = write.assume_init_mut();
         Ok((write, remaining))
     }
 }
 
+/// Panics when a length exceeds the limits of the specification; [`try_marshal`] fails instead.
 #[cfg(any(feature = "alloc", test))]
 #[must_use]
 pub fn marshal<Value: Marshal>(value: Value) -> alloc::boxed::Box<[u8]> {
-    let mut buf = alloc::boxed::Box::new_uninit_slice(calc_size(value.clone()));
+    try_marshal(value).unwrap_or_else(|e| panic!("{e}"))
+}
 
-    unsafe {
-        write_unchecked(value, buf.as_mut_ptr() as _);
-        buf.assume_init()
+/// Like [`marshal`], but fails instead of panicking when a length exceeds the limits of the
+/// specification.
+#[cfg(any(feature = "alloc", test))]
+pub fn try_marshal<Value: Marshal>(value: Value) -> Result<alloc::boxed::Box<[u8]>, Error> {
+    let size = try_calc_size(value.clone())?;
+    let mut buf = alloc::boxed::Box::new_uninit_slice(size);
+
+    let mut writer = Span::with_len(buf.as_mut_ptr().cast_init(), size);
+    value.marshal(&mut writer);
+    Ok(unsafe { buf.assume_init() })
+}
+
+/// Like [`try_marshal`], but also fails if the result is longer than the message limit of
+/// `limits`, e.g. one negotiated with a broker.
+#[cfg(any(feature = "alloc", test))]
+pub fn try_marshal_limited<Value: Marshal>(
+    value: Value,
+    limits: &crate::unmarshal::Limits<'_>,
+) -> Result<alloc::boxed::Box<[u8]>, Error> {
+    let len = try_calc_size(value.clone())?;
+    if len > limits.max_message_len {
+        Err(Error::TooLong {
+            len,
+            max: limits.max_message_len,
+        })?
     }
+    try_marshal(value)
+}
+
+/// Like [`marshal`], but reports the size and padding of the result to `metrics`.
+#[cfg(any(feature = "alloc", test))]
+#[must_use]
+pub fn marshal_with_metrics<Value: Marshal>(
+    value: Value,
+    metrics: &dyn crate::metrics::Metrics,
+) -> alloc::boxed::Box<[u8]> {
+    let mut measure = Measure::new();
+    value.clone().marshal(&mut measure);
+    let res = marshal(value);
+    metrics.message_encoded(res.len());
+    metrics.padding(measure.padding());
+    res
+}
+
+/// Like [`marshal`], but writes every NaN as [`CANONICAL_NAN`].
+#[cfg(any(feature = "alloc", test))]
+#[must_use]
+pub fn marshal_canonical<Value: Marshal>(value: Value) -> alloc::boxed::Box<[u8]> {
+    let size = try_calc_size(value.clone()).unwrap_or_else(|e| panic!("{e}"));
+    let mut buf = alloc::boxed::Box::new_uninit_slice(size);
+
+    let mut writer = Span::with_len(buf.as_mut_ptr().cast_init(), size).with_canonical_floats(true);
+    value.marshal(&mut writer);
+    unsafe { buf.assume_init() }
+}
+
+/// Fails the test unless `value` marshals to exactly `expected`, printing both buffers as hex
+/// rows of 8 bytes with their offsets and the first differing byte in brackets.
+#[macro_export]
+macro_rules! assert_marshal_eq {
+    ($value:expr, $expected:expr $(,)?) => {
+        $crate::marshal::assert_bytes_eq(&$crate::marshal::marshal($value), &$expected[..])
+    };
 }
 
-pub use writer::Write;
+#[doc(hidden)]
+#[track_caller]
+pub fn assert_bytes_eq(actual: &[u8], expected: &[u8]) {
+    let diff = actual.iter().zip(expected).position(|(x, y)| x != y);
+    let Some(diff) =
+        diff.or((actual.len() != expected.len()).then(|| actual.len().min(expected.len())))
+    else {
+        return;
+    };
+    panic!(
+        "marshalled bytes differ at offset {diff:#x} ({} bytes, expected {})\n  actual:\n{}expected:\n{}",
+        actual.len(),
+        expected.len(),
+        hex_dump(actual, diff),
+        hex_dump(expected, diff),
+    );
+}
 
+/// Rows of 8 bytes, the largest D-Bus alignment, so padding lines up across rows.
+fn hex_dump(xs: &[u8], mark: usize) -> impl fmt::Display {
+    fmt::from_fn(move |f| {
+        for (row, chunk) in xs.chunks(8).enumerate() {
+            write!(f, "{:6x}:", row * 8)?;
+            for (i, x) in chunk.iter().enumerate() {
+                if row * 8 + i == mark {
+                    write!(f, "[{x:02x}]")?;
+                } else {
+                    write!(f, " {x:02x} ")?;
+                }
+            }
+            writeln!(
+                f,
+                "{:pad$} {:?}",
+                "",
+                crate::show_bytes(chunk),
+                pad = 4 * (8 - chunk.len())
+            )?;
+        }
+        Ok(())
+    })
+}
+
+/// Checks that a (typically hand-written) [`Marshal`] impl writes exactly as many bytes as
+/// [`calc_size`] predicts.
+///
+/// The value is marshalled into a growable buffer, so an impl that writes too much cannot
+/// overrun anything; panics if the written length differs. Returns the marshalled bytes.
+#[cfg(any(feature = "alloc", test))]
+#[track_caller]
+pub fn verify_marshal_impl<Value: Marshal>(value: Value) -> alloc::boxed::Box<[u8]> {
+    let size = calc_size(value.clone());
+    let mut buf = alloc::vec::Vec::with_capacity(size);
+    value.marshal(&mut buf);
+    let written = buf.len();
+    assert_eq!(
+        written, size,
+        "marshal wrote {written} bytes but calc_size predicted {size}"
+    );
+    buf.into_boxed_slice()
+}
+
+#[cfg(feature = "alloc")]
+pub use pool::*;
+pub use scatter::*;
+pub use transcode::*;
+pub use writer::{Measure, Reserved, Span, Write};
+
+#[cfg(feature = "alloc")]
+mod pool;
+mod scatter;
+mod transcode;
 mod writer;
 
 #[cfg(target_endian = "little")]
@@ -248,3 +666,185 @@ fn test_marshal() {
         24,
     ]);
 }
+
+#[test]
+fn test_verify_marshal_impl() {
+    verify_marshal_impl(&[Entry(2i32, 23u8), Entry(3i32, 24u8)][..]);
+    verify_marshal_impl(crate::struct_new!(1u8, Variant(2u64), "hello"));
+}
+
+#[test]
+#[should_panic = "marshal wrote 1028 bytes but calc_size predicted 4"]
+fn test_verify_marshal_impl_overrun() {
+    use core::sync::atomic::{AtomicBool, Ordering};
+
+    /// Writes far more once measured than it was measured at.
+    #[derive(Clone)]
+    struct Overrun;
+    static MEASURED: AtomicBool = AtomicBool::new(false);
+    impl Marshal for Overrun {
+        fn marshal<W: Write + ?Sized>(self, w: &mut W) {
+            w.write(0u32);
+            if MEASURED.swap(true, Ordering::Relaxed) {
+                w.write_bytes(&[0; 1024]);
+            }
+        }
+    }
+    verify_marshal_impl(Overrun);
+}
+
+#[test]
+fn test_length_limits() {
+    let at_limit = Array(core::iter::repeat_n(0u64, MAX_ARRAY_LEN / 8));
+    assert_eq!(try_calc_size(at_limit.clone()), Ok(4 + 4 + MAX_ARRAY_LEN));
+    let over = Array(core::iter::repeat_n(0u64, MAX_ARRAY_LEN / 8 + 1));
+    let too_long = Error::TooLong {
+        len: MAX_ARRAY_LEN + 8,
+        max: MAX_ARRAY_LEN,
+    };
+    assert_eq!(try_calc_size(over.clone()), Err(too_long));
+    assert_eq!(try_marshal(crate::struct_new!(1u8, over)), Err(too_long));
+
+    let mut buf = [MaybeUninit::uninit(); 4];
+    assert_eq!(
+        write(7u64, &mut buf).err(),
+        Some(Error::BufferTooSmall { needed: 8 })
+    );
+}
+
+#[cfg(target_endian = "little")]
+#[test]
+fn test_array_padding() {
+    use crate::unmarshal::{ArrayIter, Iter, Reader, Token};
+
+    // The padding between the length and the first 8-aligned element does not count toward
+    // the length, and is there even when the array is empty.
+    let xs: &[u64] = &[1, 2];
+    let empty: &[u64] = &[];
+    for elements in [xs, empty] {
+        for with_prefix in [false, true] {
+            let data = if with_prefix {
+                marshal(crate::multiple_new!(7u32, elements))
+            } else {
+                marshal(crate::multiple_new!(elements))
+            };
+            let len_pos = if with_prefix { 4 } else { 0 };
+            let mut expected = alloc::vec::Vec::new();
+            if with_prefix {
+                expected.extend_from_slice(&7u32.to_le_bytes());
+            }
+            expected.extend_from_slice(&(8 * elements.len() as u32).to_le_bytes());
+            expected.resize(8, 0);
+            elements
+                .iter()
+                .for_each(|x| expected.extend_from_slice(&x.to_le_bytes()));
+            assert_eq!(data.as_slice(), expected);
+
+            let reader = || {
+                let mut r = Reader::new(&data);
+                r.read_bytes(len_pos).unwrap();
+                r
+            };
+            let mut r = reader();
+            let iter: ArrayIter<u64> = r.read().unwrap();
+            assert_eq!(iter.len(), Ok(Some(elements.len())));
+            assert_eq!(
+                iter.map(Result::unwrap).collect::<alloc::vec::Vec<_>>(),
+                elements
+            );
+            assert!(r.remaining().is_empty());
+
+            let mut r = reader();
+            assert_eq!(r.skip(b"at"), Ok(&b""[..]));
+            assert!(r.remaining().is_empty());
+
+            let signature = strings::Signature::from_str(if with_prefix { "uat" } else { "at" });
+            let Some(Ok(Token::Array(array))) = Iter::new(signature, &data).unwrap().last() else {
+                panic!()
+            };
+            assert_eq!(array.bytes().len(), 8 * elements.len());
+            assert_eq!(array.iter().count(), elements.len());
+        }
+    }
+}
+
+#[test]
+fn test_empty_arrays() {
+    use crate::unmarshal::{ArrayIter, Error, Iter, Reader, Token};
+
+    crate::define_dict! {
+        #[derive(Clone, Copy)]
+        struct Dict(DictEntry, DictKey, DictValue) {
+            x: u8,
+        }
+    }
+    let empty_struct: &[crate::struct_type!(u8, u8)] = &[];
+    let empty_i64: &[i64] = &[];
+    let data = marshal(crate::multiple_new!(
+        empty_struct,
+        Dict { x: None },
+        empty_i64
+    ));
+    assert_eq!(data.as_slice(), [0; 24]);
+
+    type Body<'a> = crate::multiple_type!(
+        ArrayIter<'a, crate::struct_type!(u8, u8)>,
+        Dict,
+        ArrayIter<'a, i64>
+    );
+    let mut r = Reader::new(&data);
+    let crate::multiple_match!(mut a, _, mut c) = r.read::<Body>().unwrap();
+    assert!(a.next().is_none() && c.next().is_none());
+    assert!(r.remaining().is_empty());
+    assert_eq!(
+        Reader::new(&data[..20]).read::<Body>().err(),
+        Some(Error::NotEnoughData)
+    );
+
+    let signature = strings::Signature::from_str("a(yy)a{sv}ax");
+    let tokens = Iter::new(signature, &data).unwrap();
+    for token in tokens.clone() {
+        let Ok(Token::Array(array)) = token else {
+            panic!()
+        };
+        assert!(array.bytes().is_empty() && array.iter().next().is_none());
+    }
+    assert_eq!(tokens.count(), 3);
+    let truncated = Iter::new(signature, &data[..20]).unwrap();
+    assert_eq!(truncated.last(), Some(Err(Error::NotEnoughData)));
+}
+
+#[test]
+fn test_fixed_size() {
+    type Header = crate::multiple_type!(u8, u32, Struct<crate::multiple_type!(u16, f64)>, bool);
+    const SIZE: Option<usize> = fixed_size::<Header>();
+    assert_eq!(SIZE, Some(28));
+    let value = crate::multiple_new!(1u8, 2u32, Struct(crate::multiple_new!(3u16, 4.0)), true);
+    assert_eq!(SIZE, Some(calc_size(value)));
+    assert_eq!(fixed_size::<crate::multiple_type!(u8, &str)>(), None);
+    assert_eq!(fixed_size::<[u32]>(), None);
+    assert_eq!(
+        <Struct<crate::multiple_type!(u16, f64)>>::FIXED_SIZE,
+        Some(16)
+    );
+    assert_eq!(<&str>::FIXED_SIZE, None);
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_owned_containers() {
+    use alloc::{boxed::Box, collections::BTreeMap, vec};
+
+    let numbers = vec![1u32, 2, 3];
+    let boxed: Box<[u32]> = numbers.clone().into();
+    crate::assert_signature_eq!(&alloc::vec::Vec<u32>, "au");
+    assert_eq!(marshal(&numbers), marshal(&numbers[..]));
+    assert_eq!(marshal(&boxed), marshal(&numbers[..]));
+
+    let map = BTreeMap::from([("b", 2u8), ("a", 1)]);
+    crate::assert_signature_eq!(&BTreeMap<&str, u8>, "a{sy}");
+    assert_eq!(
+        marshal(&map),
+        marshal(&[Entry("a", 1u8), Entry("b", 2)][..])
+    );
+}
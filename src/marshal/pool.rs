@@ -0,0 +1,166 @@
+use alloc::{boxed::Box, vec::Vec};
+use core::{
+    cell::RefCell,
+    mem,
+    ops::{Deref, DerefMut},
+};
+
+use crate::marshal::{Error, Marshal, Span, try_calc_size};
+
+/// Where [`marshal_in`] and [`Serial`](crate::Serial) get the buffers they marshal into.
+pub trait Buffers {
+    type Buffer: DerefMut<Target = [u8]>;
+    /// A buffer of exactly `len` bytes, with unspecified contents. Marshalling panics if it has
+    /// any other length, as the bytes past the message would be sent with it.
+    fn allocate(&self, len: usize) -> Self::Buffer;
+}
+
+/// Allocates a fresh [`Box`] every time.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Boxed;
+
+impl Buffers for Boxed {
+    type Buffer = Box<[u8]>;
+
+    fn allocate(&self, len: usize) -> Box<[u8]> {
+        alloc::vec![0; len].into_boxed_slice()
+    }
+}
+
+/// Recycles buffers: each [`Pooled`] goes back to the pool when dropped and is handed out again
+/// by the next allocation, so a steady stream of messages stops hitting the allocator.
+#[derive(Debug, Default)]
+pub struct BufferPool {
+    free: RefCell<Vec<Vec<u8>>>,
+    max_free: usize,
+}
+
+impl BufferPool {
+    /// A pool that keeps at most `max_free` returned buffers, dropping any beyond that.
+    pub const fn new(max_free: usize) -> Self {
+        Self {
+            free: RefCell::new(Vec::new()),
+            max_free,
+        }
+    }
+    /// The number of buffers waiting to be reused.
+    pub fn free_len(&self) -> usize {
+        self.free.borrow().len()
+    }
+}
+
+impl<'p> Buffers for &'p BufferPool {
+    type Buffer = Pooled<'p>;
+
+    fn allocate(&self, len: usize) -> Pooled<'p> {
+        let mut buf = self.free.borrow_mut().pop().unwrap_or_default();
+        buf.clear();
+        buf.resize(len, 0);
+        Pooled { buf, pool: self }
+    }
+}
+
+/// A buffer borrowed from a [`BufferPool`].
+#[derive(Debug)]
+pub struct Pooled<'p> {
+    buf: Vec<u8>,
+    pool: &'p BufferPool,
+}
+
+impl Deref for Pooled<'_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.buf
+    }
+}
+
+impl DerefMut for Pooled<'_> {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        &mut self.buf
+    }
+}
+
+impl Drop for Pooled<'_> {
+    fn drop(&mut self) {
+        let mut free = self.pool.free.borrow_mut();
+        if free.len() < self.pool.max_free {
+            free.push(mem::take(&mut self.buf));
+        }
+    }
+}
+
+/// Like [`try_marshal`](crate::marshal::try_marshal), taking the buffer from `buffers`.
+pub fn marshal_in<B: Buffers, Value: Marshal>(
+    value: Value,
+    buffers: &B,
+) -> Result<B::Buffer, Error> {
+    let size = try_calc_size(value.clone())?;
+    let mut buf = buffers.allocate(size);
+    let bytes = &mut *buf;
+    // `Buffers` is a safe trait, so a short buffer must not be written past, and a long one
+    // would carry garbage after the message.
+    assert!(
+        bytes.len() == size,
+        "allocate returned {} bytes for {size}",
+        bytes.len()
+    );
+    let mut writer = Span::with_len(bytes.as_mut_ptr(), size);
+    value.marshal(&mut writer);
+    Ok(buf)
+}
+
+#[test]
+#[should_panic = "allocate returned 0 bytes"]
+fn test_short_buffer() {
+    struct Short;
+    impl Buffers for Short {
+        type Buffer = Box<[u8]>;
+
+        fn allocate(&self, _: usize) -> Box<[u8]> {
+            Box::new([])
+        }
+    }
+    let _ = marshal_in(1u64, &Short);
+}
+
+#[test]
+#[should_panic = "allocate returned 9 bytes for 8"]
+fn test_long_buffer() {
+    struct Long;
+    impl Buffers for Long {
+        type Buffer = Box<[u8]>;
+
+        fn allocate(&self, len: usize) -> Box<[u8]> {
+            alloc::vec![0; len + 1].into_boxed_slice()
+        }
+    }
+    let _ = marshal_in(1u64, &Long);
+}
+
+#[test]
+fn test_pool() {
+    use crate::marshal::{Array, MAX_ARRAY_LEN};
+
+    let pool = BufferPool::new(1);
+    let a = marshal_in(&[1u32, 2][..], &&pool).unwrap();
+    let ptr = a.as_ptr();
+    assert_eq!(*a, *crate::marshal::marshal(&[1u32, 2][..]));
+    drop(a);
+    assert_eq!(pool.free_len(), 1);
+
+    let b = marshal_in(3u8, &&pool).unwrap();
+    assert_eq!((b.as_ptr(), &*b), (ptr, &[3][..]));
+    let c = marshal_in(4u8, &&pool).unwrap();
+    drop((b, c));
+    assert_eq!(pool.free_len(), 1);
+
+    let over = Array(core::iter::repeat_n(0u64, MAX_ARRAY_LEN / 8 + 1));
+    assert_eq!(
+        marshal_in(over, &&pool).err(),
+        Some(Error::TooLong {
+            len: MAX_ARRAY_LEN + 8,
+            max: MAX_ARRAY_LEN
+        })
+    );
+}
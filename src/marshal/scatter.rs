@@ -0,0 +1,128 @@
+use arrayvec::ArrayVec;
+
+use crate::{
+    marshal::{Marshal, Write, transcode},
+    signature::{MAX_SIGNATURE_LEN, code_alignment, max_alignment},
+    strings,
+    unmarshal::{self, Error, Iter},
+};
+
+/// Arguments that are already marshalled, such as a body being forwarded to another
+/// connection, together with their signature.
+///
+/// `bytes` must be marshalled as if starting at an 8-aligned offset, as message bodies are.
+#[derive(Debug, Clone, Copy)]
+pub struct Premarshalled<'a> {
+    signature: &'a strings::Signature,
+    bytes: &'a [u8],
+}
+
+impl<'a> Premarshalled<'a> {
+    /// Checks that `bytes` holds exactly the values of `signature`.
+    pub fn new(signature: &'a strings::Signature, bytes: &'a [u8]) -> unmarshal::Result<Self> {
+        let mut iter = Iter::new(signature, bytes)?;
+        iter.validate()?;
+        if !iter.reader().remaining().is_empty() {
+            Err(Error::InvalidArgs)?
+        }
+        Ok(Self { signature, bytes })
+    }
+    pub fn signature(&self) -> &'a strings::Signature {
+        self.signature
+    }
+    pub fn bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+}
+
+/// Marshals a sequence of [`Premarshalled`] blobs one after another, as the arguments of one
+/// message body.
+///
+/// Each blob is copied verbatim where the writer's position keeps its padding valid, and is
+/// re-encoded with the padding fixed up where it does not, so callers can concatenate blobs
+/// without knowing the offsets they will land at.
+#[derive(Debug, Clone, Copy)]
+pub struct Scatter<'a>(pub &'a [Premarshalled<'a>]);
+
+impl Scatter<'_> {
+    /// The signature of the whole body, for the header, or `None` if it would be too long.
+    pub fn signature(&self) -> Option<ArrayVec<u8, MAX_SIGNATURE_LEN>> {
+        let mut signature = ArrayVec::new();
+        for x in self.0 {
+            signature
+                .try_extend_from_slice(x.signature.as_bytes())
+                .ok()?;
+        }
+        Some(signature)
+    }
+}
+
+impl Marshal for Scatter<'_> {
+    fn marshal<W: Write + ?Sized>(self, w: &mut W) {
+        for x in self.0 {
+            let Some(&first) = x.signature.as_bytes().first() else {
+                continue;
+            };
+            w.align_to(code_alignment(first).unwrap_or(1));
+            if w.position().is_multiple_of(max_alignment(x.signature)) {
+                w.write_bytes(x.bytes);
+            } else {
+                // `Premarshalled::new` decoded every value, so re-encoding them cannot fail.
+                let iter = Iter::new(x.signature, x.bytes).unwrap();
+                transcode(iter, w, &mut |token| token).unwrap();
+            }
+        }
+    }
+}
+
+#[test]
+fn test_scatter() {
+    use crate::{Variant, marshal::marshal, struct_new};
+
+    let signature = strings::Signature::from_str;
+    let y = marshal(7u8);
+    let t = marshal(0x0102_0304_0506_0708u64);
+    let s = marshal(crate::multiple_new!("hi", 3u16));
+    let st = marshal(crate::multiple_new!(struct_new!(1u8, 2u32), Variant(4u64)));
+    let v = marshal(Variant(9u64));
+    let parts = [
+        Premarshalled::new(signature("y"), &y).unwrap(),
+        Premarshalled::new(signature("t"), &t).unwrap(),
+        Premarshalled::new(signature("y"), &y).unwrap(),
+        Premarshalled::new(signature("sq"), &s).unwrap(),
+        Premarshalled::new(signature("(yu)v"), &st).unwrap(),
+        Premarshalled::new(signature(""), &[]).unwrap(),
+        Premarshalled::new(signature("y"), &y).unwrap(),
+        Premarshalled::new(signature("v"), &v).unwrap(),
+    ];
+    let scatter = Scatter(&parts);
+    assert_eq!(&scatter.signature().unwrap()[..], b"ytysq(yu)vyv");
+    let expected = marshal(crate::multiple_new!(
+        7u8,
+        0x0102_0304_0506_0708u64,
+        7u8,
+        "hi",
+        3u16,
+        struct_new!(1u8, 2u32),
+        Variant(4u64),
+        7u8,
+        Variant(9u64)
+    ));
+    assert_eq!(marshal(scatter), expected);
+
+    assert_eq!(
+        Premarshalled::new(signature("u"), &y).err(),
+        Some(Error::NotEnoughData)
+    );
+    assert_eq!(
+        Premarshalled::new(signature("y"), &t).err(),
+        Some(Error::InvalidArgs)
+    );
+    // The array length still covers the element, but its string runs past it.
+    let mut strings = marshal(&["ab"][..]);
+    strings[4] = 0xff;
+    assert_eq!(
+        Premarshalled::new(signature("as"), &strings).err(),
+        Some(Error::NotEnoughData)
+    );
+}
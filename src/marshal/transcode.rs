@@ -0,0 +1,125 @@
+use core::mem;
+
+use crate::{
+    marshal::{MAX_ARRAY_LEN, Write},
+    unmarshal::{self, Error, Iter, Token},
+};
+
+/// Re-marshals the tokens of `iter` into `w`, passing each one through `edit` first.
+///
+/// `edit` also sees the tokens inside arrays, in wire order. It may change values but not
+/// types: returning a token of another kind, or changing the signature of an array or variant,
+/// fails with [`Error::InvalidArgs`]. Array lengths are recomputed, so strings may change length.
+pub fn transcode<'a, W: Write + ?Sized>(
+    iter: Iter<'a>,
+    w: &mut W,
+    edit: &mut impl FnMut(Token<'a>) -> Token<'a>,
+) -> unmarshal::Result<()> {
+    for token in iter {
+        let token = token?;
+        let edited = edit(token);
+        if mem::discriminant(&edited) != mem::discriminant(&token) {
+            Err(Error::InvalidArgs)?
+        }
+        match (token, edited) {
+            (_, Token::U8(x)) => w.write(x),
+            (_, Token::Bool(x)) => w.write(x),
+            (_, Token::I16(x)) => w.write(x),
+            (_, Token::U16(x)) => w.write(x),
+            (_, Token::I32(x)) => w.write(x),
+            (_, Token::U32(x)) => w.write(x),
+            (_, Token::UnixFd(x)) => w.write(crate::UnixFd(x)),
+            (_, Token::I64(x)) => w.write(x),
+            (_, Token::U64(x)) => w.write(x),
+            (_, Token::F64(x)) => w.write(x),
+            (_, Token::String(x)) => w.write(x),
+            (_, Token::Object(x)) => w.write(x),
+            (_, Token::Signature(x)) => w.write(x),
+            (Token::Array(old), Token::Array(new)) => {
+                if old.signature() != new.signature() {
+                    Err(Error::InvalidArgs)?
+                }
+                let len_pos = w.skip_aligned::<u32>();
+                w.align_to(new.alignment());
+                let begin = w.position();
+                transcode(new.iter(), w, edit)?;
+                let len = w.position() - begin;
+                w.insert_len(len, MAX_ARRAY_LEN, len_pos);
+            }
+            (Token::VariantOpen(old), Token::VariantOpen(new)) => {
+                if old != new {
+                    Err(Error::InvalidArgs)?
+                }
+                w.write(new);
+            }
+            #[cfg(feature = "extensions")]
+            (Token::Extension(old, _), Token::Extension(new, bytes)) => {
+                if old != new || bytes.len() != new.size {
+                    Err(Error::InvalidArgs)?
+                }
+                w.align_to(new.alignment);
+                w.write_bytes(bytes);
+            }
+            (_, Token::StructOpen | Token::EntryOpen) => w.align_to(8),
+            (_, Token::StructClose | Token::EntryClose | Token::VariantClose) => {}
+            _ => unreachable!(),
+        }
+    }
+    Ok(())
+}
+
+/// Like [`transcode`], producing a freshly allocated body.
+#[cfg(feature = "alloc")]
+pub fn transcode_body<'a>(
+    iter: Iter<'a>,
+    mut edit: impl FnMut(Token<'a>) -> Token<'a>,
+) -> unmarshal::Result<alloc::boxed::Box<[u8]>> {
+    let mut body = alloc::vec::Vec::new();
+    transcode(iter, &mut body, &mut edit)?;
+    Ok(body.into_boxed_slice())
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_transcode() {
+    use crate::{Entry, Variant, marshal::marshal, strings};
+
+    let body = |password| {
+        marshal(crate::struct_new!(
+            "user",
+            &[
+                Entry("password", Variant(password)),
+                Entry("x", Variant("y"))
+            ][..],
+        ))
+    };
+    let data = body("hunter2");
+    let iter = Iter::new(strings::Signature::from_str("(sa{sv})"), &data).unwrap();
+    let redacted = transcode_body(iter.clone(), |token| match token {
+        Token::String(x) if x.as_bytes() == b"hunter2" => {
+            Token::String(strings::String::from_str("***"))
+        }
+        x => x,
+    })
+    .unwrap();
+    assert_eq!(redacted, body("***"));
+    assert_eq!(transcode_body(iter.clone(), |x| x).unwrap(), data);
+    // Each token is edited once, so an edit that changes its mind cannot outgrow the body.
+    let mut calls = 0;
+    let edited = transcode_body(iter.clone(), |token| match token {
+        Token::String(x) if x.as_bytes() == b"hunter2" => {
+            calls += 1;
+            Token::String(strings::String::from_str(["***", "*******"][calls - 1]))
+        }
+        x => x,
+    })
+    .unwrap();
+    assert_eq!((edited, calls), (body("***"), 1));
+    assert_eq!(
+        transcode_body(iter, |token| match token {
+            Token::String(_) => Token::U32(1),
+            x => x,
+        }),
+        Err(Error::InvalidArgs)
+    );
+}
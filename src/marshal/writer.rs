@@ -1,6 +1,39 @@
-use core::ptr;
+use core::{marker::PhantomData, ptr};
 
-use crate::marshal::Marshal;
+use crate::{
+    marshal::{Error, Marshal},
+    signature::Signature,
+};
+
+/// A slot reserved by [`Write::skip_aligned`], to be filled later with [`Write::insert`].
+///
+/// The slot remembers the type it was reserved for and is as long as its marshalled form, so
+/// only a value of that type can be written back into it.
+#[must_use]
+#[derive(Debug)]
+pub struct Reserved<T> {
+    pos: usize,
+    size: usize,
+    marker: PhantomData<T>,
+}
+
+impl<T> Reserved<T> {
+    pub const fn position(&self) -> usize {
+        self.pos
+    }
+    pub const fn size(&self) -> usize {
+        self.size
+    }
+}
+
+impl<T: Signature> Reserved<T> {
+    /// The marshalled size of every `T`. Reserving a type whose size depends on the value, such
+    /// as a string, fails to compile.
+    const SIZE: usize = match T::FIXED_SIZE {
+        Some(size) => size,
+        None => panic!("only fixed-size types can be reserved"),
+    };
+}
 
 pub unsafe trait Write {
     fn position(&self) -> usize;
@@ -12,11 +45,32 @@ pub unsafe trait Write {
         self.seek(padding);
     }
 
-    fn skip_aligned(&mut self, n: usize) -> usize {
-        self.align_to(n);
-        let res = self.position();
-        self.seek(n);
-        res
+    /// Reserves room for a `T` where it would be written next, to be filled with
+    /// [`Write::insert`]. Only types of a fixed size can be reserved:
+    ///
+    /// ```compile_fail
+    /// use dbus_marshal::marshal::Write;
+    ///
+    /// let mut size = 0usize;
+    /// let _ = size.skip_aligned::<&str>();
+    /// ```
+    fn skip_aligned<T: Signature>(&mut self) -> Reserved<T> {
+        self.reserve(T::ALIGNMENT)
+    }
+
+    /// Reserves room for a `T` at the next multiple of `align`, for formats whose backpatched
+    /// values need more than D-Bus alignment. The slot still honours `T`'s own alignment, and
+    /// is as long as [`Write::skip_aligned`] would make it.
+    fn reserve<T: Signature>(&mut self, align: usize) -> Reserved<T> {
+        self.align_to(align.max(T::ALIGNMENT));
+        let pos = self.position();
+        let size = Reserved::<T>::SIZE;
+        self.seek(size);
+        Reserved {
+            pos,
+            size,
+            marker: PhantomData,
+        }
     }
 
     fn write_bytes(&mut self, bytes: &[u8]);
@@ -27,7 +81,30 @@ pub unsafe trait Write {
         v.marshal(self);
     }
 
-    fn insert<T: Marshal>(&mut self, v: T, pos: usize);
+    fn insert<T: Marshal>(&mut self, v: T, reserved: Reserved<T>);
+
+    /// Backpatches the byte length `len` of an array or message part into `reserved`. `max` is
+    /// the limit the specification puts on it, which [`Measure`] records as an error. Writers
+    /// panic past it instead, which the marshalling functions avoid by measuring first.
+    fn insert_len(&mut self, len: usize, max: usize, reserved: Reserved<u32>) {
+        assert!(
+            len <= max,
+            "length of {len} bytes exceeds the limit of {max}"
+        );
+        // Every limit fits in 32 bits.
+        self.insert(len as u32, reserved)
+    }
+
+    /// Whether every NaN is to be written as [`CANONICAL_NAN`](crate::marshal::CANONICAL_NAN).
+    fn canonical_floats(&self) -> bool {
+        false
+    }
+
+    /// Called as a [`UnixFd`](crate::UnixFd) argument referring to fd `index` is written, so
+    /// that [`Measure`] can count the fds a body needs attached.
+    fn note_unix_fd(&mut self, index: u32) {
+        let _ = index;
+    }
 }
 
 unsafe impl Write for usize {
@@ -47,12 +124,88 @@ unsafe impl Write for usize {
         *self += 1;
     }
 
-    fn insert<T: Marshal>(&mut self, _: T, _: usize) {}
+    fn insert<T: Marshal>(&mut self, _: T, _: Reserved<T>) {}
+}
+
+/// Counts bytes like the `usize` writer behind [`calc_size`](crate::marshal::calc_size), and
+/// records the first length over its limit instead of panicking.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Measure {
+    size: usize,
+    padding: usize,
+    unix_fds: u32,
+    error: Option<Error>,
+}
+
+impl Measure {
+    pub const fn new() -> Self {
+        Self {
+            size: 0,
+            padding: 0,
+            unix_fds: 0,
+            error: None,
+        }
+    }
+    /// The number of those bytes that are alignment padding.
+    pub const fn padding(&self) -> usize {
+        self.padding
+    }
+    /// The number of unix fds the bytes refer to: one past the highest fd index written, which
+    /// is the number of fd arguments when each has its own fd.
+    pub const fn unix_fds(&self) -> u32 {
+        self.unix_fds
+    }
+    /// The number of bytes counted, or the first length that exceeded its limit.
+    pub const fn finish(self) -> Result<usize, Error> {
+        match self.error {
+            Some(e) => Err(e),
+            None => Ok(self.size),
+        }
+    }
+}
+
+unsafe impl Write for Measure {
+    fn position(&self) -> usize {
+        self.size
+    }
+
+    fn seek(&mut self, n: usize) {
+        self.size += n;
+    }
+
+    fn align_to(&mut self, n: usize) {
+        let padding = crate::align_padding(self.size, n);
+        self.padding += padding;
+        self.size += padding;
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        self.size += bytes.len();
+    }
+
+    fn write_byte(&mut self, _: u8) {
+        self.size += 1;
+    }
+
+    fn insert<T: Marshal>(&mut self, _: T, _: Reserved<T>) {}
+
+    fn insert_len(&mut self, len: usize, max: usize, _: Reserved<u32>) {
+        if len > max && self.error.is_none() {
+            self.error = Some(Error::TooLong { len, max });
+        }
+    }
+
+    fn note_unix_fd(&mut self, index: u32) {
+        self.unix_fds = self.unix_fds.max(index.saturating_add(1));
+    }
 }
 
 pub struct Span {
     begin: *mut u8,
     cursor: *mut u8,
+    /// One past the last writable byte, or null when unbounded.
+    end: *mut u8,
+    canonical_floats: bool,
 }
 
 impl Span {
@@ -60,47 +213,154 @@ impl Span {
         Self {
             begin: ptr,
             cursor: ptr,
+            end: ptr::null_mut(),
+            canonical_floats: false,
+        }
+    }
+    /// Like [`Span::new`], but panics before any write that would go past `ptr + len`.
+    pub const fn with_len(ptr: *mut u8, len: usize) -> Self {
+        Self {
+            begin: ptr,
+            cursor: ptr,
+            end: ptr.wrapping_add(len),
+            canonical_floats: false,
+        }
+    }
+    /// When set, every NaN is written as [`CANONICAL_NAN`](crate::marshal::CANONICAL_NAN), so
+    /// equal values always produce equal bytes.
+    pub const fn with_canonical_floats(self, canonical_floats: bool) -> Self {
+        Self {
+            canonical_floats,
+            ..self
         }
     }
     const fn len(&self) -> usize {
         unsafe { self.cursor.byte_offset_from_unsigned(self.begin) }
     }
+    fn check_bounds(&self, n: usize) {
+        assert!(
+            self.end.is_null() || n <= self.end.addr() - self.cursor.addr(),
+            "write of {n} bytes at offset {} overflows span of {} bytes",
+            self.len(),
+            self.end.addr() - self.begin.addr(),
+        );
+    }
+}
+
+/// Grows to fit everything written, for output whose size is not known up front.
+#[cfg(any(feature = "alloc", test))]
+unsafe impl Write for alloc::vec::Vec<u8> {
+    fn position(&self) -> usize {
+        self.len()
+    }
+
+    fn seek(&mut self, n: usize) {
+        self.resize(self.len() + n, 0);
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        self.extend_from_slice(bytes);
+    }
+
+    fn write_byte(&mut self, byte: u8) {
+        self.push(byte);
+    }
+
+    fn insert<T: Marshal>(&mut self, v: T, reserved: Reserved<T>) {
+        assert!(
+            reserved.position() + reserved.size() <= self.len(),
+            "insert past the end of written data"
+        );
+        let mut cursor = Cursor {
+            begin: self.as_mut_ptr(),
+            pos: reserved.position(),
+            end: reserved.position() + reserved.size(),
+            canonical_floats: false,
+        };
+        cursor.write(v);
+        assert_eq!(
+            cursor.pos, cursor.end,
+            "inserted value does not fill the reserved slot"
+        );
+    }
+}
+
+/// Writes a value back into a slot of a [`Span`], at the slot's own position so that the value
+/// aligns as it would have in place.
+#[derive(Clone, Copy)]
+struct Cursor {
+    begin: *mut u8,
+    pos: usize,
+    /// The end of the slot, which no write may pass.
+    end: usize,
+    canonical_floats: bool,
+}
+
+impl Cursor {
+    fn check_bounds(&self, n: usize) {
+        assert!(
+            n <= self.end - self.pos,
+            "inserted value overflows its reserved slot"
+        );
+    }
 }
 
-struct Cursor(*mut u8);
 unsafe impl Write for Cursor {
     fn position(&self) -> usize {
-        unimplemented!()
+        self.pos
     }
 
-    fn seek(&mut self, _: usize) {
-        unimplemented!()
+    fn seek(&mut self, n: usize) {
+        self.check_bounds(n);
+        self.pos += n;
     }
 
-    fn align_to(&mut self, _: usize) {}
+    fn align_to(&mut self, n: usize) {
+        let padding = crate::align_padding(self.pos, n);
+        self.check_bounds(padding);
+        unsafe { ptr::write_bytes(self.begin.add(self.pos), 0, padding) };
+        self.pos += padding;
+    }
 
     fn write_bytes(&mut self, bytes: &[u8]) {
+        self.check_bounds(bytes.len());
         unsafe {
-            ptr::copy_nonoverlapping(bytes.as_ptr(), self.0, bytes.len());
+            ptr::copy_nonoverlapping(bytes.as_ptr(), self.begin.add(self.pos), bytes.len());
         }
+        self.pos += bytes.len();
     }
 
-    fn write_byte(&mut self, _: u8) {
-        unimplemented!()
+    fn write_byte(&mut self, byte: u8) {
+        self.write_bytes(&[byte]);
+    }
+
+    fn canonical_floats(&self) -> bool {
+        self.canonical_floats
     }
 
-    fn insert<T: Marshal>(&mut self, _: T, _: usize) {
-        unimplemented!()
+    fn insert<T: Marshal>(&mut self, v: T, reserved: Reserved<T>) {
+        assert!(
+            reserved.position() + reserved.size() <= self.pos,
+            "insert past the end of written data"
+        );
+        let mut cursor = Cursor {
+            end: reserved.position() + reserved.size(),
+            pos: reserved.position(),
+            ..*self
+        };
+        cursor.write(v);
     }
 }
 
 unsafe impl Write for Span {
     fn write_bytes(&mut self, bytes: &[u8]) {
+        self.check_bounds(bytes.len());
         unsafe { ptr::copy_nonoverlapping(bytes.as_ptr(), self.cursor, bytes.len()) }
         self.seek(bytes.len())
     }
 
     fn write_byte(&mut self, byte: u8) {
+        self.check_bounds(1);
         unsafe {
             *self.cursor = byte;
             self.cursor = self.cursor.add(1);
@@ -108,20 +368,102 @@ unsafe impl Write for Span {
     }
 
     fn seek(&mut self, n: usize) {
+        self.check_bounds(n);
         unsafe { self.cursor = self.cursor.add(n) };
     }
 
     fn align_to(&mut self, n: usize) {
         let padding = crate::align_padding(self.len(), n);
-        unsafe { ptr::write_bytes(self.cursor, 0, padding) };
-        self.seek(padding);
+        self.check_bounds(padding);
+        unsafe {
+            ptr::write_bytes(self.cursor, 0, padding);
+            self.cursor = self.cursor.add(padding);
+        }
     }
 
     fn position(&self) -> usize {
         self.len()
     }
 
-    fn insert<T: Marshal>(&mut self, v: T, pos: usize) {
-        Cursor(unsafe { self.begin.add(pos) }).write(v)
+    fn canonical_floats(&self) -> bool {
+        self.canonical_floats
+    }
+
+    fn insert<T: Marshal>(&mut self, v: T, reserved: Reserved<T>) {
+        assert!(
+            reserved.position() + reserved.size() <= self.len(),
+            "insert past the end of written data"
+        );
+        let mut cursor = Cursor {
+            begin: self.begin,
+            pos: reserved.position(),
+            end: reserved.position() + reserved.size(),
+            canonical_floats: self.canonical_floats,
+        };
+        cursor.write(v);
+        assert_eq!(
+            cursor.pos, cursor.end,
+            "inserted value does not fill the reserved slot"
+        );
     }
 }
+
+#[test]
+fn test_insert() {
+    let mut buf = [0xffu8; 19];
+    let mut w = Span::with_len(buf.as_mut_ptr(), buf.len());
+    w.write(1u8);
+    let x = w.skip_aligned::<u64>();
+    let y = w.reserve::<u16>(1);
+    w.write(2u8);
+    w.insert(3u64, x);
+    w.insert(4u16, y);
+    assert_eq!(w.position(), buf.len());
+    assert_eq!(buf[..8], [1, 0, 0, 0, 0, 0, 0, 0]);
+    assert_eq!(buf[8..16], 3u64.to_ne_bytes());
+    assert_eq!(buf[16..18], 4u16.to_ne_bytes());
+    assert_eq!(buf[18], 2);
+
+    // Slots are as long as the marshalled value, not its Rust layout.
+    let mut buf = [0xffu8; 8];
+    let mut w = Span::with_len(buf.as_mut_ptr(), buf.len());
+    let flag = w.skip_aligned::<bool>();
+    w.write(7u32);
+    w.insert(true, flag);
+    assert_eq!(
+        buf[..],
+        *crate::marshal::marshal(crate::multiple_new!(true, 7u32))
+    );
+
+    // Values that align and nest are written relative to the slot's position in the span.
+    let value = crate::struct_new!(5u8, 6u32);
+    let mut buf = [0xffu8; 16];
+    let mut w = Span::with_len(buf.as_mut_ptr(), buf.len());
+    w.write(1u8);
+    let slot = w.reserve(8);
+    w.insert(value, slot);
+    assert_eq!(w.position(), 16);
+    assert_eq!(buf[8..], *crate::marshal::marshal(value));
+
+    // A bool takes four bytes on the wire, and a struct keeps its internal padding, whatever
+    // `align` asks for.
+    let padded = crate::struct_new!(8u8, 9u64);
+    let mut buf = [0xffu8; 24];
+    let mut w = Span::with_len(buf.as_mut_ptr(), buf.len());
+    let flag = w.reserve::<bool>(1);
+    let slot = w.reserve(1);
+    assert_eq!((flag.size(), slot.position(), slot.size()), (4, 8, 16));
+    w.insert(padded, slot);
+    w.insert(false, flag);
+    assert_eq!(w.position(), buf.len());
+    let expected = crate::marshal::marshal(crate::multiple_new!(false, padded));
+    assert_eq!(buf[..], *expected);
+}
+
+#[test]
+#[should_panic = "overflows span"]
+fn test_span_bounds() {
+    let mut buf = [0u8; 3];
+    let mut w = Span::with_len(buf.as_mut_ptr(), buf.len());
+    w.write(1u32);
+}
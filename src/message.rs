@@ -1,21 +1,28 @@
 #[cfg(feature = "alloc")]
 use alloc::{borrow::ToOwned, boxed::Box};
 use core::{
+    cell::Cell,
     convert::Infallible,
     fmt::{self, Formatter},
+    hash::{Hash, Hasher},
+    marker::PhantomData,
     mem,
     num::NonZeroU32,
+    ops::Range,
 };
 
+use thiserror::Error;
+
 use crate::{
     marshal::{self, Marshal},
-    signature::{MultiSignature, Node as _, SignatureProxy},
+    metrics::Metrics,
+    signature::{MultiSignature, SignatureProxy},
     strings,
     types::{self, Variant},
     unmarshal::{self, Error, Unmarshal},
 };
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[repr(u8)]
 pub enum Endian {
     Little = b'l',
@@ -30,15 +37,41 @@ impl Endian {
             _ => Err(Error::InvalidHeader)?,
         })
     }
+    /// The byte order of this host, which every marshalled message uses.
+    #[cfg(target_endian = "little")]
+    pub const NATIVE: Self = Self::Little;
+    /// The byte order of this host, which every marshalled message uses.
+    #[cfg(target_endian = "big")]
+    pub const NATIVE: Self = Self::Big;
+    /// Whether integers in this byte order must be swapped to read them on this host.
+    pub const fn is_swapped(self) -> bool {
+        self as u8 != Self::NATIVE as u8
+    }
 }
 
-#[cfg(target_endian = "little")]
-const NATIVE_ENDIAN: Endian = Endian::Little;
+/// Length of the fixed part of the header, which holds the body and field lengths.
+pub const FIXED_HEADER_LEN: usize = 16;
 
-#[cfg(target_endian = "big")]
-const NATIVE_ENDIAN: Endian = Endian::Big;
+/// The total length of a message, computed from its fixed header alone: the header fields padded
+/// to 8 bytes, then the body. For pre-parsers that frame messages outside the crate.
+pub const fn frame_len(fixed: &[u8; FIXED_HEADER_LEN]) -> unmarshal::Result<usize> {
+    let swapped = match Endian::from_u8(fixed[0]) {
+        Ok(endian) => endian.is_swapped(),
+        Err(e) => return Err(e),
+    };
+    let mut args_len = u32::from_ne_bytes([fixed[4], fixed[5], fixed[6], fixed[7]]);
+    let mut fields_len = u32::from_ne_bytes([fixed[12], fixed[13], fixed[14], fixed[15]]);
+    if swapped {
+        args_len = args_len.swap_bytes();
+        fields_len = fields_len.swap_bytes();
+    }
+    match crate::aligned(FIXED_HEADER_LEN + fields_len as usize, 8).checked_add(args_len as _) {
+        Some(len) => Ok(len),
+        None => Err(Error::InvalidHeader),
+    }
+}
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[repr(u8)]
 pub enum MessageType {
     MethodCall = 1,
@@ -56,7 +89,7 @@ impl MessageType {
     }
 }
 
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
 #[repr(transparent)]
 pub struct Flags(pub u8);
 
@@ -116,8 +149,29 @@ macro_rules! define_fields {
     (@to_owned $field:ident $type:ty) => {
         *$field
     };
-    ($($id:literal $field:ident: $type:tt),* $(,)?) => {
-        #[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+    ($($id:literal $name:ident $field:ident $require:ident: $type:tt),* $(,)?) => {
+        /// Identifies a header field by its wire code.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        #[repr(u8)]
+        pub enum FieldId {
+            $($name = $id,)*
+        }
+
+        /// The value of a header field, tagged with which field it is.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum FieldValue<'a> {
+            $($name(define_fields!(@ref $type)),)*
+        }
+
+        impl FieldValue<'_> {
+            pub const fn id(&self) -> FieldId {
+                match self {
+                    $(Self::$name(_) => FieldId::$name,)*
+                }
+            }
+        }
+
+        #[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
         pub struct Fields<'a> {
             $(pub $field: Option<define_fields!(@ref $type)>,)*
         }
@@ -155,6 +209,29 @@ macro_rules! define_fields {
                     ..self
                 }
             })*
+            /// Every field set in `overrides`, and the remaining fields of `self`.
+            pub const fn merge(self, overrides: Self) -> Self {
+                Self {
+                    $($field: match overrides.$field {
+                        Some(value) => Some(value),
+                        None => self.$field,
+                    },)*
+                }
+            }
+            pub const fn without_field(self, id: FieldId) -> Self {
+                match id {
+                    $(FieldId::$name => Self {
+                        $field: None,
+                        ..self
+                    },)*
+                }
+            }
+            /// The fields that are set, in wire code order.
+            pub fn iter(&self) -> impl Iterator<Item = (FieldId, FieldValue<'a>)> + use<'a> {
+                [$(self.$field.map(|x| (FieldId::$name, FieldValue::$name(x))),)*]
+                    .into_iter()
+                    .flatten()
+            }
         }
 
         impl Marshal for &Fields<'_> {
@@ -187,17 +264,37 @@ macro_rules! define_fields {
                 let mut result = Self::empty();
                 let iter: unmarshal::ArrayIter<Entry> = r.read()?;
                 for x in iter {
-                    let Entry { id, field } = x?;
-                    match id {
-                        $($id => {
-                            result = result.$field(field);
-                        })*
-                        _ => {}
-                    }
+                    result = result.with_entry(x?);
                 }
                 Ok(result)
             }
         }
+
+        impl<'a> Fields<'a> {
+            fn with_entry(self, Entry { id, field }: Entry<'a>) -> Self {
+                match id {
+                    $($id => self.$field(field),)*
+                    _ => self,
+                }
+            }
+        }
+
+        impl<'a> Header<'a> {
+            $(/// The field, or [`Error::MissingField`] if it is absent.
+            pub const fn $require(&self) -> unmarshal::Result<define_fields!(@ref $type)> {
+                match self.fields.$field {
+                    Some(value) => Ok(value),
+                    None => Err(Error::MissingField(FieldId::$name)),
+                }
+            })*
+        }
+
+        impl<'a> LazyFields<'a> {
+            $(pub fn $field(&self) -> unmarshal::Result<Option<define_fields!(@ref $type)>> {
+                self.resolve($id)?;
+                Ok(self.cache.get().$field)
+            })*
+        }
     };
 }
 
@@ -224,7 +321,10 @@ macro_rules! define_field {
 
 define_field!(
     object: &'a strings::ObjectPath,
-    string: &'a strings::String,
+    bus_name: &'a strings::BusName,
+    interface: &'a strings::InterfaceName,
+    member: &'a strings::MemberName,
+    error_name: &'a strings::ErrorName,
     signature: &'a strings::Signature,
     u32: u32,
 );
@@ -239,29 +339,137 @@ impl SignatureProxy for Entry<'_> {
 }
 
 define_fields! {
-    1 path: (ref strings::ObjectPath),
-    2 interface: (ref strings::String),
-    3 member: (ref strings::String),
-    4 error_name: (ref strings::String),
-    5 reply_serial: u32,
-    6 destination: (ref strings::String),
-    7 sender: (ref strings::String),
-    8 signature: (ref strings::Signature),
-    9 unix_fds: u32,
+    1 Path path require_path: (ref strings::ObjectPath),
+    2 Interface interface require_interface: (ref strings::InterfaceName),
+    3 Member member require_member: (ref strings::MemberName),
+    4 ErrorName error_name require_error_name: (ref strings::ErrorName),
+    5 ReplySerial reply_serial require_reply_serial: u32,
+    6 Destination destination require_destination: (ref strings::BusName),
+    7 Sender sender require_sender: (ref strings::BusName),
+    8 Signature signature require_signature: (ref strings::Signature),
+    9 UnixFds unix_fds require_unix_fds: u32,
+}
+
+/// Header fields decoded on demand.
+///
+/// Each accessor scans the raw field array only up to the requested field; every field passed
+/// on the way is cached, so later lookups of those fields are free.
+pub struct LazyFields<'a> {
+    data: &'a [u8],
+    endian: Endian,
+    cache: Cell<Fields<'a>>,
+    /// Bit `n` is set once field `n` is known to be in `cache` or absent from the message.
+    resolved: Cell<u16>,
+}
+
+impl<'a> LazyFields<'a> {
+    /// `data` must start at the beginning of a message; the body need not be present.
+    pub fn new(data: &'a [u8]) -> unmarshal::Result<Self> {
+        let endian = data
+            .first()
+            .ok_or(Error::NotEnoughData)
+            .copied()
+            .and_then(Endian::from_u8)?;
+        Ok(Self {
+            data,
+            endian,
+            cache: Cell::new(Fields::empty()),
+            resolved: Cell::new(0),
+        })
+    }
+    fn resolve(&self, id: u8) -> unmarshal::Result<()> {
+        if self.resolved.get() & 1 << id != 0 {
+            return Ok(());
+        }
+        let mut reader = unmarshal::Reader::new(self.data).with_byte_swap(self.endian.is_swapped());
+        reader.seek(12)?;
+        let iter: unmarshal::ArrayIter<Entry> = reader.read()?;
+        for x in iter {
+            let entry = x?;
+            let found = entry.id;
+            self.cache.set(self.cache.get().with_entry(entry));
+            self.resolved.set(self.resolved.get() | 1 << found);
+            if found == id {
+                return Ok(());
+            }
+        }
+        self.resolved.set(u16::MAX);
+        Ok(())
+    }
+    /// Decodes every remaining field at once.
+    pub fn fields(&self) -> unmarshal::Result<Fields<'a>> {
+        self.resolve(0)?;
+        Ok(self.cache.get())
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Header<'a> {
+    /// The byte order of the header and body. Parsed messages keep the order they were sent
+    /// in and their bodies are read in it, but marshalling always writes [`Endian::NATIVE`], so
+    /// a body in the other order must be re-encoded before it is forwarded.
+    pub endian: Endian,
     pub message_type: MessageType,
     pub flags: Flags,
     pub serial: NonZeroU32,
     pub fields: Fields<'a>,
 }
 
+/// What a message is, with the header fields its type requires.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageKind<'a> {
+    MethodCall {
+        path: &'a strings::ObjectPath,
+        interface: Option<&'a strings::InterfaceName>,
+        member: &'a strings::MemberName,
+    },
+    Reply {
+        reply_serial: u32,
+    },
+    Error {
+        name: &'a strings::ErrorName,
+        reply_serial: u32,
+    },
+    Signal {
+        path: &'a strings::ObjectPath,
+        interface: &'a strings::InterfaceName,
+        member: &'a strings::MemberName,
+    },
+}
+
+impl<'a> Header<'a> {
+    /// Classifies the message, failing with [`Error::InvalidHeader`] if a field required by
+    /// its type is missing.
+    pub fn kind(&self) -> unmarshal::Result<MessageKind<'a>> {
+        let fields = &self.fields;
+        let missing = Error::InvalidHeader;
+        Ok(match self.message_type {
+            MessageType::MethodCall => MessageKind::MethodCall {
+                path: fields.path.ok_or(missing)?,
+                interface: fields.interface,
+                member: fields.member.ok_or(missing)?,
+            },
+            MessageType::MethodReturn => MessageKind::Reply {
+                reply_serial: fields.reply_serial.ok_or(missing)?,
+            },
+            MessageType::Error => MessageKind::Error {
+                name: fields.error_name.ok_or(missing)?,
+                reply_serial: fields.reply_serial.ok_or(missing)?,
+            },
+            MessageType::Signal => MessageKind::Signal {
+                path: fields.path.ok_or(missing)?,
+                interface: fields.interface.ok_or(missing)?,
+                member: fields.member.ok_or(missing)?,
+            },
+        })
+    }
+}
+
 #[cfg(feature = "alloc")]
 impl Header<'_> {
     pub fn to_owned(&self) -> OwnedHeader {
         OwnedHeader {
+            endian: self.endian,
             message_type: self.message_type,
             flags: self.flags,
             serial: self.serial,
@@ -273,6 +481,7 @@ impl Header<'_> {
 #[cfg(feature = "alloc")]
 #[derive(Debug, PartialEq, Eq)]
 pub struct OwnedHeader {
+    pub endian: Endian,
     pub message_type: MessageType,
     pub flags: Flags,
     pub serial: NonZeroU32,
@@ -283,6 +492,7 @@ pub struct OwnedHeader {
 impl OwnedHeader {
     pub fn as_ref(&self) -> Header<'_> {
         Header {
+            endian: self.endian,
             message_type: self.message_type,
             flags: self.flags,
             serial: self.serial,
@@ -291,12 +501,85 @@ impl OwnedHeader {
     }
 }
 
+#[cfg(feature = "alloc")]
+impl PartialEq<Fields<'_>> for OwnedFields {
+    fn eq(&self, other: &Fields<'_>) -> bool {
+        self.as_ref() == *other
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl PartialEq<OwnedFields> for Fields<'_> {
+    fn eq(&self, other: &OwnedFields) -> bool {
+        *self == other.as_ref()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl PartialEq<Header<'_>> for OwnedHeader {
+    fn eq(&self, other: &Header<'_>) -> bool {
+        self.as_ref() == *other
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl PartialEq<OwnedHeader> for Header<'_> {
+    fn eq(&self, other: &OwnedHeader) -> bool {
+        *self == other.as_ref()
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Message<'a, T> {
     pub header: Header<'a>,
     pub arguments: T,
 }
 
+impl<'a, T> Message<'a, T> {
+    pub fn kind(&self) -> unmarshal::Result<MessageKind<'a>> {
+        self.header.kind()
+    }
+    /// The number of arguments in the body, counted from the signature field without decoding
+    /// the values. Counting stops at a malformed type.
+    pub fn arg_count(&self) -> usize {
+        self.arg_signatures().count()
+    }
+    /// The signature of argument `i`, or `None` if the body has no such argument.
+    pub fn arg_signature(&self, i: usize) -> Option<&'a strings::Signature> {
+        self.arg_signatures().nth(i)
+    }
+    fn arg_signatures(&self) -> impl Iterator<Item = &'a strings::Signature> {
+        let mut rest = self
+            .header
+            .fields
+            .signature
+            .map_or(&[][..], |x| x.as_bytes());
+        core::iter::from_fn(move || {
+            let (x, xs) = rest.split_at(crate::signature::complete_type_len(rest)?);
+            rest = xs;
+            Some(strings::Signature::from_bytes(x))
+        })
+    }
+}
+
+/// Whether a body of `signature` can hold fd arguments, directly or inside variants.
+fn may_hold_unix_fds(signature: Option<&strings::Signature>) -> bool {
+    signature.is_some_and(|x| x.as_bytes().iter().any(|&c| c == b'h' || c == b'v'))
+}
+
+/// Fails unless every fd argument in `iter`, including those in arrays and variants, is below
+/// `attached`.
+fn check_fd_indices(iter: unmarshal::Iter<'_>, attached: usize) -> unmarshal::Result<()> {
+    for token in iter {
+        match token? {
+            unmarshal::Token::UnixFd(i) if i as usize >= attached => Err(Error::InvalidArgs)?,
+            unmarshal::Token::Array(array) => check_fd_indices(array.iter(), attached)?,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
 impl<'a> Message<'a, &'a [u8]> {
     #[cfg(feature = "alloc")]
     pub fn to_owned(&self) -> OwnedMessage<Box<[u8]>> {
@@ -305,17 +588,162 @@ impl<'a> Message<'a, &'a [u8]> {
             arguments: self.arguments.to_owned().into(),
         }
     }
-    pub fn parse<T: Unmarshal<'a> + MultiSignature>(&self) -> unmarshal::Result<T> {
+    /// Checks the message against the `attached` unix fds that arrived with it: the unix fds
+    /// field must count exactly that many, and every fd argument must index one of them.
+    pub fn check_unix_fds(&self, attached: usize) -> unmarshal::Result<()> {
+        if self.header.fields.unix_fds.unwrap_or(0) as usize != attached {
+            Err(Error::InvalidHeader)?
+        }
+        match self.header.fields.signature {
+            Some(signature) if may_hold_unix_fds(Some(signature)) => {
+                let iter = unmarshal::Iter::with_reader(signature, self.arguments_reader())?;
+                check_fd_indices(iter, attached)
+            }
+            _ => Ok(()),
+        }
+    }
+    /// A reader over the body, in the byte order of the message.
+    pub fn arguments_reader(&self) -> unmarshal::Reader<'a> {
+        unmarshal::Reader::new(self.arguments).with_byte_swap(self.header.endian.is_swapped())
+    }
+    /// A reader over the body, once the signature field is checked to be `expected`.
+    fn body_reader(
+        &self,
+        expected: &'static strings::Signature,
+    ) -> Result<unmarshal::Reader<'a>, ParseError<'a>> {
+        let found = self
+            .header
+            .fields
+            .signature
+            .unwrap_or(strings::Signature::from_bytes(b""));
+        if found != expected {
+            Err(ParseError::SignatureMismatch { expected, found })?
+        }
+        Ok(self.arguments_reader())
+    }
+    /// Parses the body as a `T`, failing with [`ParseError::TrailingBytes`] unless it consumes
+    /// the whole body.
+    pub fn parse<T: Unmarshal<'a> + MultiSignature>(&self) -> Result<T, ParseError<'a>> {
+        match self.parse_prefix()? {
+            (value, 0) => Ok(value),
+            (_, len) => Err(ParseError::TrailingBytes { len }),
+        }
+    }
+    /// Like [`Message::parse`], but allows the body to continue past the `T`, returning how many
+    /// bytes were left unread. For peers that may append arguments in later versions.
+    pub fn parse_prefix<T: Unmarshal<'a> + MultiSignature>(
+        &self,
+    ) -> Result<(T, usize), ParseError<'a>> {
+        let mut reader = self.body_reader(T::SIGNATURE)?;
+        let value = reader.read()?;
+        Ok((value, reader.remaining().len()))
+    }
+    /// Checks the signature field against the argument types of `f`, then reads the body as
+    /// its arguments and calls it. Like [`Message::parse`], the body must be consumed entirely.
+    pub fn read_args<Args, F: unmarshal::ArgsFn<'a, Args>>(
+        &self,
+        f: F,
+    ) -> Result<F::Output, ParseError<'a>> {
+        let mut reader = self.body_reader(F::Signature::SIGNATURE)?;
+        let output = reader.read_args(f)?;
+        match reader.remaining().len() {
+            0 => Ok(output),
+            len => Err(ParseError::TrailingBytes { len }),
+        }
+    }
+    /// Hashes the message type, flags, header fields and body, but not the serial, so that a
+    /// retransmission of the same message hashes the same.
+    ///
+    /// Fields are hashed in decoded form, so their order and padding on the wire do not matter.
+    pub fn content_hash<H: Hasher + Default>(&self) -> u64 {
+        let mut state = H::default();
+        self.header.message_type.hash(&mut state);
+        self.header.flags.hash(&mut state);
+        self.header.fields.hash(&mut state);
+        self.arguments.hash(&mut state);
+        state.finish()
+    }
+    /// Walks the whole body against the signature field and checks that it is well-formed and
+    /// consumes exactly the declared body length.
+    pub fn validate_body(&self) -> unmarshal::Result<()> {
         let signature = self
             .header
             .fields
             .signature
-            .unwrap_or(&strings::Signature::from_bytes(b""));
-        if signature != T::DATA.signature() {
+            .unwrap_or(strings::Signature::from_bytes(b""));
+        let mut iter = unmarshal::Iter::with_reader(signature, self.arguments_reader())?;
+        iter.validate()?;
+        if !iter.reader().remaining().is_empty() {
             Err(Error::InvalidArgs)?
         }
-        let mut reader = unmarshal::Reader::new(self.arguments);
-        reader.read()
+        Ok(())
+    }
+    /// Parses only the header of the message at the start of `data`, without requiring the
+    /// body to be present. Returns the header and the total length of the message.
+    pub fn peek_header(data: &'a [u8]) -> unmarshal::Result<(Header<'a>, usize)> {
+        let mut reader = unmarshal::Reader::new(data);
+        let (header, args_len) = read_header(&mut reader)?;
+        let header_len = crate::aligned(data.len() - reader.remaining().len(), 8);
+        Ok((header, header_len + args_len))
+    }
+    /// The total length of the message at the start of `data`, computed from the fixed part of
+    /// the header alone. Only the first [`FIXED_HEADER_LEN`] bytes need to be present.
+    pub fn frame_len(data: &[u8]) -> unmarshal::Result<usize> {
+        frame_len(data.first_chunk().ok_or(Error::NotEnoughData)?)
+    }
+    /// Scans the header field array for the destination only, skipping every other field
+    /// without decoding it. Cheaper than [`Message::peek_header`] for routing decisions.
+    pub fn peek_destination(data: &'a [u8]) -> unmarshal::Result<Option<&'a strings::BusName>> {
+        let mut reader = unmarshal::Reader::new(data);
+        let endian = reader.read_byte().and_then(Endian::from_u8)?;
+        reader = reader.with_byte_swap(endian.is_swapped());
+        reader.seek(11)?;
+        let len: u32 = reader.read()?;
+        reader.align_to(8)?;
+        let mut fields = reader.seek(len as _)?;
+        while !fields.remaining().is_empty() {
+            fields.align_to(8)?;
+            let id: u8 = fields.read()?;
+            let signature: &strings::Signature = fields.read()?;
+            match signature.as_bytes() {
+                b"s" if id == 6 => return fields.read().map(Some),
+                b"s" | b"o" => {
+                    fields.read::<&strings::String>()?;
+                }
+                b"g" => {
+                    fields.read::<&strings::Signature>()?;
+                }
+                b"u" => {
+                    fields.read::<u32>()?;
+                }
+                _ => Err(Error::InvalidHeader)?,
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Error)]
+pub enum ParseError<'a> {
+    #[error("expected signature '{expected}', got '{found}'")]
+    SignatureMismatch {
+        expected: &'static strings::Signature,
+        found: &'a strings::Signature,
+    },
+    #[error("{len} bytes left after the last argument")]
+    TrailingBytes { len: usize },
+    #[error(transparent)]
+    Unmarshal(#[from] unmarshal::Error),
+}
+
+impl From<ParseError<'_>> for unmarshal::Error {
+    fn from(value: ParseError<'_>) -> Self {
+        match value {
+            ParseError::SignatureMismatch { .. } | ParseError::TrailingBytes { .. } => {
+                Error::InvalidArgs
+            }
+            ParseError::Unmarshal(e) => e,
+        }
     }
 }
 
@@ -336,53 +764,82 @@ impl OwnedMessage<Box<[u8]>> {
     }
 }
 
+#[cfg(feature = "alloc")]
+impl PartialEq<Message<'_, &[u8]>> for OwnedMessage<Box<[u8]>> {
+    fn eq(&self, other: &Message<'_, &[u8]>) -> bool {
+        self.as_ref() == *other
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl PartialEq<OwnedMessage<Box<[u8]>>> for Message<'_, &[u8]> {
+    fn eq(&self, other: &OwnedMessage<Box<[u8]>>) -> bool {
+        *self == other.as_ref()
+    }
+}
+
 impl<T: Marshal> Marshal for &Message<'_, T> {
     fn marshal<W: marshal::Write + ?Sized>(self, w: &mut W) {
         let Message { header, arguments } = self;
-        w.write_byte(NATIVE_ENDIAN as _);
+        w.write_byte(Endian::NATIVE as _);
         w.write_byte(header.message_type as _);
         w.write_byte(header.flags.0);
         w.write_byte(1);
-        let args_len_insertion = w.position();
-        w.seek(4);
+        let args_len_insertion = w.skip_aligned::<u32>();
         w.write(header.serial);
 
-        let header_len_insertion = w.position();
-        w.seek(4);
+        let header_len_insertion = w.skip_aligned::<u32>();
         w.align_to(8);
-        w.write(&header.fields);
-        let header_len = w.position() - 16;
-        w.insert(header_len as u32, header_len_insertion);
+        let mut fields = header.fields;
+        // Unless set by hand, the unix fds field counts the fds the arguments refer to.
+        if fields.unix_fds.is_none() && may_hold_unix_fds(fields.signature) {
+            let mut body = marshal::Measure::new();
+            arguments.marshal(&mut body);
+            if body.unix_fds() > 0 {
+                fields = fields.unix_fds(body.unix_fds());
+            }
+        }
+        w.write(&fields);
+        let header_len = w.position() - FIXED_HEADER_LEN;
+        w.insert_len(header_len, marshal::MAX_ARRAY_LEN, header_len_insertion);
         w.align_to(8);
 
         let args_begin = w.position();
         arguments.marshal(w);
         let args_len = w.position() - args_begin;
-        w.insert(args_len as u32, args_len_insertion);
+        // The body may only take what the header left of the message limit.
+        let max_args_len = marshal::MAX_MESSAGE_LEN.saturating_sub(args_begin);
+        w.insert_len(args_len, max_args_len, args_len_insertion);
     }
 }
 
+/// Reads the fixed part of the header and the field array, returning the header and body length.
+fn read_header<'a>(r: &mut unmarshal::Reader<'a>) -> unmarshal::Result<(Header<'a>, usize)> {
+    let endian = r.read_byte().and_then(Endian::from_u8)?;
+    // Only the header is read in the message's byte order; `r` keeps its own.
+    let mut h = r.with_byte_swap(endian.is_swapped());
+    let message_type = h.read_byte().and_then(MessageType::from_u8)?;
+    let flags = h.read_byte().map(Flags)?;
+    let _version = h.read_byte()?;
+    let args_len: u32 = h.read()?;
+    let serial = h.read()?;
+    let serial = NonZeroU32::new(serial).ok_or(Error::InvalidHeader)?;
+    let fields = h.read()?;
+    *r = h.with_byte_swap(r.is_byte_swapped());
+    let header = Header {
+        endian,
+        message_type,
+        flags,
+        serial,
+        fields,
+    };
+    Ok((header, args_len as usize))
+}
+
 impl<'a> Unmarshal<'a> for Message<'a, &'a [u8]> {
     fn unmarshal(r: &mut unmarshal::Reader<'a>) -> unmarshal::Result<Self> {
-        let endian = r.read_byte().and_then(Endian::from_u8)?;
-        if endian != NATIVE_ENDIAN {
-            Err(Error::UnsupportedEndian)?
-        }
-        let message_type = r.read_byte().and_then(MessageType::from_u8)?;
-        let flags = r.read_byte().map(Flags)?;
-        let _version = r.read_byte()?;
-        let args_len: u32 = r.read()?;
-        let serial = r.read()?;
-        let serial = NonZeroU32::new(serial).ok_or(Error::InvalidHeader)?;
-        let fields = r.read()?;
-        let header = Header {
-            message_type,
-            flags,
-            serial,
-            fields,
-        };
+        let (header, args_len) = read_header(r)?;
         r.align_to(8)?;
-        let args_len = args_len as usize;
         let args = r.remaining().get(..args_len).ok_or(Error::NotEnoughData)?;
         r.seek(args_len)?;
         Ok(Self {
@@ -394,24 +851,126 @@ impl<'a> Unmarshal<'a> for Message<'a, &'a [u8]> {
 
 pub struct MessageIterator<'a> {
     reader: unmarshal::Reader<'a>,
+    /// Bytes consumed before the start of `reader`.
+    offset: usize,
+    /// Set when a message failed to parse, until [`MessageIterator::skip_message`] moves past it.
+    failed: bool,
+    /// The attached unix fds not yet taken by a message, if they are checked.
+    unix_fds: Option<usize>,
 }
 
 impl<'a> MessageIterator<'a> {
     pub fn new(data: &'a [u8]) -> Self {
         Self {
             reader: unmarshal::Reader::new(data),
+            offset: 0,
+            failed: false,
+            unix_fds: None,
+        }
+    }
+    /// Checks every message, and the strings in its header, against `limits`.
+    pub fn with_limits(self, limits: unmarshal::Limits<'a>) -> Self {
+        Self {
+            reader: self.reader.with_limits(limits),
+            ..self
         }
     }
+    /// Reports every message decoded, and every failure, to `metrics`.
+    pub fn with_metrics(self, metrics: &'a dyn Metrics) -> Self {
+        Self {
+            reader: self.reader.with_metrics(metrics),
+            ..self
+        }
+    }
+    /// Checks every message against the `attached` unix fds received with the buffer, which the
+    /// messages take in order. Each takes as many as its unix fds field counts, failing with
+    /// [`InvalidHeader`](Error::InvalidHeader) if fewer are left, and then goes through
+    /// [`Message::check_unix_fds`].
+    pub fn with_unix_fds(self, attached: usize) -> Self {
+        Self {
+            unix_fds: Some(attached),
+            ..self
+        }
+    }
+    /// Where the next message starts in the original buffer.
+    pub const fn offset(&self) -> usize {
+        self.offset
+    }
+    /// The attached unix fds no message has taken yet, if [`MessageIterator::with_unix_fds`]
+    /// set them.
+    pub const fn unix_fds_left(&self) -> Option<usize> {
+        self.unix_fds
+    }
+    /// Skips the next message without parsing it, going by the lengths in its fixed header
+    /// alone, and returns the span it took in the original buffer. After a message fails to
+    /// parse, this resynchronises with the messages behind it. Fails if even the fixed header
+    /// is unreadable or the message is incomplete, leaving the iterator where it was.
+    pub fn skip_message(&mut self) -> unmarshal::Result<Range<usize>> {
+        let len = Message::frame_len(self.reader.remaining())?;
+        self.reader.seek(len)?;
+        self.failed = false;
+        Ok(self.advance())
+    }
+    /// Yields messages like `self`, skipping over those that fail to parse instead of stopping.
+    pub fn quarantined(self) -> Quarantine<'a> {
+        Quarantine {
+            messages: self,
+            stuck: false,
+        }
+    }
+    /// Takes the unix fds `message` counts from those left, checking its fd arguments.
+    fn take_unix_fds(&mut self, message: &Message<'a, &'a [u8]>) -> unmarshal::Result<()> {
+        let Some(left) = self.unix_fds else {
+            return Ok(());
+        };
+        let count = message.header.fields.unix_fds.unwrap_or(0) as usize;
+        let left = left.checked_sub(count).ok_or(Error::InvalidHeader)?;
+        message.check_unix_fds(count)?;
+        self.unix_fds = Some(left);
+        Ok(())
+    }
+    /// Starts a fresh reader after the bytes read, returning their span.
+    fn advance(&mut self) -> Range<usize> {
+        let span = self.offset..self.offset + self.reader.position();
+        self.offset = span.end;
+        let metrics = self.reader.metrics();
+        self.reader =
+            unmarshal::Reader::new(self.reader.remaining()).with_limits(*self.reader.limits());
+        if let Some(metrics) = metrics {
+            self.reader = self.reader.with_metrics(metrics);
+        }
+        span
+    }
+    /// Parses the next message. After a failure nothing more is yielded, with the iterator
+    /// left at the start of the message that failed, until [`MessageIterator::skip_message`]
+    /// moves past it.
     pub fn next(&mut self) -> Option<unmarshal::Result<Message<'a, &'a [u8]>>> {
-        if self.reader.remaining().is_empty() {
+        if self.failed || self.reader.remaining().is_empty() {
             None?;
         }
-        match self.reader.read() {
+        let metrics = self.reader.metrics();
+        let start = self.reader;
+        let res = match Message::frame_len(self.reader.remaining()) {
+            Ok(len) => self.reader.limits().check_message_len(len),
+            Err(_) => Ok(()),
+        };
+        let res = res.and_then(|_| self.reader.read());
+        match res.and_then(|x| self.take_unix_fds(&x).map(|_| x)) {
             Ok(x) => {
-                self.reader = unmarshal::Reader::new(self.reader.remaining());
+                if let Some(metrics) = metrics {
+                    metrics.message_decoded(self.reader.position());
+                }
+                self.advance();
                 Some(Ok(x))
             }
-            Err(e) => Some(Err(e)),
+            Err(e) => {
+                self.reader = start;
+                self.failed = true;
+                if let Some(metrics) = metrics {
+                    metrics.error(&e);
+                }
+                Some(Err(e))
+            }
         }
     }
 }
@@ -424,32 +983,237 @@ impl<'a> Iterator for MessageIterator<'a> {
     }
 }
 
+/// A message that failed to parse, yielded by [`Quarantine`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Quarantined {
+    pub error: unmarshal::Error,
+    /// Where the message was in the original buffer, or `None` if its fixed header could not
+    /// be read either, in which case nothing more is yielded.
+    pub span: Option<Range<usize>>,
+}
+
+/// Yields the messages of a [`MessageIterator`], skipping each one that fails to parse by the
+/// lengths in its fixed header; see [`MessageIterator::quarantined`].
+pub struct Quarantine<'a> {
+    messages: MessageIterator<'a>,
+    stuck: bool,
+}
+
+impl<'a> Iterator for Quarantine<'a> {
+    type Item = Result<Message<'a, &'a [u8]>, Quarantined>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.stuck {
+            None?
+        }
+        Some(self.messages.next()?.map_err(|error| {
+            let span = self.messages.skip_message().ok();
+            self.stuck = span.is_none();
+            Quarantined { error, span }
+        }))
+    }
+}
+
+/// Splits a buffer into the bytes of each message without parsing them, so the messages can be
+/// parsed elsewhere, e.g. on worker threads. See [`Message::frame_len`].
+#[derive(Debug, Clone)]
+pub struct MessageSlices<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> MessageSlices<'a> {
+    pub const fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+    /// The bytes after the last complete message, e.g. the start of one still being received.
+    pub const fn remaining(&self) -> &'a [u8] {
+        self.data
+    }
+}
+
+impl<'a> Iterator for MessageSlices<'a> {
+    type Item = unmarshal::Result<&'a [u8]>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.data.is_empty() {
+            None?;
+        }
+        let res = Message::frame_len(self.data).and_then(|len| {
+            let (message, rest) = self
+                .data
+                .split_at_checked(len)
+                .ok_or(Error::NotEnoughData)?;
+            self.data = rest;
+            Ok(message)
+        });
+        Some(res)
+    }
+}
+
+impl<'a> MessageIterator<'a> {
+    /// Bodies of the signals matching `interface` and `member`; other messages are skipped.
+    pub fn signals<'n, T: Unmarshal<'a> + MultiSignature>(
+        self,
+        interface: &'n strings::InterfaceName,
+        member: &'n strings::MemberName,
+    ) -> Signals<'n, Self, T> {
+        Signals {
+            messages: self,
+            interface,
+            member,
+            marker: PhantomData,
+        }
+    }
+}
+
+/// Iterator returned by [`MessageIterator::signals`].
+pub struct Signals<'n, I, T> {
+    messages: I,
+    interface: &'n strings::InterfaceName,
+    member: &'n strings::MemberName,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<'a, I, T> Iterator for Signals<'_, I, T>
+where
+    I: Iterator<Item = unmarshal::Result<Message<'a, &'a [u8]>>>,
+    T: Unmarshal<'a> + MultiSignature,
+{
+    type Item = unmarshal::Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let msg = match self.messages.next()? {
+                Ok(msg) => msg,
+                Err(e) => return Some(Err(e)),
+            };
+            if let Ok(MessageKind::Signal {
+                interface, member, ..
+            }) = msg.kind()
+                && interface == self.interface
+                && member == self.member
+            {
+                return Some(msg.parse().map_err(Into::into));
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 const fn test_header() -> Header<'static> {
     Header {
+        endian: Endian::NATIVE,
         message_type: MessageType::Signal,
         flags: Flags(1),
         serial: NonZeroU32::new(0xffffffff).unwrap(),
         fields: Fields::empty()
-            .sender("org.freedesktop.DBus")
-            .destination(":1.1758")
+            .sender(strings::BusName::from_static("org.freedesktop.DBus"))
+            .destination(strings::BusName::from_static(":1.1758"))
             .path("/org/freedesktop/DBus")
-            .interface("org.freedesktop.DBus")
-            .member("NameAcquired")
+            .interface(strings::InterfaceName::from_static("org.freedesktop.DBus"))
+            .member(strings::MemberName::from_static("NameAcquired"))
             .signature("s"),
     }
 }
 
+#[test]
+fn test_kind() {
+    let header = test_header();
+    assert_eq!(
+        header.kind(),
+        Ok(MessageKind::Signal {
+            path: strings::ObjectPath::from_str("/org/freedesktop/DBus"),
+            interface: strings::InterfaceName::from_static("org.freedesktop.DBus"),
+            member: strings::MemberName::from_static("NameAcquired"),
+        })
+    );
+    let header = Header {
+        message_type: MessageType::Error,
+        ..header
+    };
+    assert_eq!(header.kind(), Err(Error::InvalidHeader));
+    assert_eq!(header.require_sender(), Ok(header.fields.sender.unwrap()));
+    assert_eq!(
+        header.require_error_name(),
+        Err(Error::MissingField(FieldId::ErrorName))
+    );
+}
+
+#[test]
+fn test_arg_signature() {
+    let mut message = Message {
+        header: test_header(),
+        arguments: (),
+    };
+    assert_eq!(message.arg_count(), 1);
+    message.header.fields.signature = Some(strings::Signature::from_str("a{sv}(ii)y"));
+    assert_eq!(message.arg_count(), 3);
+    assert_eq!(
+        message.arg_signature(1),
+        Some(strings::Signature::from_str("(ii)"))
+    );
+    assert_eq!(message.arg_signature(3), None);
+    message.header.fields.signature = None;
+    assert_eq!(message.arg_count(), 0);
+}
+
+#[test]
+fn test_fields_merge() {
+    let defaults = test_header().fields;
+    let overrides = Fields::empty()
+        .member(strings::MemberName::from_static("NameLost"))
+        .reply_serial(3u32);
+    let merged = defaults.merge(overrides);
+    assert_eq!(merged.member, overrides.member);
+    assert_eq!(merged.reply_serial, Some(3));
+    assert_eq!(merged.path, defaults.path);
+
+    let cleared = merged.without_field(FieldId::ReplySerial);
+    assert_eq!(
+        cleared,
+        defaults.merge(Fields::empty().member(overrides.member.unwrap()))
+    );
+    let ids: Vec<_> = cleared.iter().map(|(id, _)| id).collect();
+    assert_eq!(
+        ids,
+        [
+            FieldId::Path,
+            FieldId::Interface,
+            FieldId::Member,
+            FieldId::Destination,
+            FieldId::Sender,
+            FieldId::Signature
+        ]
+    );
+    assert_eq!(
+        cleared.iter().nth(2),
+        Some((
+            FieldId::Member,
+            FieldValue::Member(strings::MemberName::from_static("NameLost"))
+        ))
+    );
+    assert!(cleared.iter().all(|(id, value)| value.id() == id));
+}
+
+#[cfg(target_endian = "little")]
 #[test]
 fn test_marshal() {
     let header = test_header();
-    dbg!(&header);
     let msg = Message {
         header,
         arguments: strings::String::from_str(":1.1758"),
     };
-    let res = marshal::marshal(&msg);
-    dbg!(crate::show_bytes(&res));
+    crate::assert_marshal_eq!(
+        &msg,
+        b"l\x04\x01\x01\x0c\0\0\0\xff\xff\xff\xff\x8f\0\0\0\
+          \x01\x01o\0\x15\0\0\0/org/freedesktop/DBus\0\0\0\
+          \x02\x01s\0\x14\0\0\0org.freedesktop.DBus\0\0\0\0\
+          \x03\x01s\0\x0c\0\0\0NameAcquired\0\0\0\0\
+          \x06\x01s\0\x07\0\0\0:1.1758\0\
+          \x07\x01s\0\x14\0\0\0org.freedesktop.DBus\0\0\0\0\
+          \x08\x01g\0\x01s\0\0\
+          \x07\0\0\0:1.1758\0"
+    );
 }
 
 #[test]
@@ -471,13 +1235,394 @@ fn test_unmarshal() {
     let msg = iter.next().unwrap().unwrap();
     assert_eq!(msg.header, header);
     assert_eq!(iter.next(), None);
+
+    assert_eq!(
+        msg.parse::<&strings::String>(),
+        Ok(strings::String::from_str(":1.1758"))
+    );
+    assert_eq!(msg.validate_body(), Ok(()));
+    let mut signals = MessageIterator::new(&buf).signals::<&strings::String>(
+        strings::InterfaceName::from_static("org.freedesktop.DBus"),
+        strings::MemberName::from_static("NameAcquired"),
+    );
+    assert_eq!(
+        signals.next(),
+        Some(Ok(strings::String::from_str(":1.1758")))
+    );
+    assert_eq!(signals.count(), 1);
+    let truncated = Message {
+        arguments: &msg.arguments[..msg.arguments.len() - 1],
+        ..msg
+    };
+    assert_eq!(truncated.validate_body(), Err(Error::NotEnoughData));
+    let padded = [msg.arguments, &[0; 4]].concat();
+    let padded = Message {
+        arguments: &padded[..],
+        ..msg
+    };
+    assert_eq!(
+        padded.parse::<&strings::String>(),
+        Err(ParseError::TrailingBytes { len: 4 })
+    );
+    assert_eq!(
+        padded.parse_prefix::<&strings::String>(),
+        Ok((strings::String::from_str(":1.1758"), 4))
+    );
+    assert_eq!(
+        msg.read_args(|name: &strings::String| name.len()),
+        Ok(":1.1758".len())
+    );
+    assert!(msg.read_args(|_: u32| ()).is_err());
+    let err = msg.parse::<u32>().unwrap_err();
+    assert_eq!(
+        alloc::string::ToString::to_string(&err),
+        "expected signature 'u', got 's'"
+    );
+}
+
+#[test]
+fn test_foreign_endian() {
+    let encode = |endian: Endian| {
+        let u32_bytes = |x: u32| match endian {
+            Endian::Little => x.to_le_bytes(),
+            Endian::Big => x.to_be_bytes(),
+        };
+        let mut buf = alloc::vec![endian as u8, 1, 0, 1];
+        buf.extend(u32_bytes(11));
+        buf.extend(u32_bytes(7));
+        buf.extend(u32_bytes(40));
+        buf.extend(b"\x01\x01o\0");
+        buf.extend(u32_bytes(1));
+        buf.extend(b"/\0\0\0\0\0\0\0");
+        buf.extend(b"\x03\x01s\0");
+        buf.extend(u32_bytes(1));
+        buf.extend(b"M\0\0\0\0\0\0\0");
+        buf.extend(b"\x08\x01g\0\x02us\0");
+        buf.extend(u32_bytes(0x0102_0304));
+        buf.extend(u32_bytes(2));
+        buf.extend(b"hi\0");
+        buf
+    };
+    for endian in [Endian::Little, Endian::Big] {
+        let buf = encode(endian);
+        assert_eq!(Message::frame_len(&buf), Ok(buf.len()));
+        let msg = MessageIterator::new(&buf).next().unwrap().unwrap();
+        assert_eq!(msg.header.endian, endian);
+        assert_eq!(msg.header.serial.get(), 7);
+        assert_eq!(
+            msg.header.fields.member,
+            Some(strings::MemberName::from_static("M"))
+        );
+        assert_eq!(msg.validate_body(), Ok(()));
+        assert_eq!(
+            msg.read_args(|x: u32, s: &strings::String| (x, s.len())),
+            Ok((0x0102_0304, 2))
+        );
+        let lazy = LazyFields::new(&buf).unwrap();
+        assert_eq!(lazy.fields(), Ok(msg.header.fields));
+        assert_eq!(Message::peek_destination(&buf), Ok(None));
+    }
+    assert_eq!(
+        Message::frame_len(&[b'x'; FIXED_HEADER_LEN]),
+        Err(Error::InvalidHeader)
+    );
+}
+
+#[test]
+fn test_owned_eq() {
+    let header = test_header();
+    let owned = header.to_owned();
+    assert_eq!(owned, header);
+    assert_eq!(header.fields, owned.fields);
+    let msg = Message {
+        header,
+        arguments: &b"body"[..],
+    };
+    let owned = msg.to_owned();
+    assert_eq!(owned, msg);
+    let other = Message {
+        arguments: &b"other"[..],
+        ..msg
+    };
+    assert_ne!(other, owned);
+}
+
+#[test]
+fn test_content_hash() {
+    use std::hash::DefaultHasher;
+
+    let header = test_header();
+    let a = marshal::marshal(&Message {
+        header,
+        arguments: strings::String::from_str(":1.1758"),
+    });
+    let b = marshal::marshal(&Message {
+        header: Header {
+            serial: NonZeroU32::new(7).unwrap(),
+            ..header
+        },
+        arguments: strings::String::from_str(":1.1758"),
+    });
+    let c = marshal::marshal(&Message {
+        header,
+        arguments: strings::String::from_str(":1.1759"),
+    });
+    let hash = |data: &[u8]| {
+        let msg: Message<&[u8]> = unmarshal::Reader::new(data).read().unwrap();
+        msg.content_hash::<DefaultHasher>()
+    };
+    assert_ne!(a, b);
+    assert_eq!(hash(&a), hash(&b));
+    assert_ne!(hash(&a), hash(&c));
+}
+
+#[test]
+fn test_peek_header() {
+    let header = test_header();
+    let buf = marshal::marshal(&Message {
+        header,
+        arguments: strings::String::from_str(":1.1758"),
+    });
+    let header_end = buf.len() - 12;
+    let (peeked, len) = Message::peek_header(&buf[..header_end]).unwrap();
+    assert_eq!(peeked, header);
+    assert_eq!(len, buf.len());
+    assert_eq!(
+        Message::peek_destination(&buf[..header_end]),
+        Ok(header.fields.destination)
+    );
+
+    let lazy = LazyFields::new(&buf[..header_end]).unwrap();
+    assert_eq!(lazy.member(), Ok(header.fields.member));
+    assert_eq!(lazy.error_name(), Ok(None));
+    assert_eq!(lazy.fields(), Ok(header.fields));
+}
+
+#[test]
+fn test_message_too_long() {
+    let half = marshal::Array(core::iter::repeat_n(0u64, marshal::MAX_ARRAY_LEN / 8));
+    let message = Message {
+        header: test_header(),
+        arguments: crate::multiple_new!(half.clone(), half),
+    };
+    assert!(matches!(
+        marshal::try_calc_size(&message),
+        Err(marshal::Error::TooLong { len, .. }) if len == 2 * (marshal::MAX_ARRAY_LEN + 8)
+    ));
+}
+
+#[test]
+fn test_message_slices() {
+    let header = test_header();
+    let a = marshal::marshal(&Message {
+        header,
+        arguments: strings::String::from_str(":1.1758"),
+    });
+    let b = marshal::marshal(&Message {
+        header,
+        arguments: 7u32,
+    });
+    let buf = [&a[..], &b[..], &a[..5]].concat();
+    assert_eq!(Message::frame_len(&a[..16]), Ok(a.len()));
+    assert_eq!(frame_len(a.first_chunk().unwrap()), Ok(a.len()));
+    let mut fixed = *a.first_chunk().unwrap();
+    fixed[0] = b'x';
+    assert_eq!(frame_len(&fixed), Err(Error::InvalidHeader));
+
+    let mut slices = MessageSlices::new(&buf);
+    assert_eq!(slices.next(), Some(Ok(&a[..])));
+    assert_eq!(slices.next(), Some(Ok(&b[..])));
+    assert_eq!(slices.next(), Some(Err(Error::NotEnoughData)));
+    assert_eq!(slices.remaining(), &a[..5]);
+    let messages: Vec<_> = MessageSlices::new(&buf[..a.len() + b.len()])
+        .map(|x| {
+            unmarshal::Reader::new(x.unwrap())
+                .read::<Message<_>>()
+                .unwrap()
+        })
+        .collect();
+    assert_eq!(messages[1].header, header);
+}
+
+#[test]
+fn test_check_unix_fds() {
+    let header = Header {
+        fields: Fields::empty()
+            .path(strings::ObjectPath::from_str("/"))
+            .member(strings::MemberName::from_static("Pass"))
+            .signature(strings::Signature::from_str("hah"))
+            .unix_fds(2u32),
+        ..test_header()
+    };
+    let bytes = marshal::marshal(&Message {
+        header,
+        arguments: crate::multiple_new!(0u32, &[1u32, 1][..]),
+    });
+    let message = MessageIterator::new(&bytes).next().unwrap().unwrap();
+    assert_eq!(message.check_unix_fds(2), Ok(()));
+    assert_eq!(message.check_unix_fds(1), Err(Error::InvalidHeader));
+
+    let header = Header {
+        fields: header.fields.unix_fds(1u32),
+        ..header
+    };
+    let bytes = marshal::marshal(&Message {
+        header,
+        arguments: crate::multiple_new!(0u32, &[0u32, 1][..]),
+    });
+    let message = MessageIterator::new(&bytes).next().unwrap().unwrap();
+    assert_eq!(message.check_unix_fds(1), Err(Error::InvalidArgs));
+
+    let header = Header {
+        fields: header.fields.signature(strings::Signature::from_str("v")),
+        ..header
+    };
+    let bytes = marshal::marshal(&Message {
+        header,
+        arguments: types::Variant(types::UnixFd(1)),
+    });
+    let message = MessageIterator::new(&bytes).next().unwrap().unwrap();
+    assert_eq!(message.check_unix_fds(1), Err(Error::InvalidArgs));
+}
+
+#[test]
+fn test_unix_fds_counted() {
+    fn unix_fds(signature: &str, arguments: impl Marshal) -> Option<u32> {
+        let header = Header {
+            fields: test_header()
+                .fields
+                .signature(strings::Signature::from_str(signature)),
+            ..test_header()
+        };
+        let bytes = marshal::marshal(&Message { header, arguments });
+        let message = MessageIterator::new(&bytes).next().unwrap().unwrap();
+        message.header.fields.unix_fds
+    }
+    use types::UnixFd;
+    assert_eq!(
+        unix_fds(
+            "hah",
+            crate::multiple_new!(UnixFd(0), &[UnixFd(1), UnixFd(2)][..])
+        ),
+        Some(3)
+    );
+    assert_eq!(
+        unix_fds("v", crate::multiple_new!(types::Variant(UnixFd(0)))),
+        Some(1)
+    );
+    assert_eq!(unix_fds("u", crate::multiple_new!(7u32)), None);
+}
+
+#[test]
+fn test_message_iterator_unix_fds() {
+    let header = Header {
+        fields: test_header()
+            .fields
+            .signature(strings::Signature::from_str("h")),
+        ..test_header()
+    };
+    let mut bytes = marshal::marshal(&Message {
+        header,
+        arguments: types::UnixFd(0),
+    })
+    .into_vec();
+    bytes.extend_from_within(..);
+
+    let mut messages = MessageIterator::new(&bytes).with_unix_fds(2);
+    assert!(messages.next().unwrap().is_ok());
+    assert!(messages.next().unwrap().is_ok());
+    assert_eq!(messages.unix_fds_left(), Some(0));
+
+    let mut messages = MessageIterator::new(&bytes).with_unix_fds(1);
+    assert!(messages.next().unwrap().is_ok());
+    assert_eq!(messages.next(), Some(Err(Error::InvalidHeader)));
+    assert_eq!(messages.unix_fds_left(), Some(0));
+}
+
+#[test]
+fn test_validate_body() {
+    let header = Header {
+        fields: test_header()
+            .fields
+            .signature(strings::Signature::from_str("a(ts)")),
+        ..test_header()
+    };
+    let bytes = marshal::marshal(&Message {
+        header,
+        arguments: &[types::Struct(crate::multiple_new!(1u64, "x"))][..],
+    });
+    let message = MessageIterator::new(&bytes).next().unwrap().unwrap();
+    assert_eq!(message.validate_body(), Ok(()));
+    // The array length still covers the element, but its string runs past it.
+    let mut arguments = message.arguments.to_vec();
+    arguments[16] = 0xff;
+    let corrupted = Message {
+        arguments: &arguments[..],
+        ..message
+    };
+    assert_eq!(corrupted.validate_body(), Err(Error::NotEnoughData));
+}
+
+#[test]
+fn test_quarantine() {
+    let header = test_header();
+    let mut a = marshal::marshal(&Message {
+        header,
+        arguments: strings::String::from_str("one"),
+    });
+    a[1] = 9;
+    let b = marshal::marshal(&Message {
+        header,
+        arguments: strings::String::from_str("two"),
+    });
+    let buf = [&a[..], &b[..], &b[..5]].concat();
+
+    let mut messages = MessageIterator::new(&buf);
+    assert!(messages.next().unwrap().is_err());
+    assert!(messages.next().is_none());
+    assert_eq!(messages.offset(), 0);
+    assert_eq!(messages.skip_message(), Ok(0..a.len()));
+    assert_eq!(
+        messages
+            .next()
+            .unwrap()
+            .unwrap()
+            .parse::<&strings::String>(),
+        Ok(strings::String::from_str("two"))
+    );
+
+    // A loop over the plain iterator ends at the first failure instead of repeating it.
+    let results: alloc::vec::Vec<_> = MessageIterator::new(&buf).map(|x| x.is_ok()).collect();
+    assert_eq!(results, [false]);
+
+    let mut quarantine = MessageIterator::new(&buf).quarantined();
+    let Some(Err(quarantined)) = quarantine.next() else {
+        panic!()
+    };
+    assert_eq!(quarantined.span, Some(0..a.len()));
+    assert_eq!(
+        quarantine
+            .next()
+            .unwrap()
+            .unwrap()
+            .parse::<&strings::String>(),
+        Ok(strings::String::from_str("two"))
+    );
+    assert_eq!(
+        quarantine.next().unwrap().unwrap_err(),
+        Quarantined {
+            error: Error::NotEnoughData,
+            span: None,
+        }
+    );
+    assert!(quarantine.next().is_none());
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Proxy<'a> {
-    pub name: &'a strings::String,
+    pub name: &'a strings::BusName,
     pub path: &'a strings::ObjectPath,
-    pub interface: &'a strings::String,
+    pub interface: &'a strings::InterfaceName,
 }
 
 impl<'a> Proxy<'a> {
@@ -489,7 +1634,33 @@ impl<'a> Proxy<'a> {
     }
 }
 
+pub use body::*;
+pub use dispatch::*;
+pub use intern::*;
 #[cfg(feature = "alloc")]
-pub use serial::Serial;
+pub use journal::*;
+pub use properties::*;
+pub use redact::*;
+pub use sender::*;
+#[cfg(feature = "alloc")]
+pub use serial::{DynamicMethod, ErrorContext, ErrorExt, ErrorReply, ReplySkeleton, Serial};
+pub use serials::*;
+#[cfg(feature = "alloc")]
+pub use stream::*;
+pub use tree::*;
+mod body;
+mod dispatch;
+mod intern;
+#[cfg(feature = "alloc")]
+mod journal;
+mod properties;
+mod redact;
+mod search;
+mod sender;
 #[cfg(feature = "alloc")]
 mod serial;
+mod serials;
+#[cfg(feature = "alloc")]
+mod stream;
+mod tree;
+pub mod typed;
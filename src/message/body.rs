@@ -0,0 +1,39 @@
+use super::*;
+
+/// The arguments of an outgoing message, which decide its signature field: the signature of
+/// `T`, or no field at all when `T` has no arguments.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BodySpec<T>(pub T);
+
+impl<T: Marshal + MultiSignature> BodySpec<T> {
+    pub const SIGNATURE_FIELD: Option<&'static strings::Signature> =
+        if T::SIGNATURE.as_bytes().is_empty() {
+            None
+        } else {
+            Some(T::SIGNATURE)
+        };
+    /// The length of the marshalled body.
+    pub fn marshalled_len(&self) -> usize {
+        marshal::calc_size(self.0.clone())
+    }
+    /// The message of `header` with this body, setting the signature field to match it.
+    pub fn into_message(self, mut header: Header<'_>) -> Message<'_, T> {
+        header.fields.signature = Self::SIGNATURE_FIELD;
+        Message {
+            header,
+            arguments: self.0,
+        }
+    }
+}
+
+#[test]
+fn test_body_spec() {
+    assert_eq!(BodySpec::<types::Empty>::SIGNATURE_FIELD, None);
+    let body = BodySpec(crate::multiple_new!(1u8, "a"));
+    assert_eq!(body.marshalled_len(), 4 + 4 + 2);
+    let message = body.into_message(test_header());
+    assert_eq!(
+        message.header.fields.signature,
+        Some(strings::Signature::from_str("ys"))
+    );
+}
@@ -0,0 +1,187 @@
+use super::*;
+
+/// A method a service answers, as listed by [`method_table!`](crate::method_table).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MethodEntry<H> {
+    pub interface: &'static strings::InterfaceName,
+    pub member: &'static strings::MemberName,
+    /// The signature of the arguments the handler expects.
+    pub signature: &'static strings::Signature,
+    pub handler: H,
+}
+
+/// Why [`lookup_method`] found no handler.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum LookupError {
+    #[error("not a method call")]
+    NotMethodCall,
+    #[error("unknown method")]
+    UnknownMethod,
+    #[error("expected signature '{expected}'")]
+    SignatureMismatch {
+        expected: &'static strings::Signature,
+    },
+}
+
+impl LookupError {
+    pub const fn name(self) -> &'static str {
+        match self {
+            LookupError::NotMethodCall | LookupError::UnknownMethod => {
+                "org.freedesktop.DBus.Error.UnknownMethod"
+            }
+            LookupError::SignatureMismatch { .. } => "org.freedesktop.DBus.Error.InvalidArgs",
+        }
+    }
+}
+
+/// Panics, at compile time when called from [`method_table!`](crate::method_table), if two
+/// entries share an interface and member.
+#[doc(hidden)]
+pub const fn check_methods<H>(table: &[MethodEntry<H>]) -> &[MethodEntry<H>] {
+    let mut i = 0;
+    while i < table.len() {
+        let mut j = i + 1;
+        while j < table.len() {
+            if table[i].interface.as_bytes() == table[j].interface.as_bytes()
+                && table[i].member.as_bytes() == table[j].member.as_bytes()
+            {
+                panic!("method listed twice");
+            }
+            j += 1;
+        }
+        i += 1;
+    }
+    table
+}
+
+/// The entry answering the method call `header`, matched by interface and member and checked
+/// against the signature of the body. A call without an interface matches the first entry with
+/// its member, as the specification allows.
+pub fn lookup_method<'t, H>(
+    table: &'t [MethodEntry<H>],
+    header: &Header<'_>,
+) -> Result<&'t MethodEntry<H>, LookupError> {
+    let Ok(MessageKind::MethodCall {
+        interface, member, ..
+    }) = header.kind()
+    else {
+        Err(LookupError::NotMethodCall)?
+    };
+    let entry = table
+        .iter()
+        .find(|x| x.member == member && interface.is_none_or(|i| x.interface == i))
+        .ok_or(LookupError::UnknownMethod)?;
+    let signature = header.fields.signature.map_or(&[][..], |x| x.as_bytes());
+    if entry.signature.as_bytes() != signature {
+        Err(LookupError::SignatureMismatch {
+            expected: entry.signature,
+        })?
+    }
+    Ok(entry)
+}
+
+/// Defines a constant table of [`MethodEntry`]s for [`lookup_method`], without allocating.
+///
+/// Each entry is `"interface", "member", (argument types) => handler`, where the handler is a
+/// function taking those arguments. Names are validated and signatures derived from the argument
+/// types at compile time, and listing a method twice fails compilation, as does a handler whose
+/// parameters differ from the argument types:
+///
+/// ```compile_fail
+/// fn add(a: u32, b: u32) -> u32 {
+///     a + b
+/// }
+///
+/// dbus_marshal::method_table! {
+///     const METHODS: [fn(u32, u32) -> u32] = [
+///         "org.example.Calc", "Add", (u32, i32) => add,
+///     ];
+/// }
+/// ```
+///
+/// A table of matching entries:
+///
+/// ```
+/// fn add(a: u32, b: u32) -> u32 {
+///     a + b
+/// }
+///
+/// dbus_marshal::method_table! {
+///     const METHODS: [fn(u32, u32) -> u32] = [
+///         "org.example.Calc", "Add", (u32, u32) => add,
+///     ];
+/// }
+/// assert_eq!(METHODS[0].signature.as_bytes(), b"uu");
+/// ```
+#[macro_export]
+macro_rules! method_table {
+    (
+        $vis:vis const $name:ident: [$handler:ty] = [
+            $($interface:literal, $member:literal, ($($arg:ty),* $(,)?) => $f:expr),* $(,)?
+        ];
+    ) => {
+        // Every handler must take the argument types its signature is derived from.
+        const _: () = {
+            $(let _: fn($($arg),*) -> _ = $f;)*
+        };
+        $vis const $name: &[$crate::MethodEntry<$handler>] =
+            $crate::check_methods(&[$($crate::MethodEntry {
+                interface: $crate::InterfaceName::from_static($interface),
+                member: $crate::MemberName::from_static($member),
+                signature: <$crate::multiple_type!($($arg),*) as $crate::signature::MultiSignature>::SIGNATURE,
+                handler: $f,
+            }),*]);
+    };
+}
+
+#[test]
+fn test_method_table() {
+    fn add(a: u32, b: u32) -> u32 {
+        a + b
+    }
+    fn ping(_: u32, _: u32) -> u32 {
+        0
+    }
+    crate::method_table! {
+        const METHODS: [fn(u32, u32) -> u32] = [
+            "org.example.Calc", "Add", (u32, u32) => add,
+            "org.example.Peer", "Ping", (u32, u32) => ping,
+        ];
+    }
+    let mut header = Header {
+        endian: Endian::NATIVE,
+        message_type: MessageType::MethodCall,
+        flags: Flags::empty(),
+        serial: NonZeroU32::new(1).unwrap(),
+        fields: Fields::empty()
+            .path(strings::ObjectPath::from_str("/"))
+            .interface(strings::InterfaceName::from_static("org.example.Calc"))
+            .member(strings::MemberName::from_static("Add"))
+            .signature(strings::Signature::from_str("uu")),
+    };
+    let entry = lookup_method(METHODS, &header).unwrap();
+    assert_eq!((entry.handler)(1, 2), 3);
+
+    header.fields.interface = None;
+    assert_eq!(lookup_method(METHODS, &header), Ok(&METHODS[0]));
+    header.fields.signature = None;
+    assert_eq!(
+        lookup_method(METHODS, &header),
+        Err(LookupError::SignatureMismatch {
+            expected: strings::Signature::from_str("uu")
+        })
+    );
+    header.fields.signature = Some(strings::Signature::from_str("uu"));
+    header.fields.member = Some(strings::MemberName::from_static("Ping"));
+    assert_eq!(lookup_method(METHODS, &header), Ok(&METHODS[1]));
+    header.fields.interface = Some(strings::InterfaceName::from_static("org.example.Calc"));
+    assert_eq!(
+        lookup_method(METHODS, &header),
+        Err(LookupError::UnknownMethod)
+    );
+    header.message_type = MessageType::Signal;
+    assert_eq!(
+        lookup_method(METHODS, &header),
+        Err(LookupError::NotMethodCall)
+    );
+}
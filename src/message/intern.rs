@@ -0,0 +1,116 @@
+#[cfg(feature = "alloc")]
+use alloc::{collections::BTreeSet, sync::Arc};
+
+use super::*;
+
+/// Deduplicates names that repeat across messages, such as senders and interfaces.
+pub trait Interner {
+    type Handle: Clone + Eq;
+    /// The handle for `name`, or `None` if the interner cannot hold it.
+    fn intern(&mut self, name: &str) -> Option<Self::Handle>;
+}
+
+/// Interns into shared [`Arc<str>`] handles, so equal names share one allocation and compare by
+/// pointer first.
+#[cfg(feature = "alloc")]
+#[derive(Debug, Default)]
+pub struct ArcInterner {
+    names: BTreeSet<Arc<str>>,
+}
+
+#[cfg(feature = "alloc")]
+impl ArcInterner {
+    pub const fn new() -> Self {
+        Self {
+            names: BTreeSet::new(),
+        }
+    }
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Interner for ArcInterner {
+    type Handle = Arc<str>;
+
+    fn intern(&mut self, name: &str) -> Option<Arc<str>> {
+        if let Some(handle) = self.names.get(name) {
+            return Some(handle.clone());
+        }
+        let handle: Arc<str> = name.into();
+        self.names.insert(handle.clone());
+        Some(handle)
+    }
+}
+
+/// Interns into indices of a fixed table of expected names, without allocating. Names missing
+/// from the table are not interned.
+#[derive(Debug, Clone, Copy)]
+pub struct TableInterner<'t> {
+    pub names: &'t [&'t str],
+}
+
+impl Interner for TableInterner<'_> {
+    type Handle = usize;
+
+    fn intern(&mut self, name: &str) -> Option<usize> {
+        self.names.iter().position(|&x| x == name)
+    }
+}
+
+/// The bus names and member names of a header, interned.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InternedNames<H> {
+    pub sender: Option<H>,
+    pub destination: Option<H>,
+    pub interface: Option<H>,
+    pub member: Option<H>,
+    pub error_name: Option<H>,
+}
+
+impl Header<'_> {
+    /// Interns the name fields of the header. Absent fields, and names the interner cannot
+    /// hold, are `None`.
+    pub fn intern_names<I: Interner + ?Sized>(&self, interner: &mut I) -> InternedNames<I::Handle> {
+        let fields = &self.fields;
+        // Names are validated on parse, so they are ASCII.
+        let mut intern =
+            |name: Option<&[u8]>| interner.intern(unsafe { str::from_utf8_unchecked(name?) });
+        InternedNames {
+            sender: intern(fields.sender.map(|x| x.as_bytes())),
+            destination: intern(fields.destination.map(|x| x.as_bytes())),
+            interface: intern(fields.interface.map(|x| x.as_bytes())),
+            member: intern(fields.member.map(|x| x.as_bytes())),
+            error_name: intern(fields.error_name.map(|x| x.as_bytes())),
+        }
+    }
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_intern() {
+    let header = test_header();
+    let mut interner = ArcInterner::new();
+    let a = header.intern_names(&mut interner);
+    let b = header.intern_names(&mut interner);
+    assert!(Arc::ptr_eq(
+        a.sender.as_ref().unwrap(),
+        b.sender.as_ref().unwrap()
+    ));
+    assert_eq!(a.interface.as_deref(), Some("org.freedesktop.DBus"));
+    assert_eq!(a.error_name, None);
+    // sender and interface are the same name
+    assert_eq!(interner.len(), 3);
+
+    let mut table = TableInterner {
+        names: &["NameAcquired", "org.freedesktop.DBus"],
+    };
+    let names = header.intern_names(&mut table);
+    assert_eq!(names.sender, Some(1));
+    assert_eq!(names.member, Some(0));
+    assert_eq!(names.destination, None);
+}
@@ -0,0 +1,134 @@
+use alloc::vec::Vec;
+
+use super::*;
+
+/// Where a message of a [`MessageFile`] lies, and the header fields to look it up by.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexEntry<'a> {
+    pub span: Range<usize>,
+    pub message_type: MessageType,
+    pub serial: NonZeroU32,
+    pub sender: Option<&'a strings::BusName>,
+}
+
+/// A capture of back-to-back messages, such as a memory-mapped trace file, indexed in one pass
+/// so that messages can be found by serial and decoded one at a time.
+#[derive(Debug, Clone)]
+pub struct MessageFile<'a> {
+    data: &'a [u8],
+    entries: Vec<IndexEntry<'a>>,
+    /// Indices into `entries`, ordered by serial and then by position.
+    by_serial: Vec<usize>,
+}
+
+impl<'a> MessageFile<'a> {
+    /// Indexes every message in `data`. Fails on the first message that does not parse, with
+    /// its span if its fixed header is readable.
+    pub fn index(data: &'a [u8]) -> Result<Self, Quarantined> {
+        let mut messages = MessageIterator::new(data);
+        let mut entries = Vec::new();
+        loop {
+            let start = messages.offset();
+            match messages.next() {
+                None => break,
+                Some(Ok(message)) => entries.push(IndexEntry {
+                    span: start..messages.offset(),
+                    message_type: message.header.message_type,
+                    serial: message.header.serial,
+                    sender: message.header.fields.sender,
+                }),
+                Some(Err(error)) => Err(Quarantined {
+                    error,
+                    span: messages.skip_message().ok(),
+                })?,
+            }
+        }
+        let mut by_serial: Vec<usize> = (0..entries.len()).collect();
+        by_serial.sort_by_key(|&i| entries[i].serial);
+        Ok(Self {
+            data,
+            entries,
+            by_serial,
+        })
+    }
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+    /// The index, in file order.
+    pub fn entries(&self) -> &[IndexEntry<'a>] {
+        &self.entries
+    }
+    /// Decodes the `i`th message.
+    pub fn get(&self, i: usize) -> Option<Message<'a, &'a [u8]>> {
+        let span = self.entries.get(i)?.span.clone();
+        // The message parsed while indexing.
+        Some(MessageIterator::new(&self.data[span]).next()?.unwrap())
+    }
+    /// The positions of the messages sent under `serial`, in file order. Serials are only
+    /// unique per sender, so there may be several.
+    pub fn find_serial(&self, serial: NonZeroU32) -> impl Iterator<Item = usize> + '_ {
+        let start = self
+            .by_serial
+            .partition_point(|&i| self.entries[i].serial < serial);
+        self.by_serial[start..]
+            .iter()
+            .copied()
+            .take_while(move |&i| self.entries[i].serial == serial)
+    }
+    /// The messages from the `i`th on, to resume reading part way through the file.
+    pub fn messages_from(&self, i: usize) -> MessageIterator<'a> {
+        let start = self
+            .entries
+            .get(i)
+            .map_or(self.data.len(), |x| x.span.start);
+        MessageIterator::new(&self.data[start..])
+    }
+}
+
+#[test]
+fn test_message_file() {
+    let message = |serial, sender| Message {
+        header: Header {
+            endian: Endian::NATIVE,
+            message_type: MessageType::Signal,
+            flags: Flags::empty(),
+            serial: NonZeroU32::new(serial).unwrap(),
+            fields: Fields::empty()
+                .path(strings::ObjectPath::from_str("/"))
+                .interface(strings::InterfaceName::from_static("org.example.Trace"))
+                .member(strings::MemberName::from_static("Tick"))
+                .sender(strings::BusName::from_static(sender))
+                .signature(strings::Signature::from_str("u")),
+        },
+        arguments: crate::multiple_new!(serial),
+    };
+    let mut data = Vec::new();
+    for (serial, sender) in [(3, ":1.1"), (1, ":1.1"), (3, ":1.2"), (2, ":1.2")] {
+        data.extend_from_slice(&marshal::marshal(&message(serial, sender)));
+    }
+
+    let file = MessageFile::index(&data).unwrap();
+    assert_eq!(file.len(), 4);
+    assert_eq!(file.entries()[3].span.end, data.len());
+    let serial = |x| NonZeroU32::new(x).unwrap();
+    assert_eq!(file.find_serial(serial(3)).collect::<Vec<_>>(), [0, 2]);
+    assert_eq!(file.find_serial(serial(4)).count(), 0);
+    let found = file.get(2).unwrap();
+    assert_eq!(
+        found.header.fields.sender,
+        Some(strings::BusName::from_static(":1.2"))
+    );
+    assert_eq!(file.messages_from(1).count(), 3);
+    assert!(file.get(4).is_none());
+
+    let second = file.entries()[1].span.clone();
+    let mut corrupt = data.clone();
+    corrupt[second.start + 1] = 0;
+    assert_eq!(
+        MessageFile::index(&corrupt).err().map(|x| x.span),
+        Some(Some(second))
+    );
+}
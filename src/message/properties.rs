@@ -0,0 +1,362 @@
+#[cfg(feature = "alloc")]
+use alloc::collections::BTreeMap;
+
+use super::*;
+use crate::signature::Signature;
+
+pub const PROPERTIES_INTERFACE: &strings::InterfaceName =
+    strings::InterfaceName::from_static("org.freedesktop.DBus.Properties");
+pub const PROPERTIES_CHANGED: &strings::MemberName =
+    strings::MemberName::from_static("PropertiesChanged");
+
+/// The body of a `PropertiesChanged` signal, `sa{sv}as`.
+///
+/// `changed` is meant to be a struct defined with [`define_dict!`](crate::define_dict), so the
+/// type that parses a `GetAll` reply also emits changes, under the same keys. `invalidated` is
+/// a `&[&str]` or similar when sending and an [`ArrayIter`](unmarshal::ArrayIter) of strings
+/// when receiving.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PropertiesChanged<'a, D, I> {
+    pub interface: &'a strings::InterfaceName,
+    pub changed: D,
+    pub invalidated: I,
+}
+
+unsafe impl<D: Signature, I: Signature> MultiSignature for PropertiesChanged<'_, D, I> {
+    type Data =
+        <crate::multiple_type!(&'static strings::InterfaceName, D, I) as MultiSignature>::Data;
+    const DATA: Self::Data =
+        <crate::multiple_type!(&'static strings::InterfaceName, D, I) as MultiSignature>::DATA;
+}
+
+impl<D: Marshal, I: Marshal> Marshal for PropertiesChanged<'_, D, I> {
+    fn marshal<W: marshal::Write + ?Sized>(self, w: &mut W) {
+        w.write(self.interface);
+        w.write(self.changed);
+        w.write(self.invalidated);
+    }
+}
+
+impl<'a, D: Unmarshal<'a>, I: Unmarshal<'a>> Unmarshal<'a> for PropertiesChanged<'a, D, I> {
+    fn unmarshal(r: &mut unmarshal::Reader<'a>) -> unmarshal::Result<Self> {
+        Ok(Self {
+            interface: r.read()?,
+            changed: r.read()?,
+            invalidated: r.read()?,
+        })
+    }
+}
+
+/// The properties of remote objects, filled from `GetAll` replies and kept up to date with
+/// `PropertiesChanged` signals, so reads are answered locally.
+///
+/// Invalidated properties are dropped, and [`PropertyCache::invalidate`] marks a whole
+/// interface stale, e.g. when its owner leaves the bus; either way the caller fetches them
+/// again.
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, Default)]
+pub struct PropertyCache {
+    objects: BTreeMap<Box<strings::ObjectPath>, BTreeMap<Box<strings::InterfaceName>, Cached>>,
+}
+
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, Default)]
+struct Cached {
+    properties: BTreeMap<Box<strings::String>, CachedValue>,
+    stale: bool,
+}
+
+/// The encoded value of one property, preceded by the padding that restores its alignment,
+/// in the byte order of the message it came from.
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone)]
+struct CachedValue {
+    signature: Box<strings::Signature>,
+    padding: usize,
+    bytes: Box<[u8]>,
+    byte_swapped: bool,
+}
+
+#[cfg(feature = "alloc")]
+type PropertyEntries<'a> =
+    unmarshal::ArrayIter<'a, types::Entry<&'a strings::String, unmarshal::DynVariant<'a>>>;
+
+#[cfg(feature = "alloc")]
+impl Cached {
+    fn update(&mut self, entries: PropertyEntries<'_>) -> unmarshal::Result<()> {
+        for entry in entries {
+            let types::Entry(name, value) = entry?;
+            let padding = value.offset() % 8;
+            let value = CachedValue {
+                signature: value.signature().to_owned(),
+                padding,
+                bytes: [&[0; 8][..padding], value.bytes()].concat().into(),
+                byte_swapped: value.is_byte_swapped(),
+            };
+            self.properties.insert(name.to_owned(), value);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl PropertyCache {
+    pub const fn new() -> Self {
+        Self {
+            objects: BTreeMap::new(),
+        }
+    }
+    /// Replaces the cached properties of `interface` on `path` with those of `reply`, a
+    /// `GetAll` reply with an `a{sv}` body.
+    pub fn apply_get_all(
+        &mut self,
+        path: &strings::ObjectPath,
+        interface: &strings::InterfaceName,
+        reply: &Message<'_, &[u8]>,
+    ) -> unmarshal::Result<()> {
+        let mut cached = Cached::default();
+        cached.update(reply.parse()?)?;
+        self.objects
+            .entry(path.to_owned())
+            .or_default()
+            .insert(interface.to_owned(), cached);
+        Ok(())
+    }
+    /// Applies a `PropertiesChanged` signal to the interface it names, if that is cached.
+    /// Returns whether the message was such a signal for a cached interface.
+    pub fn apply_changed(&mut self, message: &Message<'_, &[u8]>) -> unmarshal::Result<bool> {
+        let fields = &message.header.fields;
+        let Some(path) = fields.path else {
+            return Ok(false);
+        };
+        if fields.interface != Some(PROPERTIES_INTERFACE)
+            || fields.member != Some(PROPERTIES_CHANGED)
+        {
+            return Ok(false);
+        }
+        let signal: PropertiesChanged<PropertyEntries, unmarshal::ArrayIter<&strings::String>> =
+            message.parse()?;
+        let Some(cached) = self
+            .objects
+            .get_mut(path)
+            .and_then(|x| x.get_mut(signal.interface))
+        else {
+            return Ok(false);
+        };
+        cached.update(signal.changed)?;
+        for name in signal.invalidated {
+            cached.properties.remove(name?);
+        }
+        Ok(true)
+    }
+    /// The cached value of property `name`, or `None` if it is not cached. Fails if it is not a
+    /// `T`.
+    pub fn get<'c, T: Unmarshal<'c> + Signature>(
+        &'c self,
+        path: &strings::ObjectPath,
+        interface: &strings::InterfaceName,
+        name: &str,
+    ) -> Option<unmarshal::Result<T>> {
+        let value = self
+            .objects
+            .get(path)?
+            .get(interface)?
+            .properties
+            .get(strings::String::from_str(name))?;
+        if *value.signature != *T::SIGNATURE {
+            return Some(Err(Error::InvalidArgs));
+        }
+        let mut reader = unmarshal::Reader::new(&value.bytes).with_byte_swap(value.byte_swapped);
+        Some(reader.seek(value.padding).and_then(|_| reader.read()))
+    }
+    /// Marks the properties of `interface` on `path` as needing a fresh `GetAll`; they are
+    /// still returned until it is applied.
+    pub fn invalidate(&mut self, path: &strings::ObjectPath, interface: &strings::InterfaceName) {
+        if let Some(cached) = self
+            .objects
+            .get_mut(path)
+            .and_then(|x| x.get_mut(interface))
+        {
+            cached.stale = true;
+        }
+    }
+    /// Whether `interface` on `path` is uncached or marked by [`PropertyCache::invalidate`].
+    pub fn is_stale(&self, path: &strings::ObjectPath, interface: &strings::InterfaceName) -> bool {
+        self.objects
+            .get(path)
+            .and_then(|x| x.get(interface))
+            .is_none_or(|x| x.stale)
+    }
+    /// Forgets every interface of `path`, e.g. once the object is removed.
+    pub fn remove_object(&mut self, path: &strings::ObjectPath) {
+        self.objects.remove(path);
+    }
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_property_cache() {
+    let path = strings::ObjectPath::from_str("/org/example/Unit");
+    let interface = strings::InterfaceName::from_static("org.example.Unit");
+    crate::define_dict! {
+        #[derive(Debug, Clone, Copy, PartialEq)]
+        struct Unit(UnitEntry, UnitKey, UnitValue)<'a> {
+            #[key = "Restarts"]
+            restarts: u32,
+            #[key = "Started"]
+            started: u64,
+            #[key = "State"]
+            state: &'a strings::String,
+        }
+    }
+    let body = marshal::marshal(Unit {
+        restarts: Some(3),
+        started: Some(1),
+        state: Some(strings::String::from_str("active")),
+    });
+    let reply = Message {
+        header: Header {
+            message_type: MessageType::MethodReturn,
+            fields: Fields::empty().signature(strings::Signature::from_str("a{sv}")),
+            ..test_header()
+        },
+        arguments: &body[..],
+    };
+    let mut cache = PropertyCache::new();
+    assert!(cache.is_stale(path, interface));
+    cache.apply_get_all(path, interface, &reply).unwrap();
+    assert!(!cache.is_stale(path, interface));
+    assert_eq!(cache.get::<u64>(path, interface, "Started"), Some(Ok(1)));
+    assert_eq!(
+        cache.get::<&strings::String>(path, interface, "State"),
+        Some(Ok(strings::String::from_str("active")))
+    );
+    assert_eq!(
+        cache.get::<u32>(path, interface, "State"),
+        Some(Err(Error::InvalidArgs))
+    );
+    assert_eq!(cache.get::<u32>(path, interface, "Missing"), None);
+
+    let signal = |path| {
+        marshal::marshal(&Message {
+            header: Header {
+                endian: Endian::NATIVE,
+                message_type: MessageType::Signal,
+                flags: Flags::empty(),
+                serial: NonZeroU32::new(1).unwrap(),
+                fields: Fields::empty()
+                    .path(path)
+                    .interface(PROPERTIES_INTERFACE)
+                    .member(PROPERTIES_CHANGED)
+                    .signature(strings::Signature::from_str("sa{sv}as")),
+            },
+            arguments: PropertiesChanged {
+                interface,
+                changed: &[types::Entry("Started", Variant(2u64))][..],
+                invalidated: &["State"][..],
+            },
+        })
+    };
+    let bytes = signal(path);
+    let message = MessageIterator::new(&bytes).next().unwrap().unwrap();
+    assert_eq!(cache.apply_changed(&message), Ok(true));
+    assert_eq!(cache.get::<u64>(path, interface, "Started"), Some(Ok(2)));
+    assert_eq!(cache.get::<u32>(path, interface, "Restarts"), Some(Ok(3)));
+    assert_eq!(cache.get::<u32>(path, interface, "State"), None);
+
+    let bytes = signal(strings::ObjectPath::from_str("/other"));
+    let message = MessageIterator::new(&bytes).next().unwrap().unwrap();
+    assert_eq!(cache.apply_changed(&message), Ok(false));
+
+    // A reply in the other byte order is read back in that order.
+    let swapped = |x: u32| x.swap_bytes().to_ne_bytes();
+    let body = [
+        &swapped(24)[..],
+        &[0; 4],
+        &swapped(7),
+        b"Started\0\x01t\0\0",
+        &0x0102_0304_0506_0708u64.swap_bytes().to_ne_bytes(),
+    ]
+    .concat();
+    let endian = match Endian::NATIVE {
+        Endian::Little => Endian::Big,
+        Endian::Big => Endian::Little,
+    };
+    let reply = Message {
+        header: Header {
+            endian,
+            ..reply.header
+        },
+        arguments: &body[..],
+    };
+    let other = strings::ObjectPath::from_str("/org/example/Other");
+    cache.apply_get_all(other, interface, &reply).unwrap();
+    assert_eq!(
+        cache.get::<u64>(other, interface, "Started"),
+        Some(Ok(0x0102_0304_0506_0708))
+    );
+    // Errors reading the body come through as they are.
+    let truncated = Message {
+        arguments: &body[..12],
+        ..reply
+    };
+    assert_eq!(
+        cache.apply_get_all(other, interface, &truncated),
+        Err(Error::NotEnoughData)
+    );
+
+    cache.invalidate(path, interface);
+    assert!(cache.is_stale(path, interface));
+    assert_eq!(cache.get::<u32>(path, interface, "Restarts"), Some(Ok(3)));
+    cache.remove_object(path);
+    assert_eq!(cache.get::<u32>(path, interface, "Restarts"), None);
+}
+
+#[test]
+fn test_properties() {
+    crate::define_dict! {
+        #[derive(Debug, Clone, Copy, PartialEq)]
+        struct Unit(UnitEntry, UnitKey, UnitValue)<'a> {
+            #[key = "ActiveState"]
+            active_state: &'a strings::String,
+            #[key = "NRestarts"]
+            restarts: u32,
+        }
+    }
+    let interface = strings::InterfaceName::from_static("org.freedesktop.systemd1.Unit");
+    let state = strings::String::from_str("active");
+
+    let reply = marshal::marshal(
+        &[
+            types::Entry("ActiveState", Variant(state)),
+            types::Entry("Id", Variant(strings::String::from_str("a.service"))),
+        ][..],
+    );
+    let unit: Unit = unmarshal::Reader::new(&reply).read().unwrap();
+    assert_eq!(
+        unit,
+        Unit {
+            active_state: Some(state),
+            restarts: None
+        }
+    );
+
+    let changed = Unit {
+        active_state: None,
+        restarts: Some(2),
+    };
+    let body = marshal::marshal(PropertiesChanged {
+        interface,
+        changed,
+        invalidated: &["ActiveState"][..],
+    });
+    let parsed: PropertiesChanged<Unit, unmarshal::ArrayIter<&strings::String>> =
+        unmarshal::Reader::new(&body).read().unwrap();
+    assert_eq!(parsed.interface, interface);
+    assert_eq!(parsed.changed, changed);
+    assert_eq!(
+        parsed.invalidated.map(Result::unwrap).collect::<Vec<_>>(),
+        [strings::String::from_str("ActiveState")]
+    );
+    crate::assert_signature_eq!(PropertiesChanged<Unit, &[&str]>, "sa{sv}as");
+}
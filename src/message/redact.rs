@@ -0,0 +1,223 @@
+use arrayvec::ArrayVec;
+
+use super::*;
+use crate::{
+    signature::MAX_NESTING_DEPTH,
+    unmarshal::{Iter, Token},
+};
+
+/// A value that [`Message::redacted`] hides in the arguments of one method or signal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RedactRule<'r> {
+    pub interface: &'r strings::InterfaceName,
+    pub member: &'r strings::MemberName,
+    /// The index of the argument, followed by the index of a field within each struct or dict
+    /// entry below it (0 for the key, 1 for the value). Arrays and variants take no index: a
+    /// rule covers every element. Everything below the value is hidden as well.
+    pub path: &'r [usize],
+}
+
+impl RedactRule<'_> {
+    fn applies_to(&self, header: &Header<'_>) -> bool {
+        header.fields.member == Some(self.member)
+            && header.fields.interface.is_none_or(|x| x == self.interface)
+    }
+}
+
+impl<'a> Message<'a, &'a [u8]> {
+    /// Formats the arguments like [`ArrayData::pretty`](unmarshal::ArrayData::pretty), printing
+    /// the basic values that `rules` cover as `<redacted>` while containers stay visible.
+    /// Nothing is decoded until the result is formatted.
+    pub fn redacted<'r>(&self, rules: &'r [RedactRule<'r>]) -> impl fmt::Debug + use<'a, 'r> {
+        let message = *self;
+        fmt::from_fn(move |f| {
+            let signature = message
+                .header
+                .fields
+                .signature
+                .unwrap_or(strings::Signature::from_bytes(b""));
+            let mut printer = Printer {
+                f,
+                rules,
+                header: &message.header,
+                path: ArrayVec::new(),
+                failed: false,
+            };
+            match Iter::with_reader(signature, message.arguments_reader()) {
+                Ok(mut iter) => printer.list(&mut iter, "[", "]", false, true),
+                Err(e) => write!(printer.f, "<{e}>"),
+            }
+        })
+    }
+}
+
+struct Printer<'f, 'h, 'r> {
+    f: &'f mut fmt::Formatter<'h>,
+    rules: &'r [RedactRule<'r>],
+    header: &'r Header<'r>,
+    /// Where the current value is, as a rule names it. Values nested deeper than it can hold
+    /// are always redacted.
+    path: ArrayVec<usize, MAX_NESTING_DEPTH>,
+    /// Set once decoding failed; the error has been printed and nothing follows it.
+    failed: bool,
+}
+
+impl<'a> Printer<'_, '_, '_> {
+    fn next(&mut self, iter: &mut Iter<'a>) -> Result<Option<Token<'a>>, fmt::Error> {
+        if self.failed {
+            return Ok(None);
+        }
+        match iter.next() {
+            Some(Ok(token)) => Ok(Some(token)),
+            Some(Err(e)) => {
+                self.failed = true;
+                write!(self.f, "<{e}>").map(|_| None)
+            }
+            None => Ok(None),
+        }
+    }
+    fn is_redacted(&self) -> bool {
+        self.rules
+            .iter()
+            .any(|rule| rule.applies_to(self.header) && *rule.path == *self.path)
+    }
+    /// Prints values until `iter` ends or yields a closing token, indexing them into the path
+    /// if `indexed`.
+    fn list(
+        &mut self,
+        iter: &mut Iter<'a>,
+        open: &str,
+        close: &str,
+        redacted: bool,
+        indexed: bool,
+    ) -> fmt::Result {
+        self.f.write_str(open)?;
+        let mut i = 0;
+        while let Some(token) = self.next(iter)? {
+            if matches!(
+                token,
+                Token::StructClose | Token::EntryClose | Token::VariantClose
+            ) {
+                break;
+            }
+            if i > 0 {
+                self.f.write_str(", ")?;
+            }
+            // Past the deepest path a rule can name, a rule may still cover the value.
+            let pushed = indexed && self.path.try_push(i).is_ok();
+            self.value(iter, token, redacted || (indexed && !pushed))?;
+            if pushed {
+                self.path.pop();
+            }
+            i += 1;
+        }
+        self.f.write_str(close)
+    }
+    fn value(&mut self, iter: &mut Iter<'a>, token: Token<'a>, redacted: bool) -> fmt::Result {
+        let redacted = redacted || self.is_redacted();
+        match token {
+            Token::StructOpen => self.list(iter, "(", ")", redacted, true),
+            Token::EntryOpen => self.list(iter, "{", "}", redacted, true),
+            Token::VariantOpen(signature) => {
+                write!(self.f, "Variant({signature:?}, ")?;
+                self.list(iter, "", ")", redacted, false)
+            }
+            Token::Array(array) => self.list(&mut array.iter(), "[", "]", redacted, false),
+            _ if redacted => self.f.write_str("<redacted>"),
+            token => write!(self.f, "{token:?}"),
+        }
+    }
+}
+
+#[test]
+fn test_redacted() {
+    let message = Message {
+        header: Header {
+            endian: Endian::NATIVE,
+            message_type: MessageType::MethodCall,
+            flags: Flags::empty(),
+            serial: NonZeroU32::new(1).unwrap(),
+            fields: Fields::empty()
+                .path(strings::ObjectPath::from_str("/"))
+                .interface(strings::InterfaceName::from_static("org.example.Secrets"))
+                .member(strings::MemberName::from_static("Store"))
+                .signature(strings::Signature::from_str("sa(ss)")),
+        },
+        arguments: crate::multiple_new!(
+            "login",
+            &[types::Struct(crate::multiple_new!("alice", "hunter2"))][..]
+        ),
+    };
+    let bytes = marshal::marshal(&message);
+    let message = MessageIterator::new(&bytes).next().unwrap().unwrap();
+    let rule = RedactRule {
+        interface: strings::InterfaceName::from_static("org.example.Secrets"),
+        member: strings::MemberName::from_static("Store"),
+        path: &[1, 1],
+    };
+    assert_eq!(
+        alloc::format!("{:?}", message.redacted(&[rule])),
+        r#"[String("login"), [(String("alice"), <redacted>)]]"#
+    );
+    let everything = RedactRule { path: &[1], ..rule };
+    assert_eq!(
+        alloc::format!("{:?}", message.redacted(&[everything])),
+        r#"[String("login"), [(<redacted>, <redacted>)]]"#
+    );
+    let other = RedactRule {
+        member: strings::MemberName::from_static("Load"),
+        ..rule
+    };
+    assert_eq!(
+        alloc::format!("{:?}", message.redacted(&[other])),
+        r#"[String("login"), [(String("alice"), String("hunter2"))]]"#
+    );
+}
+
+#[test]
+fn test_redacted_depth() {
+    let header = Header {
+        fields: Fields::empty()
+            .path(strings::ObjectPath::from_str("/"))
+            .interface(strings::InterfaceName::from_static("org.example.Secrets"))
+            .member(strings::MemberName::from_static("Store")),
+        ..test_header()
+    };
+    let rule = RedactRule {
+        interface: strings::InterfaceName::from_static("org.example.Secrets"),
+        member: strings::MemberName::from_static("Store"),
+        path: &[0, 1],
+    };
+    let nested = |n| [&"(".repeat(n), "y", &")".repeat(n)].concat();
+    let body = [
+        7, 0, 0, 0, 6, 0, 0, 0, b's', b'e', b'c', b'r', b'e', b't', 0,
+    ];
+    // The byte at the bottom fills every level of the path, and the string after it must
+    // still be found at [0, 1].
+    let signature = ["(", &nested(MAX_NESTING_DEPTH - 2), "s)"].concat();
+    let message = Message {
+        header: Header {
+            fields: header
+                .fields
+                .signature(strings::Signature::from_str(&signature)),
+            ..header
+        },
+        arguments: &body[..],
+    };
+    let printed = alloc::format!("{:?}", message.redacted(&[rule]));
+    assert!(printed.ends_with("), <redacted>)]"), "{printed}");
+
+    // Nested past any path a rule can name, the string must not leak either.
+    let signature = ["(", &nested(70), "s)"].concat();
+    let message = Message {
+        header: Header {
+            fields: header
+                .fields
+                .signature(strings::Signature::from_str(&signature)),
+            ..header
+        },
+        arguments: &body[..],
+    };
+    let printed = alloc::format!("{:?}", message.redacted(&[rule]));
+    assert!(!printed.contains("secret"), "{printed}");
+}
@@ -0,0 +1,149 @@
+use arrayvec::ArrayVec;
+
+use super::*;
+use crate::{
+    signature::MAX_NESTING_DEPTH,
+    unmarshal::{Token, Visitor},
+};
+
+impl<'a> Message<'a, &'a [u8]> {
+    /// Calls `f` with every [`Token::String`] and [`Token::Object`] in the body, depth first,
+    /// e.g. to index bus traffic for full-text search.
+    ///
+    /// Each value comes with its path, numbered like [`RedactRule::path`]: the index of the
+    /// argument, then the index of the field within each struct or dict entry below it. Array
+    /// elements and variant contents take no index.
+    pub fn for_each_string(&self, f: impl FnMut(&[usize], Token<'a>)) -> unmarshal::Result<()> {
+        let signature = self
+            .header
+            .fields
+            .signature
+            .unwrap_or(strings::Signature::from_bytes(b""));
+        let mut walker = Strings {
+            f,
+            path: ArrayVec::new(),
+            levels: ArrayVec::new(),
+        };
+        walker.push_level(true)?;
+        self.arguments_reader()
+            .read_dynamic_with(signature, &mut walker)
+    }
+}
+
+struct Strings<F> {
+    f: F,
+    path: ArrayVec<usize, MAX_NESTING_DEPTH>,
+    /// Per open container, whether its values are indexed into the path, and the index of the
+    /// next one.
+    levels: ArrayVec<(bool, usize), MAX_NESTING_DEPTH>,
+}
+
+impl<F> Strings<F> {
+    fn push_level(&mut self, indexed: bool) -> unmarshal::Result<()> {
+        self.levels
+            .try_push((indexed, 0))
+            .map_err(|_| Error::NestingDepthExceeded)
+    }
+    fn begin(&mut self) -> unmarshal::Result<()> {
+        match self.levels.last() {
+            Some(&(true, next)) => self
+                .path
+                .try_push(next)
+                .map_err(|_| Error::NestingDepthExceeded),
+            _ => Ok(()),
+        }
+    }
+    fn end(&mut self) {
+        if let Some((true, next)) = self.levels.last_mut() {
+            self.path.pop();
+            *next += 1;
+        }
+    }
+}
+
+impl<'a, F: FnMut(&[usize], Token<'a>)> Visitor<'a> for Strings<F> {
+    fn token(&mut self, token: Token<'a>) -> unmarshal::Result<()> {
+        match token {
+            Token::StructOpen | Token::EntryOpen => {
+                self.begin()?;
+                self.push_level(true)
+            }
+            Token::VariantOpen(_) => {
+                self.begin()?;
+                self.push_level(false)
+            }
+            Token::StructClose | Token::EntryClose | Token::VariantClose => {
+                self.levels.pop();
+                self.end();
+                Ok(())
+            }
+            token => {
+                self.begin()?;
+                if let Token::String(_) | Token::Object(_) = token {
+                    (self.f)(&self.path, token);
+                }
+                self.end();
+                Ok(())
+            }
+        }
+    }
+    fn array_open(&mut self, _: &'a strings::Signature) -> unmarshal::Result<()> {
+        self.begin()?;
+        self.push_level(false)
+    }
+    fn array_close(&mut self) -> unmarshal::Result<()> {
+        self.levels.pop();
+        self.end();
+        Ok(())
+    }
+}
+
+#[test]
+fn test_for_each_string() {
+    let message = Message {
+        header: Header {
+            endian: Endian::NATIVE,
+            message_type: MessageType::Signal,
+            flags: Flags::empty(),
+            serial: NonZeroU32::new(1).unwrap(),
+            fields: Fields::empty()
+                .path(strings::ObjectPath::from_str("/"))
+                .interface(strings::InterfaceName::from_static("org.example.Index"))
+                .member(strings::MemberName::from_static("Changed"))
+                .signature(strings::Signature::from_str("usa{sv}(uo)")),
+        },
+        arguments: crate::multiple_new!(
+            1u32,
+            "first",
+            &[
+                types::Entry("a", Variant("b")),
+                types::Entry("c", Variant("d"))
+            ][..],
+            types::Struct(crate::multiple_new!(
+                2u32,
+                strings::ObjectPath::from_str("/e")
+            ))
+        ),
+    };
+    let bytes = marshal::marshal(&message);
+    let message = MessageIterator::new(&bytes).next().unwrap().unwrap();
+    let mut found = alloc::vec::Vec::new();
+    message
+        .for_each_string(|path, token| found.push((path.to_vec(), token)))
+        .unwrap();
+    let string = |x| Token::String(strings::String::from_str(x));
+    assert_eq!(
+        found,
+        [
+            (alloc::vec![1], string("first")),
+            (alloc::vec![2, 0], string("a")),
+            (alloc::vec![2, 1], string("b")),
+            (alloc::vec![2, 0], string("c")),
+            (alloc::vec![2, 1], string("d")),
+            (
+                alloc::vec![3, 1],
+                Token::Object(strings::ObjectPath::from_str("/e"))
+            ),
+        ]
+    );
+}
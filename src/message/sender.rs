@@ -0,0 +1,71 @@
+use super::*;
+
+/// Knows which unique name currently owns each well-known name, e.g. by following
+/// `NameOwnerChanged` signals.
+pub trait NameTracker {
+    fn owner(&self, name: &strings::WellKnownName) -> Option<&strings::UniqueName>;
+}
+
+/// Knows no owners, so only unique names and the bus itself match.
+impl NameTracker for () {
+    fn owner(&self, _: &strings::WellKnownName) -> Option<&strings::UniqueName> {
+        None
+    }
+}
+
+/// A fixed list of well-known names and their owners.
+impl NameTracker for [(&strings::WellKnownName, &strings::UniqueName)] {
+    fn owner(&self, name: &strings::WellKnownName) -> Option<&strings::UniqueName> {
+        self.iter()
+            .find(|(x, _)| *x == name)
+            .map(|&(_, owner)| owner)
+    }
+}
+
+/// The name the bus driver sends under, the only well-known name that appears as a sender.
+const BUS: &strings::BusName = strings::BusName::from_static("org.freedesktop.DBus");
+
+impl Header<'_> {
+    /// Whether the message was sent by `expected`.
+    ///
+    /// The bus rewrites senders to unique names, so a well-known `expected` matches a sender
+    /// that `names` says currently owns it, rather than the bytes of the name itself. Only the
+    /// bus itself sends under its well-known name.
+    pub fn sent_by<N: NameTracker + ?Sized>(&self, expected: &strings::BusName, names: &N) -> bool {
+        let Some(sender) = self.fields.sender else {
+            return false;
+        };
+        match (sender.as_unique(), expected.as_well_known()) {
+            _ if expected == BUS => sender == BUS,
+            (Some(sender), Some(expected)) => names.owner(expected) == Some(sender),
+            (Some(_), None) => sender == expected,
+            (None, _) => false,
+        }
+    }
+}
+
+#[test]
+fn test_sent_by() {
+    let bus = strings::BusName::from_static;
+    let mut header = test_header();
+    assert!(header.sent_by(bus("org.freedesktop.DBus"), &()));
+
+    header.fields.sender = Some(bus(":1.7"));
+    let service = bus("org.example.Service");
+    assert!(header.sent_by(bus(":1.7"), &()));
+    assert!(!header.sent_by(service, &()));
+
+    header.fields.sender = Some(service);
+    assert!(!header.sent_by(service, &()));
+    header.fields.sender = Some(bus(":1.7"));
+
+    let owners: &[(&strings::WellKnownName, &strings::UniqueName)] = &[(
+        strings::WellKnownName::from_static("org.example.Service"),
+        strings::UniqueName::from_static(":1.7"),
+    )];
+    assert!(header.sent_by(service, owners));
+    header.fields.sender = Some(bus(":1.8"));
+    assert!(!header.sent_by(service, owners));
+    header.fields.sender = None;
+    assert!(!header.sent_by(bus(":1.7"), &()));
+}
@@ -1,9 +1,220 @@
-use core::num::NonZeroU32;
+use alloc::string::{String, ToString};
+use core::{fmt::Write as _, num::NonZeroU32};
 
 use super::*;
 
+/// Numbers outgoing messages and marshals them into buffers from `B`.
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
-pub struct Serial(u32);
+pub struct Serial<B = marshal::Boxed> {
+    value: u32,
+    buffers: B,
+}
+
+/// The error half of a method handler's result, sent back as an error reply whose single
+/// argument is `message`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErrorReply<'a> {
+    pub name: &'a strings::ErrorName,
+    pub message: String,
+}
+
+impl From<unmarshal::Error> for ErrorReply<'static> {
+    fn from(value: unmarshal::Error) -> Self {
+        Self {
+            name: strings::ErrorName::from_static(reply_name(value)),
+            message: value.to_string(),
+        }
+    }
+}
+
+/// The error name to reply with when decoding a call failed. Every way the peer's arguments
+/// can fail to decode is their fault, so only the limits keep a name of their own.
+fn reply_name(error: unmarshal::Error) -> &'static str {
+    match error {
+        Error::NotEnoughData | Error::InvalidHeader | Error::UnsupportedEndian => {
+            Error::InvalidArgs.name()
+        }
+        _ => error.name(),
+    }
+}
+
+/// A method call whose interface, member and signature are only known at runtime, the dynamic
+/// counterpart of [`Serial::method_call`] for scripting bridges. The arguments are
+/// [`Value`](unmarshal::Value)s, checked against the signature on every call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DynamicMethod<'a> {
+    pub proxy: Proxy<'a>,
+    pub member: &'a strings::MemberName,
+    pub signature: &'a strings::Signature,
+}
+
+/// The arguments of a [`DynamicMethod`], marshalled one after another.
+#[derive(Clone, Copy)]
+struct Values<'v, 'a>(&'v [unmarshal::Value<'a>]);
+
+impl Marshal for Values<'_, '_> {
+    fn marshal<W: marshal::Write + ?Sized>(self, w: &mut W) {
+        for value in self.0 {
+            w.write(value);
+        }
+    }
+}
+
+impl<'a> DynamicMethod<'a> {
+    /// Checks the names and that `signature` is valid as [`Signature::validate`] defines it,
+    /// failing with [`Error::InvalidArgs`](unmarshal::Error::InvalidArgs) otherwise.
+    ///
+    /// [`Signature::validate`]: strings::Signature::validate
+    pub fn new(proxy: Proxy<'a>, member: &'a str, signature: &'a str) -> unmarshal::Result<Self> {
+        Ok(Self {
+            proxy,
+            member: member.try_into()?,
+            signature: strings::Signature::validate(signature.as_bytes())?,
+        })
+    }
+    /// Checks `args` against the signature, one value per complete type, and marshals the
+    /// call with the next serial of `serial`. Arguments too long to marshal fail with
+    /// [`Error::InvalidArgs`](unmarshal::Error::InvalidArgs) as well.
+    pub fn call<B: marshal::Buffers>(
+        &self,
+        serial: &mut Serial<B>,
+        flags: Flags,
+        args: &[unmarshal::Value<'_>],
+    ) -> unmarshal::Result<B::Buffer> {
+        let mut rest = self.signature.as_bytes();
+        for arg in args {
+            let len = crate::signature::complete_type_len(rest).ok_or(Error::InvalidArgs)?;
+            arg.check(strings::Signature::from_bytes(&rest[..len]))?;
+            rest = &rest[len..];
+        }
+        if !rest.is_empty() {
+            Err(Error::InvalidArgs)?
+        }
+        let message = Message {
+            header: Header {
+                endian: Endian::NATIVE,
+                message_type: MessageType::MethodCall,
+                flags,
+                serial: serial.next(),
+                fields: Fields {
+                    signature: (!self.signature.is_empty()).then_some(self.signature),
+                    member: Some(self.member),
+                    ..self.proxy.method_call()
+                },
+            },
+            arguments: Values(args),
+        };
+        marshal::marshal_in(&message, &serial.buffers).map_err(|_| Error::InvalidArgs)
+    }
+}
+
+/// The header of a method return, marshalled before its body is known; see
+/// [`Serial::reply_skeleton`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplySkeleton {
+    header: Box<[u8]>,
+}
+
+impl ReplySkeleton {
+    /// The marshalled header, padded to 8 bytes, with a body length of zero.
+    pub fn header(&self) -> &[u8] {
+        &self.header
+    }
+    /// The whole message, `body` appended to the header and its length patched in. `body` must
+    /// be marshalled as if starting at an 8-aligned offset and match the signature the skeleton
+    /// was made with. Fails if it is longer than the message limit leaves room for.
+    pub fn finish(self, body: &[u8]) -> Result<Box<[u8]>, marshal::Error> {
+        let max = marshal::MAX_MESSAGE_LEN.saturating_sub(self.header.len());
+        if body.len() > max {
+            Err(marshal::Error::TooLong {
+                len: body.len(),
+                max,
+            })?
+        }
+        let mut message = [&self.header[..], body].concat();
+        message[4..8].copy_from_slice(&(body.len() as u32).to_ne_bytes());
+        Ok(message.into())
+    }
+}
+
+/// Where decoding failed, for the message of [`ErrorExt::to_error_reply`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ErrorContext<'c> {
+    /// What was being decoded, such as the name of an argument.
+    pub what: Option<&'c str>,
+    /// How far into the body decoding got.
+    pub offset: Option<usize>,
+}
+
+impl<'c> ErrorContext<'c> {
+    pub const fn new() -> Self {
+        Self {
+            what: None,
+            offset: None,
+        }
+    }
+    pub const fn what(self, what: &'c str) -> Self {
+        Self {
+            what: Some(what),
+            ..self
+        }
+    }
+    pub const fn at(self, offset: usize) -> Self {
+        Self {
+            offset: Some(offset),
+            ..self
+        }
+    }
+}
+
+impl From<&unmarshal::Reader<'_>> for ErrorContext<'_> {
+    fn from(value: &unmarshal::Reader<'_>) -> Self {
+        Self::new().at(value.position())
+    }
+}
+
+/// Turns a failure to decode a method call into the error reply to send back, named after the
+/// standard error it corresponds to.
+pub trait ErrorExt {
+    fn to_error_reply<'c>(&self, ctx: impl Into<ErrorContext<'c>>) -> ErrorReply<'static>;
+}
+
+fn error_reply(
+    name: &'static str,
+    error: &dyn fmt::Display,
+    ctx: ErrorContext,
+) -> ErrorReply<'static> {
+    let mut message = error.to_string();
+    if let Some(what) = ctx.what {
+        write!(message, " while reading {what}").unwrap();
+    }
+    if let Some(offset) = ctx.offset {
+        write!(message, " at offset {offset}").unwrap();
+    }
+    ErrorReply {
+        name: strings::ErrorName::from_static(name),
+        message,
+    }
+}
+
+impl ErrorExt for unmarshal::Error {
+    fn to_error_reply<'c>(&self, ctx: impl Into<ErrorContext<'c>>) -> ErrorReply<'static> {
+        error_reply(reply_name(*self), self, ctx.into())
+    }
+}
+
+impl ErrorExt for ParseError<'_> {
+    fn to_error_reply<'c>(&self, ctx: impl Into<ErrorContext<'c>>) -> ErrorReply<'static> {
+        let name = reply_name(unmarshal::Error::from(*self));
+        error_reply(name, self, ctx.into())
+    }
+}
+
+impl ErrorExt for LookupError {
+    fn to_error_reply<'c>(&self, ctx: impl Into<ErrorContext<'c>>) -> ErrorReply<'static> {
+        error_reply(self.name(), self, ctx.into())
+    }
+}
 
 impl Default for Serial {
     fn default() -> Self {
@@ -13,134 +224,300 @@ impl Default for Serial {
 
 impl Serial {
     pub const fn from_raw(value: u32) -> Self {
-        Self(value)
+        Self {
+            value,
+            buffers: marshal::Boxed,
+        }
     }
     pub const fn new() -> Self {
-        Self(0)
+        Self::from_raw(0)
+    }
+}
+
+impl<B> Serial<B> {
+    /// Continues the numbering, marshalling into buffers from `buffers` from now on.
+    pub fn with_buffers<C: marshal::Buffers>(self, buffers: C) -> Serial<C> {
+        Serial {
+            value: self.value,
+            buffers,
+        }
     }
     fn get(&self) -> NonZeroU32 {
-        unsafe { NonZeroU32::new_unchecked(self.0) }
+        unsafe { NonZeroU32::new_unchecked(self.value) }
     }
     fn next(&mut self) -> NonZeroU32 {
-        self.0 += 1;
+        self.value += 1;
         self.get()
     }
+}
 
+impl<B: marshal::Buffers> Serial<B> {
     pub fn method_call<'a, T: Marshal + MultiSignature>(
         &mut self,
         flags: Flags,
         proxy: Proxy<'_>,
-        member: impl Into<&'a strings::String>,
+        member: impl Into<&'a strings::MemberName>,
         arguments: T,
-    ) -> Box<[u8]> {
-        let sig = T::DATA;
-        let signature = sig.signature();
-        let fields = Fields {
-            signature: if signature.is_empty() {
-                None
-            } else {
-                Some(signature)
+    ) -> Result<B::Buffer, marshal::Error> {
+        let header = Header {
+            endian: Endian::NATIVE,
+            message_type: MessageType::MethodCall,
+            flags,
+            serial: self.next(),
+            fields: Fields {
+                member: Some(member.into()),
+                ..proxy.method_call()
             },
-            member: Some(member.into()),
-            ..proxy.method_call()
         };
-        marshal::marshal(&Message {
-            header: Header {
-                message_type: MessageType::MethodCall,
-                flags,
-                serial: self.next(),
-                fields,
-            },
-            arguments,
-        })
+        let message = BodySpec(arguments).into_message(header);
+        marshal::marshal_in(&message, &self.buffers)
     }
 
     pub fn method_return<T: Marshal + MultiSignature>(
         &mut self,
         method_call: &Header,
         arguments: T,
-    ) -> Box<[u8]> {
-        let sig = T::DATA;
-        let signature = sig.signature();
-        let fields = Fields {
-            signature: if signature.is_empty() {
-                None
-            } else {
-                Some(signature)
+    ) -> Result<B::Buffer, marshal::Error> {
+        let header = Header {
+            endian: Endian::NATIVE,
+            message_type: MessageType::MethodReturn,
+            flags: Flags::empty(),
+            serial: self.next(),
+            fields: Fields {
+                reply_serial: Some(method_call.serial.get()),
+                destination: method_call.fields.sender,
+                ..Fields::empty()
             },
-            reply_serial: Some(method_call.serial.get()),
-            destination: method_call.fields.sender,
-            ..Fields::empty()
         };
-        marshal::marshal(&Message {
-            header: Header {
-                message_type: MessageType::MethodReturn,
-                flags: Flags::empty(),
-                serial: self.next(),
-                fields,
-            },
-            arguments,
-        })
+        let message = BodySpec(arguments).into_message(header);
+        marshal::marshal_in(&message, &self.buffers)
     }
 
     pub fn error<'a, T: Marshal + MultiSignature>(
         &mut self,
-        name: impl Into<&'a strings::String>,
+        name: impl Into<&'a strings::ErrorName>,
         method_call: &Header,
         arguments: T,
-    ) -> Box<[u8]> {
-        let sig = T::DATA;
-        let signature = sig.signature();
-        let fields = Fields {
-            signature: if signature.is_empty() {
-                None
-            } else {
-                Some(signature)
+    ) -> Result<B::Buffer, marshal::Error> {
+        let header = Header {
+            endian: Endian::NATIVE,
+            message_type: MessageType::Error,
+            flags: Flags::empty(),
+            serial: self.next(),
+            fields: Fields {
+                error_name: Some(name.into()),
+                reply_serial: Some(method_call.serial.get()),
+                destination: method_call.fields.sender,
+                ..Fields::empty()
             },
-            error_name: Some(name.into()),
-            reply_serial: Some(method_call.serial.get()),
-            destination: method_call.fields.sender,
-            ..Fields::empty()
         };
-        marshal::marshal(&Message {
+        let message = BodySpec(arguments).into_message(header);
+        marshal::marshal_in(&message, &self.buffers)
+    }
+
+    /// Numbers and marshals the header of a method return answering `method_call`, whose body
+    /// of the given signature is supplied later to [`ReplySkeleton::finish`].
+    pub fn reply_skeleton(
+        &mut self,
+        method_call: &Header,
+        signature: &strings::Signature,
+    ) -> Result<ReplySkeleton, marshal::Error> {
+        let message = Message {
             header: Header {
-                message_type: MessageType::Error,
+                endian: Endian::NATIVE,
+                message_type: MessageType::MethodReturn,
                 flags: Flags::empty(),
                 serial: self.next(),
-                fields,
+                fields: Fields {
+                    signature: (!signature.is_empty()).then_some(signature),
+                    reply_serial: Some(method_call.serial.get()),
+                    destination: method_call.fields.sender,
+                    ..Fields::empty()
+                },
             },
-            arguments,
+            arguments: types::Empty,
+        };
+        Ok(ReplySkeleton {
+            header: marshal::try_marshal(&message)?,
         })
     }
 
+    /// Answers `method_call` with a method return carrying `T`, or an error reply built from `E`.
+    pub fn reply<'a, T: Marshal + MultiSignature, E: Into<ErrorReply<'a>>>(
+        &mut self,
+        method_call: &Header,
+        result: Result<T, E>,
+    ) -> Result<B::Buffer, marshal::Error> {
+        match result {
+            Ok(arguments) => self.method_return(method_call, arguments),
+            Err(e) => {
+                let ErrorReply { name, message } = e.into();
+                self.error(name, method_call, message.as_str())
+            }
+        }
+    }
+
     pub fn signal<'a, 'b, 'c, T: Marshal + MultiSignature>(
         &mut self,
         path: impl Into<&'a strings::ObjectPath>,
-        interface: impl Into<&'b strings::String>,
-        member: impl Into<&'c strings::String>,
+        interface: impl Into<&'b strings::InterfaceName>,
+        member: impl Into<&'c strings::MemberName>,
         arguments: T,
-    ) -> Box<[u8]> {
-        let sig = T::DATA;
-        let signature = sig.signature();
-        let fields = Fields {
-            signature: if signature.is_empty() {
-                None
-            } else {
-                Some(signature)
+    ) -> Result<B::Buffer, marshal::Error> {
+        let header = Header {
+            endian: Endian::NATIVE,
+            message_type: MessageType::Signal,
+            flags: Flags::empty(),
+            serial: self.next(),
+            fields: Fields {
+                path: Some(path.into()),
+                interface: Some(interface.into()),
+                member: Some(member.into()),
+                ..Fields::empty()
             },
-            path: Some(path.into()),
-            interface: Some(interface.into()),
-            member: Some(member.into()),
-            ..Fields::empty()
         };
-        marshal::marshal(&Message {
-            header: Header {
-                message_type: MessageType::Signal,
-                flags: Flags::empty(),
-                serial: self.next(),
-                fields,
-            },
-            arguments,
-        })
+        let message = BodySpec(arguments).into_message(header);
+        marshal::marshal_in(&message, &self.buffers)
     }
 }
+
+#[test]
+fn test_reply() {
+    let call = test_header();
+    let mut serial = Serial::new();
+    let reply = serial
+        .reply(&call, Err::<u32, _>(unmarshal::Error::InvalidArgs))
+        .unwrap();
+    let msg = MessageIterator::new(&reply).next().unwrap().unwrap();
+    assert_eq!(msg.header.message_type, MessageType::Error);
+    assert_eq!(
+        msg.header.fields.error_name,
+        Some(strings::ErrorName::from_static(
+            "org.freedesktop.DBus.Error.InvalidArgs"
+        ))
+    );
+    assert_eq!(
+        msg.parse::<&strings::String>(),
+        Ok(strings::String::from_str("invalid args"))
+    );
+
+    let reply = serial
+        .reply(&call, Ok::<_, unmarshal::Error>(1u32))
+        .unwrap();
+    let msg = MessageIterator::new(&reply).next().unwrap().unwrap();
+    assert_eq!(msg.header.message_type, MessageType::MethodReturn);
+    assert_eq!(msg.parse::<u32>(), Ok(1));
+
+    let pool = marshal::BufferPool::new(4);
+    let mut serial = serial.with_buffers(&pool);
+    let reply = serial
+        .reply(&call, Ok::<_, unmarshal::Error>(2u32))
+        .unwrap();
+    let msg = MessageIterator::new(&reply).next().unwrap().unwrap();
+    assert_eq!(msg.header.serial.get(), 3);
+    drop(reply);
+    assert_eq!(pool.free_len(), 1);
+}
+
+#[test]
+fn test_dynamic_method() {
+    use unmarshal::Value;
+
+    let proxy = Proxy {
+        name: strings::BusName::from_static("org.example.Calc"),
+        path: strings::ObjectPath::from_str("/"),
+        interface: strings::InterfaceName::from_static("org.example.Calc"),
+    };
+    let method = DynamicMethod::new(proxy, "Add", "uu").unwrap();
+    let mut serial = Serial::new();
+    let call = method
+        .call(&mut serial, Flags::empty(), &[Value::U32(1), Value::U32(2)])
+        .unwrap();
+    let msg = MessageIterator::new(&call).next().unwrap().unwrap();
+    assert_eq!(msg.header.fields.member, Some(method.member));
+    assert_eq!(msg.read_args(|a: u32, b: u32| a + b), Ok(3));
+    assert_eq!(
+        method
+            .call(&mut serial, Flags::empty(), &[Value::U32(1)])
+            .err(),
+        Some(Error::InvalidArgs)
+    );
+    assert_eq!(
+        method
+            .call(&mut serial, Flags::empty(), &[Value::U32(1), Value::U8(2)])
+            .err(),
+        Some(Error::InvalidArgs)
+    );
+    assert_eq!(
+        DynamicMethod::new(proxy, "Add", "a").err(),
+        Some(Error::InvalidArgs)
+    );
+    let long = "u".repeat(256);
+    assert_eq!(
+        DynamicMethod::new(proxy, "Add", &long).err(),
+        Some(Error::InvalidArgs)
+    );
+    let deep = [&"a".repeat(33), "u"].concat();
+    assert_eq!(
+        DynamicMethod::new(proxy, "Add", &deep).err(),
+        Some(Error::InvalidArgs)
+    );
+    assert_eq!(
+        DynamicMethod::new(proxy, "Add.", "u").err(),
+        Some(Error::InvalidArgs)
+    );
+}
+
+#[test]
+fn test_reply_skeleton() {
+    let call = test_header();
+    let mut serial = Serial::new();
+    let skeleton = serial
+        .reply_skeleton(&call, strings::Signature::from_str("su"))
+        .unwrap();
+    assert_eq!(skeleton.header().len() % 8, 0);
+    let body = marshal::marshal(crate::multiple_new!("done", 7u32));
+    let reply = skeleton.finish(&body).unwrap();
+    let msg = MessageIterator::new(&reply).next().unwrap().unwrap();
+    assert_eq!(msg.header.message_type, MessageType::MethodReturn);
+    assert_eq!(msg.header.fields.reply_serial, Some(call.serial.get()));
+    assert_eq!(
+        msg.read_args(|s: &strings::String, n: u32| (s.len(), n)),
+        Ok((4, 7))
+    );
+    let expected = Serial::new().method_return(&call, crate::multiple_new!("done", 7u32));
+    assert_eq!(Ok(reply), expected);
+}
+
+#[test]
+fn test_error_ext() {
+    let data = marshal::marshal(7u32);
+    let mut r = unmarshal::Reader::new(&data);
+    r.read::<u32>().unwrap();
+    let e = r.read::<u64>().unwrap_err();
+    let reply = e.to_error_reply(&r);
+    assert_eq!(
+        reply.name,
+        strings::ErrorName::from_static("org.freedesktop.DBus.Error.InvalidArgs")
+    );
+    assert_eq!(ErrorReply::from(e).name, reply.name);
+    assert_eq!(reply.message, "not enough data at offset 4");
+
+    let reply =
+        unmarshal::Error::BudgetExceeded.to_error_reply(ErrorContext::new().what("options").at(12));
+    assert_eq!(
+        reply.name,
+        strings::ErrorName::from_static("org.freedesktop.DBus.Error.LimitsExceeded")
+    );
+    assert_eq!(
+        reply.message,
+        "decode budget exceeded while reading options at offset 12"
+    );
+
+    let reply = LookupError::UnknownMethod.to_error_reply(ErrorContext::new());
+    assert_eq!(
+        reply.name,
+        strings::ErrorName::from_static("org.freedesktop.DBus.Error.UnknownMethod")
+    );
+    assert_eq!(reply.message, "unknown method");
+}
@@ -0,0 +1,119 @@
+use arrayvec::ArrayVec;
+use thiserror::Error;
+
+use super::*;
+
+/// A message went out under the serial of a method call that is still awaiting its reply.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+#[error("serial {0} reused before its reply arrived")]
+pub struct SerialReused(pub NonZeroU32);
+
+/// Catches serials reused while a method call sent under them still awaits its reply, as
+/// [`Serial::from_raw`] or hand-built headers make easy to do. Keep one per connection and show
+/// it every outgoing message and every incoming reply.
+///
+/// Only the `N` most recent calls are remembered. To track only in debug builds, construct it
+/// with `.with_enabled(cfg!(debug_assertions))`.
+#[derive(Debug, Clone)]
+pub struct SerialTracker<const N: usize = 64> {
+    pending: ArrayVec<NonZeroU32, N>,
+    enabled: bool,
+}
+
+impl<const N: usize> SerialTracker<N> {
+    pub const fn new() -> Self {
+        Self {
+            pending: ArrayVec::new_const(),
+            enabled: true,
+        }
+    }
+    /// When unset, nothing is tracked and every check passes.
+    pub const fn with_enabled(mut self, enabled: bool) -> Self {
+        self.enabled = enabled;
+        self
+    }
+    /// Records an outgoing message, failing if its serial is still pending.
+    pub fn check_sent(&mut self, header: &Header<'_>) -> Result<(), SerialReused> {
+        if !self.enabled {
+            return Ok(());
+        }
+        if self.pending.contains(&header.serial) {
+            Err(SerialReused(header.serial))?
+        }
+        if header.message_type == MessageType::MethodCall && !header.flags.no_reply_expected() {
+            if self.pending.is_full() {
+                self.pending.remove(0);
+            }
+            self.pending.push(header.serial);
+        }
+        Ok(())
+    }
+    /// Records an outgoing message, panicking if its serial is still pending.
+    #[track_caller]
+    pub fn sent(&mut self, header: &Header<'_>) {
+        if let Err(e) = self.check_sent(header) {
+            panic!("{e}");
+        }
+    }
+    /// Records an incoming message, releasing the serial it replies to.
+    pub fn received(&mut self, header: &Header<'_>) {
+        if let Some(reply_serial) = header.fields.reply_serial {
+            self.pending.retain(|x| x.get() != reply_serial);
+        }
+    }
+    /// The serials of calls still awaiting replies, oldest first.
+    pub fn pending(&self) -> &[NonZeroU32] {
+        &self.pending
+    }
+}
+
+impl<const N: usize> Default for SerialTracker<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[test]
+fn test_serial_tracker() {
+    let call = |serial, flags| Header {
+        endian: Endian::NATIVE,
+        message_type: MessageType::MethodCall,
+        flags,
+        serial: NonZeroU32::new(serial).unwrap(),
+        fields: Fields::empty()
+            .path(strings::ObjectPath::from_str("/"))
+            .member(strings::MemberName::from_static("Ping")),
+    };
+    let reply = |reply_serial| Header {
+        endian: Endian::NATIVE,
+        message_type: MessageType::MethodReturn,
+        flags: Flags::empty(),
+        serial: NonZeroU32::new(100).unwrap(),
+        fields: Fields::empty().reply_serial(reply_serial),
+    };
+
+    let mut tracker = SerialTracker::<2>::new();
+    tracker.sent(&call(1, Flags::empty()));
+    tracker.sent(&call(2, Flags::empty().with_no_reply_expected()));
+    tracker.sent(&call(2, Flags::empty()));
+    assert_eq!(
+        tracker.check_sent(&call(1, Flags::empty())),
+        Err(SerialReused(NonZeroU32::new(1).unwrap()))
+    );
+    tracker.received(&reply(1u32));
+    tracker.sent(&call(1, Flags::empty()));
+    assert_eq!(
+        tracker.pending(),
+        [2, 1].map(|x| NonZeroU32::new(x).unwrap())
+    );
+    tracker.sent(&call(3, Flags::empty()));
+    assert_eq!(
+        tracker.pending(),
+        [1, 3].map(|x| NonZeroU32::new(x).unwrap())
+    );
+
+    let mut disabled = SerialTracker::<2>::new().with_enabled(false);
+    disabled.sent(&call(1, Flags::empty()));
+    disabled.sent(&call(1, Flags::empty()));
+    assert!(disabled.pending().is_empty());
+}
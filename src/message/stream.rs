@@ -0,0 +1,167 @@
+use alloc::vec::Vec;
+
+use super::*;
+
+/// Collects bytes read from a stream and splits off each message once it is complete.
+#[derive(Debug, Default, Clone)]
+pub struct StreamDecoder {
+    buffer: Vec<u8>,
+    /// Where the next message starts. Returned messages stay in `buffer` until `push` compacts
+    /// it, so splitting off a message does not move the bytes after it.
+    start: usize,
+    limits: unmarshal::Limits<'static>,
+}
+
+/// What a [`StreamDecoder`] can tell an event loop about its buffered bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Wakeup {
+    /// Whether [`StreamDecoder::next_message`] has a complete message to return.
+    pub ready: bool,
+    /// How many bytes the next message still lacks, once its fixed header has arrived.
+    pub missing: Option<usize>,
+    /// How many bytes to read next: what the next message lacks if known, otherwise what its
+    /// fixed header lacks. Zero when a message is ready.
+    pub read_hint: usize,
+}
+
+impl StreamDecoder {
+    pub const fn new() -> Self {
+        Self {
+            buffer: Vec::new(),
+            start: 0,
+            limits: unmarshal::Limits::new(),
+        }
+    }
+    /// Checks each message against `limits`, rejecting one that is too long as soon as its
+    /// fixed header arrives.
+    pub fn with_limits(self, limits: unmarshal::Limits<'static>) -> Self {
+        Self { limits, ..self }
+    }
+    /// The number of bytes buffered and not yet returned as messages.
+    pub fn buffered(&self) -> usize {
+        self.buffer.len() - self.start
+    }
+    /// Appends `data` read from the stream and reports the state of the next message. Fails
+    /// if the fixed header of the next message is invalid, after which the stream cannot be
+    /// resynchronized.
+    pub fn push(&mut self, data: &[u8]) -> unmarshal::Result<Wakeup> {
+        if self.start > self.buffered() {
+            self.buffer.drain(..self.start);
+            self.start = 0;
+        }
+        self.buffer.extend_from_slice(data);
+        self.wakeup()
+    }
+    /// The state of the next message, as [`StreamDecoder::push`] reports it.
+    pub fn wakeup(&self) -> unmarshal::Result<Wakeup> {
+        let buffer = &self.buffer[self.start..];
+        let buffered = buffer.len();
+        if buffered < FIXED_HEADER_LEN {
+            return Ok(Wakeup {
+                ready: false,
+                missing: None,
+                read_hint: FIXED_HEADER_LEN - buffered,
+            });
+        }
+        let len = Message::frame_len(buffer)?;
+        self.limits.check_message_len(len)?;
+        let missing = len.saturating_sub(buffered);
+        Ok(Wakeup {
+            ready: missing == 0,
+            missing: Some(missing),
+            read_hint: missing,
+        })
+    }
+    /// Removes the next message from the buffer and parses it, or returns `None` if it is not
+    /// complete yet. Fails, without removing anything, where [`StreamDecoder::wakeup`] does.
+    pub fn next_message(&mut self) -> Option<unmarshal::Result<OwnedMessage<Box<[u8]>>>> {
+        match self.wakeup() {
+            Ok(wakeup) if wakeup.ready => {}
+            Ok(_) => return None,
+            Err(e) => return Some(Err(e)),
+        }
+        let buffer = &self.buffer[self.start..];
+        let len = Message::frame_len(buffer).unwrap();
+        let res = MessageIterator::new(&buffer[..len])
+            .with_limits(self.limits)
+            .next()?
+            .map(|x| x.to_owned());
+        self.start += len;
+        Some(res)
+    }
+}
+
+#[test]
+fn test_stream_decoder() {
+    let message = Message {
+        header: test_header(),
+        arguments: strings::String::from_str(":1.1758"),
+    };
+    let bytes = marshal::marshal(&message);
+    let bytes = [&bytes[..], &bytes[..]].concat();
+    let mut decoder = StreamDecoder::new();
+    assert_eq!(
+        decoder.push(&bytes[..10]),
+        Ok(Wakeup {
+            ready: false,
+            missing: None,
+            read_hint: 6,
+        })
+    );
+    let len = Message::frame_len(&bytes).unwrap();
+    assert_eq!(
+        decoder.push(&bytes[10..20]),
+        Ok(Wakeup {
+            ready: false,
+            missing: Some(len - 20),
+            read_hint: len - 20,
+        })
+    );
+    assert!(decoder.next_message().is_none());
+    let wakeup = decoder.push(&bytes[20..]).unwrap();
+    assert!(wakeup.ready);
+    assert_eq!(wakeup.read_hint, 0);
+    let first = decoder.next_message().unwrap().unwrap();
+    assert_eq!(first.header, message.header);
+    assert_eq!(decoder.buffered(), len);
+    assert!(decoder.next_message().unwrap().is_ok());
+    assert_eq!(decoder.wakeup().unwrap().read_hint, FIXED_HEADER_LEN);
+    assert_eq!(decoder.buffered(), 0);
+    decoder.push(&bytes[..len]).unwrap();
+    assert_eq!(decoder.buffer.len(), len);
+    assert_eq!(
+        decoder.next_message().unwrap().unwrap().header,
+        message.header
+    );
+
+    let limits = unmarshal::Limits::new().with_max_message_len(len - 1);
+    let mut decoder = StreamDecoder::new().with_limits(limits);
+    assert_eq!(
+        decoder.push(&bytes[..FIXED_HEADER_LEN]),
+        Err(Error::LimitExceeded(unmarshal::Limit::MessageLen))
+    );
+    assert_eq!(
+        decoder.next_message(),
+        Some(Err(Error::LimitExceeded(unmarshal::Limit::MessageLen)))
+    );
+    assert_eq!(
+        MessageIterator::new(&bytes).with_limits(limits).next(),
+        Some(Err(Error::LimitExceeded(unmarshal::Limit::MessageLen)))
+    );
+    assert_eq!(
+        marshal::try_marshal_limited(&message, &limits),
+        Err(marshal::Error::TooLong { len, max: len - 1 })
+    );
+    let limits = limits.with_max_message_len(len);
+    assert!(
+        MessageIterator::new(&bytes)
+            .with_limits(limits)
+            .next()
+            .unwrap()
+            .is_ok()
+    );
+    assert_eq!(
+        marshal::try_marshal_limited(&message, &limits).as_deref(),
+        Ok(&bytes[..len])
+    );
+}
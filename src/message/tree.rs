@@ -0,0 +1,217 @@
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use core::borrow::Borrow;
+
+use arrayvec::{ArrayVec, CapacityError};
+
+use super::*;
+
+/// Storage for the entries of a [`PathTree`], kept sorted by path.
+pub trait PathEntries<K, T>: Default {
+    fn as_slice(&self) -> &[(K, T)];
+    fn as_mut_slice(&mut self) -> &mut [(K, T)];
+    fn try_insert(&mut self, index: usize, entry: (K, T)) -> Result<(), CapacityError<(K, T)>>;
+    fn remove(&mut self, index: usize) -> (K, T);
+}
+
+impl<K, T, const N: usize> PathEntries<K, T> for ArrayVec<(K, T), N> {
+    fn as_slice(&self) -> &[(K, T)] {
+        self
+    }
+    fn as_mut_slice(&mut self) -> &mut [(K, T)] {
+        self
+    }
+    fn try_insert(&mut self, index: usize, entry: (K, T)) -> Result<(), CapacityError<(K, T)>> {
+        ArrayVec::try_insert(self, index, entry)
+    }
+    fn remove(&mut self, index: usize) -> (K, T) {
+        ArrayVec::remove(self, index)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<K, T> PathEntries<K, T> for Vec<(K, T)> {
+    fn as_slice(&self) -> &[(K, T)] {
+        self
+    }
+    fn as_mut_slice(&mut self) -> &mut [(K, T)] {
+        self
+    }
+    fn try_insert(&mut self, index: usize, entry: (K, T)) -> Result<(), CapacityError<(K, T)>> {
+        self.insert(index, entry);
+        Ok(())
+    }
+    fn remove(&mut self, index: usize) -> (K, T) {
+        Vec::remove(self, index)
+    }
+}
+
+/// Values keyed by object path, such as the objects a server exports, with lookups of whole
+/// subtrees for `GetManagedObjects` and of child nodes for `Introspect`.
+///
+/// Keys are anything that borrows as an [`ObjectPath`](strings::ObjectPath), such as
+/// `&ObjectPath` kept in a fixed-capacity [`ArrayVec`] (the default storage `S`), or
+/// `Box<ObjectPath>` kept in a `Vec` under the `alloc` feature. Entries are kept sorted, and
+/// since `/` sorts before every other byte a path may contain, each subtree is a contiguous
+/// run of them.
+#[derive(Debug, Clone)]
+pub struct PathTree<K, T, S = ArrayVec<(K, T), 16>> {
+    entries: S,
+    marker: PhantomData<(K, T)>,
+}
+
+impl<K, T, S: Default> Default for PathTree<K, T, S> {
+    fn default() -> Self {
+        Self {
+            entries: S::default(),
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<K: Borrow<strings::ObjectPath>, T, S: PathEntries<K, T>> PathTree<K, T, S> {
+    pub fn new() -> Self {
+        Self {
+            entries: S::default(),
+            marker: PhantomData,
+        }
+    }
+    pub fn len(&self) -> usize {
+        self.entries.as_slice().len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    fn search(&self, path: &strings::ObjectPath) -> Result<usize, usize> {
+        self.entries
+            .as_slice()
+            .binary_search_by(|(key, _)| key.borrow().cmp(path))
+    }
+    /// Stores `value` under `key`, returning the value it replaces. Fails, handing the entry
+    /// back, if the storage is full.
+    pub fn insert(&mut self, key: K, value: T) -> Result<Option<T>, CapacityError<(K, T)>> {
+        match self.search(key.borrow()) {
+            Ok(i) => Ok(Some(mem::replace(
+                &mut self.entries.as_mut_slice()[i].1,
+                value,
+            ))),
+            Err(i) => self.entries.try_insert(i, (key, value)).map(|_| None),
+        }
+    }
+    pub fn remove(&mut self, path: &strings::ObjectPath) -> Option<T> {
+        let i = self.search(path).ok()?;
+        Some(self.entries.remove(i).1)
+    }
+    pub fn get(&self, path: &strings::ObjectPath) -> Option<&T> {
+        let i = self.search(path).ok()?;
+        Some(&self.entries.as_slice()[i].1)
+    }
+    pub fn get_mut(&mut self, path: &strings::ObjectPath) -> Option<&mut T> {
+        let i = self.search(path).ok()?;
+        Some(&mut self.entries.as_mut_slice()[i].1)
+    }
+    pub fn contains(&self, path: &strings::ObjectPath) -> bool {
+        self.search(path).is_ok()
+    }
+    /// Every entry, in path order.
+    pub fn iter(&self) -> impl Iterator<Item = (&strings::ObjectPath, &T)> {
+        self.entries
+            .as_slice()
+            .iter()
+            .map(|(key, value)| (key.borrow(), value))
+    }
+    /// The entries at `path` and below it, in path order.
+    pub fn subtree<'t>(
+        &'t self,
+        path: &'t strings::ObjectPath,
+    ) -> impl Iterator<Item = (&'t strings::ObjectPath, &'t T)> {
+        let start = self.search(path).unwrap_or_else(|i| i);
+        self.entries.as_slice()[start..]
+            .iter()
+            .map(|(key, value)| (key.borrow(), value))
+            .take_while(move |(key, _)| key.starts_with_path(path))
+    }
+    /// The names of the nodes directly below `path` that have an entry at or below them, each
+    /// once, as `Introspect` lists them.
+    pub fn children<'t>(&'t self, path: &'t strings::ObjectPath) -> impl Iterator<Item = &'t str> {
+        let mut last = None;
+        self.subtree(path).filter_map(move |(key, _)| {
+            let rest = &key.as_bytes()[path.len()..];
+            // Below the root, the separator is already part of `path`.
+            let rest = rest.strip_prefix(b"/").unwrap_or(rest);
+            let name = rest
+                .split(|&x| x == b'/')
+                .next()
+                .filter(|x| !x.is_empty())?;
+            // SAFETY: object paths are ASCII.
+            let name = unsafe { core::str::from_utf8_unchecked(name) };
+            (last.replace(name) != Some(name)).then_some(name)
+        })
+    }
+}
+
+#[test]
+fn test_path_tree() {
+    let path = strings::ObjectPath::from_str;
+    let mut tree = PathTree::<&strings::ObjectPath, u32>::new();
+    assert!(PathTree::<&strings::ObjectPath, &str>::default().is_empty());
+    for (i, x) in ["/org/a/x", "/org/a", "/org/b", "/org/a0", "/org/a/y/z", "/"]
+        .into_iter()
+        .enumerate()
+    {
+        assert_eq!(tree.insert(path(x), i as u32), Ok(None));
+    }
+    assert_eq!(tree.insert(path("/org/b"), 7), Ok(Some(2)));
+    assert_eq!(tree.get(path("/org/b")), Some(&7));
+    assert_eq!(tree.get(path("/org")), None);
+
+    let subtree = |x| {
+        tree.subtree(path(x))
+            .map(|(k, _)| k.as_bytes())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        subtree("/org/a"),
+        [&b"/org/a"[..], b"/org/a/x", b"/org/a/y/z"]
+    );
+    assert_eq!(subtree("/org").len(), 5);
+    assert_eq!(subtree("/").len(), 6);
+
+    let children = |x| tree.children(path(x)).collect::<Vec<_>>();
+    assert_eq!(children("/"), ["org"]);
+    assert_eq!(children("/org"), ["a", "a0", "b"]);
+    assert_eq!(children("/org/a"), ["x", "y"]);
+    assert!(children("/org/b").is_empty());
+
+    assert_eq!(tree.remove(path("/org/a")), Some(1));
+    assert_eq!(tree.len(), 5);
+    assert_eq!(
+        tree.children(path("/org")).collect::<Vec<_>>(),
+        ["a", "a0", "b"]
+    );
+
+    let mut full = PathTree::<_, (), ArrayVec<_, 1>>::new();
+    assert_eq!(full.insert(path("/a"), ()), Ok(None));
+    assert!(full.insert(path("/b"), ()).is_err());
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_path_tree_vec() {
+    let mut tree = PathTree::<Box<strings::ObjectPath>, u32, Vec<_>>::new();
+    for i in 0..100u32 {
+        let key = alloc::format!("/node/n{i}");
+        let key = strings::ObjectPath::from_str(&key).to_owned();
+        assert_eq!(tree.insert(key, i), Ok(None));
+    }
+    assert_eq!(tree.len(), 100);
+    assert_eq!(
+        tree.get_mut(strings::ObjectPath::from_str("/node/n42")),
+        Some(&mut 42)
+    );
+    assert_eq!(
+        tree.children(strings::ObjectPath::from_str("/node"))
+            .count(),
+        100
+    );
+}
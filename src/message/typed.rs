@@ -0,0 +1,133 @@
+//! Messages tagged with their type, so that accessors exist only where the field is required.
+
+use core::{marker::PhantomData, ops::Deref};
+
+use super::{Message, MessageType};
+use crate::{strings, unmarshal};
+
+/// Marker of a method call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Call;
+/// Marker of a method return.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Return;
+/// Marker of an error reply.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Error;
+/// Marker of a signal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Signal;
+
+/// A [`Message`] whose type is `K` and whose header has every field `K` requires.
+///
+/// Derefs to the untyped message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Typed<'a, K, T> {
+    message: Message<'a, T>,
+    marker: PhantomData<K>,
+}
+
+/// A [`Message`] sorted by type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Classified<'a, T> {
+    Call(Typed<'a, Call, T>),
+    Return(Typed<'a, Return, T>),
+    Error(Typed<'a, Error, T>),
+    Signal(Typed<'a, Signal, T>),
+}
+
+impl<'a, T> Message<'a, T> {
+    /// Tags the message with its type, failing with
+    /// [`Error::InvalidHeader`](unmarshal::Error::InvalidHeader) if a field its type requires
+    /// is missing.
+    pub fn classify(self) -> unmarshal::Result<Classified<'a, T>> {
+        self.kind()?;
+        Ok(match self.header.message_type {
+            MessageType::MethodCall => Classified::Call(Typed::new(self)),
+            MessageType::MethodReturn => Classified::Return(Typed::new(self)),
+            MessageType::Error => Classified::Error(Typed::new(self)),
+            MessageType::Signal => Classified::Signal(Typed::new(self)),
+        })
+    }
+}
+
+impl<'a, K, T> Typed<'a, K, T> {
+    const fn new(message: Message<'a, T>) -> Self {
+        Self {
+            message,
+            marker: PhantomData,
+        }
+    }
+    pub fn into_message(self) -> Message<'a, T> {
+        self.message
+    }
+}
+
+impl<'a, K, T> Deref for Typed<'a, K, T> {
+    type Target = Message<'a, T>;
+
+    fn deref(&self) -> &Message<'a, T> {
+        &self.message
+    }
+}
+
+// The unwraps below cannot fail: `classify` checked that the fields of each type are present.
+
+impl<'a, T> Typed<'a, Call, T> {
+    pub fn path(&self) -> &'a strings::ObjectPath {
+        self.header.fields.path.unwrap()
+    }
+    pub fn interface(&self) -> Option<&'a strings::InterfaceName> {
+        self.header.fields.interface
+    }
+    pub fn member(&self) -> &'a strings::MemberName {
+        self.header.fields.member.unwrap()
+    }
+}
+
+impl<T> Typed<'_, Return, T> {
+    pub fn reply_serial(&self) -> u32 {
+        self.header.fields.reply_serial.unwrap()
+    }
+}
+
+impl<'a, T> Typed<'a, Error, T> {
+    pub fn error_name(&self) -> &'a strings::ErrorName {
+        self.header.fields.error_name.unwrap()
+    }
+    pub fn reply_serial(&self) -> u32 {
+        self.header.fields.reply_serial.unwrap()
+    }
+}
+
+impl<'a, T> Typed<'a, Signal, T> {
+    pub fn path(&self) -> &'a strings::ObjectPath {
+        self.header.fields.path.unwrap()
+    }
+    pub fn interface(&self) -> &'a strings::InterfaceName {
+        self.header.fields.interface.unwrap()
+    }
+    pub fn member(&self) -> &'a strings::MemberName {
+        self.header.fields.member.unwrap()
+    }
+}
+
+#[test]
+fn test_classify() {
+    let message = Message {
+        header: super::test_header(),
+        arguments: (),
+    };
+    let Ok(Classified::Signal(signal)) = message.classify() else {
+        panic!()
+    };
+    assert_eq!(
+        signal.member(),
+        strings::MemberName::from_static("NameAcquired")
+    );
+    assert_eq!(signal.into_message(), message);
+
+    let mut error = message;
+    error.header.message_type = MessageType::Error;
+    assert_eq!(error.classify(), Err(unmarshal::Error::InvalidHeader));
+}
@@ -0,0 +1,121 @@
+//! Hooks for counting what is encoded and decoded, e.g. to export as service metrics.
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::unmarshal;
+
+/// Receives statistics from the readers, iterators and marshalling functions it is given to.
+/// Every method does nothing by default.
+pub trait Metrics: Sync {
+    /// A whole message of `len` bytes was decoded by a [`MessageIterator`](crate::MessageIterator).
+    fn message_decoded(&self, len: usize) {
+        let _ = len;
+    }
+    /// A whole message of `len` bytes was marshalled by
+    /// [`marshal_with_metrics`](crate::marshal::marshal_with_metrics).
+    fn message_encoded(&self, len: usize) {
+        let _ = len;
+    }
+    /// Decoding failed with `error`.
+    fn error(&self, error: &unmarshal::Error) {
+        let _ = error;
+    }
+    /// An [`Iter`](unmarshal::Iter) entered a variant `depth` variants deep.
+    fn nesting(&self, depth: usize) {
+        let _ = depth;
+    }
+    /// `bytes` of alignment padding were skipped or written.
+    fn padding(&self, bytes: usize) {
+        let _ = bytes;
+    }
+}
+
+/// Counts everything reported, for metrics that are read out periodically.
+#[derive(Debug, Default)]
+pub struct Counters {
+    pub messages_decoded: AtomicUsize,
+    pub bytes_decoded: AtomicUsize,
+    pub messages_encoded: AtomicUsize,
+    pub bytes_encoded: AtomicUsize,
+    pub errors: AtomicUsize,
+    pub max_nesting: AtomicUsize,
+    pub padding: AtomicUsize,
+}
+
+impl Counters {
+    pub const fn new() -> Self {
+        Self {
+            messages_decoded: AtomicUsize::new(0),
+            bytes_decoded: AtomicUsize::new(0),
+            messages_encoded: AtomicUsize::new(0),
+            bytes_encoded: AtomicUsize::new(0),
+            errors: AtomicUsize::new(0),
+            max_nesting: AtomicUsize::new(0),
+            padding: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl Metrics for Counters {
+    fn message_decoded(&self, len: usize) {
+        self.messages_decoded.fetch_add(1, Ordering::Relaxed);
+        self.bytes_decoded.fetch_add(len, Ordering::Relaxed);
+    }
+    fn message_encoded(&self, len: usize) {
+        self.messages_encoded.fetch_add(1, Ordering::Relaxed);
+        self.bytes_encoded.fetch_add(len, Ordering::Relaxed);
+    }
+    fn error(&self, _: &unmarshal::Error) {
+        self.errors.fetch_add(1, Ordering::Relaxed);
+    }
+    fn nesting(&self, depth: usize) {
+        self.max_nesting.fetch_max(depth, Ordering::Relaxed);
+    }
+    fn padding(&self, bytes: usize) {
+        self.padding.fetch_add(bytes, Ordering::Relaxed);
+    }
+}
+
+#[test]
+fn test_metrics() {
+    use crate::*;
+
+    let counters = Counters::new();
+    let message = Message {
+        header: Header {
+            endian: Endian::NATIVE,
+            message_type: MessageType::Signal,
+            flags: Flags::empty(),
+            serial: core::num::NonZeroU32::new(1).unwrap(),
+            fields: Fields::empty()
+                .path(strings::ObjectPath::from_str("/"))
+                .interface(strings::InterfaceName::from_static("org.example.Metrics"))
+                .member(strings::MemberName::from_static("Changed"))
+                .signature(strings::Signature::from_str("yv")),
+        },
+        arguments: crate::multiple_new!(1u8, Variant(Variant(2u64))),
+    };
+    let bytes = crate::marshal::marshal_with_metrics(&message, &counters);
+    assert_eq!(counters.messages_encoded.load(Ordering::Relaxed), 1);
+    assert_eq!(counters.bytes_encoded.load(Ordering::Relaxed), bytes.len());
+    let encoded_padding = counters.padding.swap(0, Ordering::Relaxed);
+    assert!(encoded_padding > 0);
+
+    let mut messages = MessageIterator::new(&bytes).with_metrics(&counters);
+    let message = messages.next().unwrap().unwrap();
+    assert_eq!(counters.messages_decoded.load(Ordering::Relaxed), 1);
+    assert_eq!(counters.bytes_decoded.load(Ordering::Relaxed), bytes.len());
+
+    let signature = message.header.fields.signature.unwrap();
+    let iter = unmarshal::Iter::new(signature, message.arguments)
+        .unwrap()
+        .with_metrics(&counters);
+    assert_eq!(iter.count(), 6);
+    assert_eq!(counters.max_nesting.load(Ordering::Relaxed), 2);
+    assert!(counters.padding.load(Ordering::Relaxed) > 0);
+
+    assert!(messages.next().is_none());
+    let mut truncated = MessageIterator::new(&bytes[..bytes.len() - 1]).with_metrics(&counters);
+    assert!(truncated.next().unwrap().is_err());
+    assert_eq!(counters.errors.load(Ordering::Relaxed), 1);
+}
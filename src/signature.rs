@@ -1,4 +1,4 @@
-use core::{mem, slice};
+use core::{marker::Freeze, mem, num::NonZero, slice};
 
 use crate::strings;
 
@@ -7,7 +7,7 @@ mod private {
 }
 use private::Sealed;
 
-pub trait Node: Sealed {
+pub trait Node: Sealed + Freeze + 'static {
     fn signature(&self) -> &strings::Signature;
 }
 
@@ -69,10 +69,51 @@ pub struct Quadruple<X, Y, Z, W>(pub X, pub Y, pub Z, pub W);
 pub unsafe trait MultiSignature {
     type Data: Node;
     const DATA: Self::Data;
+    /// The wire signature, borrowed for `'static`.
+    const SIGNATURE: &'static strings::Signature =
+        strings::Signature::from_bytes(signature_bytes::<Self>());
 }
 
 pub unsafe trait Signature: MultiSignature {
     const ALIGNMENT: usize;
+    /// The marshalled size of every value, if it does not depend on the value. See
+    /// [`fixed_size`](crate::marshal::fixed_size).
+    const FIXED_SIZE: Option<usize> = crate::marshal::fixed_size::<Self>();
+}
+
+/// The raw signature bytes of `T`, usable in const context.
+#[doc(hidden)]
+pub const fn signature_bytes<T: MultiSignature + ?Sized>() -> &'static [u8] {
+    let data: &'static T::Data = const { &T::DATA };
+    unsafe {
+        slice::from_raw_parts(
+            data as *const T::Data as *const u8,
+            mem::size_of::<T::Data>(),
+        )
+    }
+}
+
+#[doc(hidden)]
+pub const fn signature_is<T: MultiSignature + ?Sized>(signature: &str) -> bool {
+    signature_bytes::<T>() == signature.as_bytes()
+}
+
+/// Fails compilation unless the wire signature of a type is exactly the given string.
+#[macro_export]
+macro_rules! assert_signature_eq {
+    ($t:ty, $signature:literal $(,)?) => {
+        const _: () = assert!(
+            $crate::signature::signature_is::<$t>($signature),
+            "{}",
+            concat!(
+                "signature of `",
+                stringify!($t),
+                "` is not \"",
+                $signature,
+                "\""
+            ),
+        );
+    };
 }
 
 pub trait SignatureProxy {
@@ -115,6 +156,16 @@ impl_signature! {
     f64 = b'd',
 }
 
+macro_rules! impl_non_zero {
+    ($($t:ty),* $(,)?) => {
+        $(impl SignatureProxy for NonZero<$t> {
+            type Proxy = $t;
+        })*
+    };
+}
+
+impl_non_zero!(u8, i16, u16, i32, u32, i64, u64);
+
 unsafe impl MultiSignature for bool {
     type Data = u8;
 
@@ -140,6 +191,27 @@ unsafe impl Signature for strings::String {
     const ALIGNMENT: usize = 4;
 }
 
+macro_rules! impl_name_signature {
+    ($($t:ty),* $(,)?) => {
+        $(unsafe impl MultiSignature for $t {
+            type Data = u8;
+            const DATA: Self::Data = b's';
+        }
+        unsafe impl Signature for $t {
+            const ALIGNMENT: usize = 4;
+        })*
+    };
+}
+
+impl_name_signature!(
+    strings::BusName,
+    strings::UniqueName,
+    strings::WellKnownName,
+    strings::InterfaceName,
+    strings::MemberName,
+    strings::ErrorName,
+);
+
 unsafe impl MultiSignature for strings::Signature {
     type Data = u8;
     const DATA: Self::Data = b'g';
@@ -164,6 +236,214 @@ unsafe impl<T: Signature> Signature for [T] {
     const ALIGNMENT: usize = 4;
 }
 
+#[cfg(feature = "alloc")]
+impl<T: Signature> SignatureProxy for alloc::vec::Vec<T> {
+    type Proxy = [T];
+}
+#[cfg(feature = "alloc")]
+impl<T: Signature> SignatureProxy for alloc::boxed::Box<[T]> {
+    type Proxy = [T];
+}
+#[cfg(feature = "alloc")]
+impl<K: Signature, V: Signature> SignatureProxy for alloc::collections::BTreeMap<K, V> {
+    type Proxy = [crate::Entry<K, V>];
+}
+#[cfg(feature = "std")]
+impl<K: Signature, V: Signature, S> SignatureProxy for std::collections::HashMap<K, V, S> {
+    type Proxy = [crate::Entry<K, V>];
+}
+
+/// How deeply arrays, and separately structs and dict entries, may nest in a signature.
+pub const MAX_CONTAINER_DEPTH: usize = 32;
+
+/// The most containers a value may be nested in, counting arrays, structs, dict entries and
+/// variants together: the spec's 32 levels of arrays plus 32 of structs.
+pub const MAX_NESTING_DEPTH: usize = 2 * MAX_CONTAINER_DEPTH;
+
+/// Whether `code` is a basic type, the kind a dict entry key must be.
+pub const fn is_basic(code: u8) -> bool {
+    matches!(
+        code,
+        b'y' | b'b' | b'n' | b'q' | b'i' | b'u' | b'x' | b't' | b'd' | b'h' | b's' | b'o' | b'g'
+    )
+}
+
+/// Alignment of values whose type starts with `code`, or `None` if `code` cannot start a type.
+pub const fn code_alignment(code: u8) -> Option<usize> {
+    Some(match code {
+        b'y' | b'g' | b'v' => 1,
+        b'n' | b'q' => 2,
+        b'b' | b'i' | b'u' | b'h' | b's' | b'o' | b'a' => 4,
+        b'x' | b't' | b'd' | b'(' | b'{' => 8,
+        _ => None?,
+    })
+}
+
+/// A fixed-size basic type outside the D-Bus type system, such as a private bus's vendor type.
+///
+/// Registered with [`Iter::with_extensions`](crate::unmarshal::Iter::with_extensions), which then
+/// accepts `code` in signatures and yields its values as
+/// [`Token::Extension`](crate::unmarshal::Token::Extension). `size` must be nonzero and
+/// `alignment` a power of two.
+#[cfg(feature = "extensions")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Extension {
+    pub code: u8,
+    pub alignment: usize,
+    pub size: usize,
+}
+
+#[cfg(not(feature = "extensions"))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Extension {}
+
+#[cfg(feature = "extensions")]
+pub(crate) const fn find_extension(code: u8, extensions: &[Extension]) -> Option<&Extension> {
+    let mut i = 0;
+    while i < extensions.len() {
+        if extensions[i].code == code {
+            return Some(&extensions[i]);
+        }
+        i += 1;
+    }
+    None
+}
+
+/// The largest alignment of any type in `signature`, which decides at which offsets values of
+/// it can be copied without changing their padding. Variants count as 8, as they may hold
+/// anything.
+pub(crate) fn max_alignment(signature: &[u8]) -> usize {
+    signature
+        .iter()
+        .filter_map(|&x| {
+            if x == b'v' {
+                Some(8)
+            } else {
+                code_alignment(x)
+            }
+        })
+        .max()
+        .unwrap_or(1)
+}
+
+/// Like [`code_alignment`], also accepting the codes of `extensions`.
+pub(crate) const fn code_alignment_with(code: u8, extensions: &[Extension]) -> Option<usize> {
+    if let Some(alignment) = code_alignment(code) {
+        return Some(alignment);
+    }
+    #[cfg(feature = "extensions")]
+    if let Some(extension) = find_extension(code, extensions) {
+        return Some(extension.alignment);
+    }
+    let _ = extensions;
+    None
+}
+
+/// Length of the single complete type at the front of `signature`, or `None` if it is malformed.
+pub const fn complete_type_len(signature: &[u8]) -> Option<usize> {
+    complete_type_len_with(signature, &[])
+}
+
+/// Like [`complete_type_len`], also accepting the codes of `extensions` as basic types.
+pub(crate) const fn complete_type_len_with(
+    signature: &[u8],
+    extensions: &[Extension],
+) -> Option<usize> {
+    type_len(signature, extensions, None)
+}
+
+/// Like [`complete_type_len`], but also applying the limits of [`MAX_CONTAINER_DEPTH`] and
+/// the rules for dict entries: only directly inside an array, with a basic key and one value.
+pub(crate) const fn strict_type_len(signature: &[u8]) -> Option<usize> {
+    type_len(signature, &[], Some(Open::NONE))
+}
+
+/// The containers open around a type in a strict walk of a signature.
+#[derive(Clone, Copy)]
+struct Open {
+    arrays: usize,
+    structs: usize,
+    in_array: bool,
+}
+
+impl Open {
+    const NONE: Self = Self {
+        arrays: 0,
+        structs: 0,
+        in_array: false,
+    };
+}
+
+/// Length of the single complete type at the front of `signature`. With `open`, the walk is
+/// strict and `open` says which containers are around the type.
+const fn type_len(signature: &[u8], extensions: &[Extension], open: Option<Open>) -> Option<usize> {
+    let [code, rest @ ..] = signature else {
+        return None;
+    };
+    match *code {
+        b'a' => {
+            let inner = match open {
+                Some(Open { arrays, .. }) if arrays >= MAX_CONTAINER_DEPTH => return None,
+                Some(open) => Some(Open {
+                    arrays: open.arrays + 1,
+                    in_array: true,
+                    ..open
+                }),
+                None => None,
+            };
+            match type_len(rest, extensions, inner) {
+                Some(n) => Some(n + 1),
+                None => None,
+            }
+        }
+        b'(' | b'{' => {
+            let entry = *code == b'{';
+            let inner = match open {
+                Some(Open { structs, .. }) if structs >= MAX_CONTAINER_DEPTH => return None,
+                Some(Open {
+                    in_array: false, ..
+                }) if entry => return None,
+                Some(open) => Some(Open {
+                    structs: open.structs + 1,
+                    in_array: false,
+                    ..open
+                }),
+                None => None,
+            };
+            let close = if entry { b'}' } else { b')' };
+            let mut i = 1;
+            let mut fields = 0;
+            while i < signature.len() && signature[i] != close {
+                if entry && fields == 0 && open.is_some() && !is_basic(signature[i]) {
+                    return None;
+                }
+                match type_len(signature.split_at(i).1, extensions, inner) {
+                    Some(n) => i += n,
+                    None => return None,
+                }
+                fields += 1;
+            }
+            if fields == 0 || i >= signature.len() || (entry && open.is_some() && fields != 2) {
+                return None;
+            }
+            Some(i + 1)
+        }
+        code => match code_alignment_with(code, extensions) {
+            Some(_) => Some(1),
+            None => None,
+        },
+    }
+}
+
+#[test]
+fn test_complete_type_len() {
+    assert_eq!(complete_type_len(b"a{sv}i"), Some(5));
+    assert_eq!(complete_type_len(b"(ia(yy))"), Some(8));
+    assert_eq!(complete_type_len(b"()"), None);
+    assert_eq!(complete_type_len(b"(i"), None);
+    assert_eq!(complete_type_len(b"a"), None);
+}
+
 #[test]
 fn test_signature() {
     type T = crate::struct_type!(u8, u32, i16);
@@ -173,4 +453,11 @@ fn test_signature() {
     assert_eq!(z, 2);
 
     assert_eq!(T::DATA.signature(), strings::Signature::from_str("(yun)"));
+    const SIGNATURE: &strings::Signature = <[T]>::SIGNATURE;
+    assert_eq!(SIGNATURE, strings::Signature::from_str("a(yun)"));
+    crate::assert_signature_eq!(T, "(yun)");
+    crate::assert_signature_eq!([crate::Entry<&str, crate::Variant<()>>], "a{sv}");
 }
+
+pub use builder::*;
+mod builder;
@@ -0,0 +1,253 @@
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+use arrayvec::ArrayVec;
+use thiserror::Error;
+
+use super::{MAX_CONTAINER_DEPTH, is_basic};
+use crate::strings;
+
+/// The longest signature the specification allows.
+pub const MAX_SIGNATURE_LEN: usize = 255;
+
+/// Why a [`SignatureBuilder`] refused a step.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum BuildError {
+    #[error("'{}' is not a basic type", *.0 as char)]
+    NotBasic(u8),
+    #[error("signature longer than {MAX_SIGNATURE_LEN} bytes")]
+    TooLong,
+    #[error("containers nested too deeply")]
+    TooDeep,
+    #[error("dict entry outside of an array")]
+    MisplacedDictEntry,
+    #[error("dict entry key is not a basic type")]
+    DictKeyNotBasic,
+    #[error("dict entry does not have exactly two fields")]
+    DictEntryArity,
+    #[error("empty struct")]
+    EmptyStruct,
+    #[error("nothing to close")]
+    NothingOpen,
+    #[error("unclosed container")]
+    Unclosed,
+}
+
+/// Storage for the bytes of a [`SignatureBuilder`].
+pub trait SignatureBuf: Default {
+    fn push(&mut self, code: u8) -> Result<(), BuildError>;
+    fn as_bytes(&self) -> &[u8];
+}
+
+impl<const N: usize> SignatureBuf for ArrayVec<u8, N> {
+    fn push(&mut self, code: u8) -> Result<(), BuildError> {
+        self.try_push(code).map_err(|_| BuildError::TooLong)
+    }
+    fn as_bytes(&self) -> &[u8] {
+        self
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl SignatureBuf for Vec<u8> {
+    fn push(&mut self, code: u8) -> Result<(), BuildError> {
+        Vec::push(self, code);
+        Ok(())
+    }
+    fn as_bytes(&self) -> &[u8] {
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Frame {
+    /// An array whose element type is not complete yet.
+    Array,
+    Struct {
+        fields: usize,
+    },
+    DictEntry {
+        fields: usize,
+    },
+}
+
+/// Composes a signature one type at a time, rejecting each step that would make it invalid.
+///
+/// The bytes live in `B`: an [`ArrayVec`] of the maximum signature length by default, or a
+/// `Vec` with the `alloc` feature.
+#[derive(Debug, Clone, Default)]
+pub struct SignatureBuilder<B = ArrayVec<u8, MAX_SIGNATURE_LEN>> {
+    buf: B,
+    stack: ArrayVec<Frame, { 2 * MAX_CONTAINER_DEPTH }>,
+}
+
+impl<B: SignatureBuf> SignatureBuilder<B> {
+    pub fn new() -> Self {
+        Self {
+            buf: B::default(),
+            stack: ArrayVec::new(),
+        }
+    }
+    /// Appends a basic type, such as `b'u'` or `b's'`.
+    pub fn push_basic(&mut self, code: u8) -> Result<&mut Self, BuildError> {
+        if !is_basic(code) {
+            Err(BuildError::NotBasic(code))?
+        }
+        self.start(true)?;
+        self.write(code)?;
+        self.complete();
+        Ok(self)
+    }
+    pub fn push_variant(&mut self) -> Result<&mut Self, BuildError> {
+        self.start(false)?;
+        self.write(b'v')?;
+        self.complete();
+        Ok(self)
+    }
+    /// Starts an array; the next complete type is its element type.
+    pub fn open_array(&mut self) -> Result<&mut Self, BuildError> {
+        self.open(Frame::Array, b'a')
+    }
+    pub fn open_struct(&mut self) -> Result<&mut Self, BuildError> {
+        self.open(Frame::Struct { fields: 0 }, b'(')
+    }
+    /// Starts a dict entry, which must be the element type of a just opened array.
+    pub fn open_dict_entry(&mut self) -> Result<&mut Self, BuildError> {
+        if self.stack.last() != Some(&Frame::Array) {
+            Err(BuildError::MisplacedDictEntry)?
+        }
+        self.open(Frame::DictEntry { fields: 0 }, b'{')
+    }
+    /// Closes the innermost struct or dict entry.
+    pub fn close(&mut self) -> Result<&mut Self, BuildError> {
+        let close = match self.stack.last() {
+            Some(Frame::Struct { fields: 0 }) => Err(BuildError::EmptyStruct)?,
+            Some(Frame::Struct { .. }) => b')',
+            Some(Frame::DictEntry { fields: 2 }) => b'}',
+            Some(Frame::DictEntry { .. }) => Err(BuildError::DictEntryArity)?,
+            Some(Frame::Array) | None => Err(BuildError::NothingOpen)?,
+        };
+        self.write(close)?;
+        self.stack.pop();
+        self.complete();
+        Ok(self)
+    }
+    /// The signature built so far, once every container is closed.
+    pub fn signature(&self) -> Result<&strings::Signature, BuildError> {
+        if !self.stack.is_empty() {
+            Err(BuildError::Unclosed)?
+        }
+        Ok(strings::Signature::from_bytes(self.buf.as_bytes()))
+    }
+    /// The bytes of the signature, once every container is closed.
+    pub fn finish(self) -> Result<B, BuildError> {
+        self.signature()?;
+        Ok(self.buf)
+    }
+    fn write(&mut self, code: u8) -> Result<(), BuildError> {
+        if self.buf.as_bytes().len() >= MAX_SIGNATURE_LEN {
+            Err(BuildError::TooLong)?
+        }
+        self.buf.push(code)
+    }
+    /// Checks that a type may begin here, given whether it is basic.
+    fn start(&self, basic: bool) -> Result<(), BuildError> {
+        match self.stack.last() {
+            Some(Frame::DictEntry { fields: 0 }) if !basic => Err(BuildError::DictKeyNotBasic),
+            Some(Frame::DictEntry { fields: 2 }) => Err(BuildError::DictEntryArity),
+            _ => Ok(()),
+        }
+    }
+    fn open(&mut self, frame: Frame, code: u8) -> Result<&mut Self, BuildError> {
+        self.start(false)?;
+        let is_array = |x: &Frame| *x == Frame::Array;
+        let depth = self
+            .stack
+            .iter()
+            .filter(|x| is_array(x) == is_array(&frame))
+            .count();
+        if depth >= MAX_CONTAINER_DEPTH {
+            Err(BuildError::TooDeep)?
+        }
+        self.write(code)?;
+        self.stack.push(frame);
+        Ok(self)
+    }
+    /// Records that a complete type ended, which completes any arrays it is the element of.
+    fn complete(&mut self) {
+        while self.stack.last() == Some(&Frame::Array) {
+            self.stack.pop();
+        }
+        if let Some(Frame::Struct { fields } | Frame::DictEntry { fields }) = self.stack.last_mut()
+        {
+            *fields += 1;
+        }
+    }
+}
+
+#[test]
+fn test_signature_builder() {
+    let mut builder = SignatureBuilder::<ArrayVec<u8, MAX_SIGNATURE_LEN>>::new();
+    builder
+        .push_basic(b'u')
+        .unwrap()
+        .open_array()
+        .unwrap()
+        .open_dict_entry()
+        .unwrap()
+        .push_basic(b's')
+        .unwrap()
+        .push_variant()
+        .unwrap()
+        .close()
+        .unwrap()
+        .open_struct()
+        .unwrap()
+        .open_array()
+        .unwrap()
+        .push_basic(b'y')
+        .unwrap()
+        .close()
+        .unwrap();
+    assert_eq!(
+        builder.signature(),
+        Ok(strings::Signature::from_str("ua{sv}(ay)"))
+    );
+
+    let mut builder = SignatureBuilder::<ArrayVec<u8, MAX_SIGNATURE_LEN>>::new();
+    assert_eq!(
+        builder.open_dict_entry().err(),
+        Some(BuildError::MisplacedDictEntry)
+    );
+    assert_eq!(
+        builder.push_basic(b'v').err(),
+        Some(BuildError::NotBasic(b'v'))
+    );
+    builder.open_array().unwrap().open_dict_entry().unwrap();
+    assert_eq!(
+        builder.push_variant().err(),
+        Some(BuildError::DictKeyNotBasic)
+    );
+    builder.push_basic(b's').unwrap();
+    assert_eq!(builder.close().err(), Some(BuildError::DictEntryArity));
+    assert_eq!(builder.signature(), Err(BuildError::Unclosed));
+    builder.open_struct().unwrap();
+    assert_eq!(builder.close().err(), Some(BuildError::EmptyStruct));
+
+    let mut builder = SignatureBuilder::<ArrayVec<u8, MAX_SIGNATURE_LEN>>::new();
+    for _ in 0..MAX_CONTAINER_DEPTH {
+        builder.open_array().unwrap();
+    }
+    assert_eq!(builder.open_array().err(), Some(BuildError::TooDeep));
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_signature_builder_vec() {
+    let mut builder = SignatureBuilder::<Vec<u8>>::new();
+    for _ in 0..MAX_SIGNATURE_LEN {
+        builder.push_basic(b'y').unwrap();
+    }
+    assert_eq!(builder.push_basic(b'y').err(), Some(BuildError::TooLong));
+    assert_eq!(builder.finish().unwrap().len(), MAX_SIGNATURE_LEN);
+}
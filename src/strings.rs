@@ -1,11 +1,15 @@
 #[cfg(feature = "alloc")]
-use alloc::{borrow::ToOwned, boxed::Box};
+use alloc::{borrow::ToOwned, boxed::Box, vec::Vec};
 use core::{
     fmt::{self, Debug, Display, Formatter},
     mem,
     ops::Deref,
 };
 
+use thiserror::Error as ThisError;
+
+use crate::unmarshal::Error;
+
 #[repr(transparent)]
 #[derive(PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Signature([u8]);
@@ -21,9 +25,6 @@ pub struct ObjectPath([u8]);
 macro_rules! impl_string {
     ($($t:ty),* $(,)?) => {
         $(impl $t {
-            pub const fn as_bytes(&self) -> &[u8] {
-                &self.0
-            }
             pub const fn from_bytes(bytes: &[u8]) -> &Self {
                 unsafe { mem::transmute(bytes) }
             }
@@ -31,6 +32,30 @@ macro_rules! impl_string {
                 <$t>::from_bytes(bytes.as_bytes())
             }
         }
+        impl<'a> const From<&'a str> for &'a $t {
+            fn from(s: &'a str) -> Self {
+                <$t>::from_str(s)
+            }
+        }
+        impl<'a> const From<&'a [u8]> for &'a $t {
+            fn from(s: &'a [u8]) -> Self {
+                <$t>::from_bytes(s)
+            }
+        }
+        #[cfg(feature = "alloc")]
+        impl From<Box<[u8]>> for Box<$t> {
+            fn from(s: Box<[u8]>) -> Self {
+                unsafe { mem::transmute(s) }
+            }
+        })*
+        impl_string!(@common $($t),*);
+    };
+    (@common $($t:ty),*) => {
+        $(impl $t {
+            pub const fn as_bytes(&self) -> &[u8] {
+                &self.0
+            }
+        }
         impl const Deref for $t {
             type Target = [u8];
 
@@ -50,16 +75,6 @@ macro_rules! impl_string {
                 write!(f, "{s}")
             }
         }
-        impl<'a> const From<&'a str> for &'a $t {
-            fn from(s: &'a str) -> Self {
-                <$t>::from_str(s)
-            }
-        }
-        impl<'a> const From<&'a [u8]> for &'a $t {
-            fn from(s: &'a [u8]) -> Self {
-                <$t>::from_bytes(s)
-            }
-        }
         #[cfg(feature = "alloc")]
         impl ToOwned for $t {
             type Owned = Box<$t>;
@@ -72,12 +87,6 @@ macro_rules! impl_string {
             }
         }
         #[cfg(feature = "alloc")]
-        impl From<Box<[u8]>> for Box<$t> {
-            fn from(s: Box<[u8]>) -> Self {
-                unsafe { mem::transmute(s) }
-            }
-        }
-        #[cfg(feature = "alloc")]
         impl Clone for Box<$t> {
             fn clone(&self) -> Self {
                 let slice: &$t = &*self;
@@ -94,12 +103,518 @@ macro_rules! impl_string {
             fn as_ref(&self) -> &[u8] {
                 self.as_bytes()
             }
+        }
+        impl<'a> TryFrom<&'a $t> for &'a str {
+            type Error = core::str::Utf8Error;
+
+            fn try_from(s: &'a $t) -> Result<Self, core::str::Utf8Error> {
+                str::from_utf8(s.as_bytes())
+            }
+        }
+        impl PartialEq<str> for $t {
+            fn eq(&self, other: &str) -> bool {
+                self.as_bytes() == other.as_bytes()
+            }
+        }
+        /// An `Option` of a string cannot be compared with an `Option<&str>`, as in
+        /// `fields.member == Some("Ping")`: the orphan rules forbid implementing that for
+        /// `Option`. Compare the contents, as in `fields.member.is_some_and(|x| x == "Ping")`,
+        /// or a string on both sides, as in
+        /// `fields.member == Some(MemberName::from_static("Ping"))`.
+        impl PartialEq<&str> for $t {
+            fn eq(&self, other: &&str) -> bool {
+                self.as_bytes() == other.as_bytes()
+            }
+        }
+        impl PartialEq<$t> for str {
+            fn eq(&self, other: &$t) -> bool {
+                self.as_bytes() == other.as_bytes()
+            }
         })*
     };
 }
 
 impl_string!(Signature, String, ObjectPath);
 
+const fn is_signature(bytes: &[u8]) -> bool {
+    if bytes.len() > 255 {
+        return false;
+    }
+    let mut i = 0;
+    while i < bytes.len() {
+        match crate::signature::strict_type_len(bytes.split_at(i).1) {
+            Some(n) => i += n,
+            None => return false,
+        }
+    }
+    true
+}
+
+impl Signature {
+    /// Returns the signature if `bytes` is a valid one: at most 255 bytes of complete types,
+    /// nested at most 32 arrays and 32 structs deep, with dict entries only as array elements
+    /// and holding a basic key and one value. Unlike [`Signature::from_bytes`], which trusts its
+    /// input, this lets a server reject a malformed signature before touching the body.
+    pub const fn validate(bytes: &[u8]) -> Result<&Self, Error> {
+        if !is_signature(bytes) {
+            Err(Error::InvalidArgs)?
+        }
+        Ok(Self::from_bytes(bytes))
+    }
+}
+
+/// Why bytes are not a valid object path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ThisError)]
+pub enum ObjectPathError {
+    #[error("object path does not start with '/'")]
+    NoLeadingSlash,
+    #[error("empty object path element at byte {0}")]
+    EmptyElement(usize),
+    #[error("byte {byte:#04x} at {at} is not allowed in an object path")]
+    InvalidByte { byte: u8, at: usize },
+}
+
+/// Checks one path element, whose first byte is at `at` in the whole path.
+const fn check_path_element(element: &[u8], at: usize) -> Result<(), ObjectPathError> {
+    if element.is_empty() {
+        Err(ObjectPathError::EmptyElement(at))?
+    }
+    let mut i = 0;
+    while i < element.len() {
+        let byte = element[i];
+        if !byte.is_ascii_alphanumeric() && byte != b'_' {
+            Err(ObjectPathError::InvalidByte { byte, at: at + i })?
+        }
+        i += 1;
+    }
+    Ok(())
+}
+
+impl ObjectPath {
+    /// Returns the path if `bytes` is `/` or `/`-separated non-empty elements of ASCII letters,
+    /// digits and `_`, each preceded by a `/`. [`ObjectPath::from_bytes`] trusts its input.
+    pub const fn validate(bytes: &[u8]) -> Result<&Self, ObjectPathError> {
+        let [b'/', rest @ ..] = bytes else {
+            return Err(ObjectPathError::NoLeadingSlash);
+        };
+        if rest.is_empty() {
+            return Ok(Self::from_bytes(bytes));
+        }
+        let mut start = 1;
+        let mut i = 1;
+        while i <= bytes.len() {
+            if i == bytes.len() || bytes[i] == b'/' {
+                check_path_element(bytes.split_at(i).0.split_at(start).1, start)?;
+                start = i + 1;
+            }
+            i += 1;
+        }
+        Ok(Self::from_bytes(bytes))
+    }
+    /// The path one element up, or `None` for the root.
+    pub fn parent(&self) -> Option<&ObjectPath> {
+        match self.as_bytes().iter().rposition(|&x| x == b'/')? {
+            _ if self.as_bytes() == b"/" => None,
+            0 => Some(ObjectPath::from_bytes(b"/")),
+            i => Some(ObjectPath::from_bytes(&self.as_bytes()[..i])),
+        }
+    }
+    /// Whether `self` is `prefix` or lies below it, comparing whole path elements.
+    pub fn starts_with_path(&self, prefix: &ObjectPath) -> bool {
+        match self.as_bytes().strip_prefix(prefix.as_bytes()) {
+            Some(rest) => prefix.ends_with(b"/") || rest.is_empty() || rest[0] == b'/',
+            None => false,
+        }
+    }
+}
+
+/// An object path built up one element at a time, always valid.
+#[cfg(feature = "alloc")]
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ObjectPathBuf(Vec<u8>);
+
+#[cfg(feature = "alloc")]
+impl ObjectPathBuf {
+    /// The root path, `/`.
+    pub fn new() -> Self {
+        Self(alloc::vec![b'/'])
+    }
+    /// Appends `element`, which must be non-empty ASCII letters, digits and `_`.
+    pub fn push_element(&mut self, element: &str) -> Result<&mut Self, ObjectPathError> {
+        let at = if self.0 == b"/" { 1 } else { self.0.len() + 1 };
+        check_path_element(element.as_bytes(), at)?;
+        if at != 1 {
+            self.0.push(b'/');
+        }
+        self.0.extend_from_slice(element.as_bytes());
+        Ok(self)
+    }
+    /// Removes the last element, returning whether there was one.
+    pub fn pop(&mut self) -> bool {
+        let Some(parent) = self.parent() else {
+            return false;
+        };
+        let len = parent.len();
+        self.0.truncate(len);
+        true
+    }
+    pub fn as_path(&self) -> &ObjectPath {
+        ObjectPath::from_bytes(&self.0)
+    }
+    pub fn into_boxed_path(self) -> Box<ObjectPath> {
+        self.0.into_boxed_slice().into()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Default for ObjectPathBuf {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Deref for ObjectPathBuf {
+    type Target = ObjectPath;
+
+    fn deref(&self) -> &ObjectPath {
+        self.as_path()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Debug for ObjectPathBuf {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Debug::fmt(self.as_path(), f)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Display for ObjectPathBuf {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Display::fmt(self.as_path(), f)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl TryFrom<&ObjectPath> for ObjectPathBuf {
+    type Error = ObjectPathError;
+
+    fn try_from(path: &ObjectPath) -> Result<Self, ObjectPathError> {
+        ObjectPath::validate(path).map(|x| Self(x.to_vec()))
+    }
+}
+
+/// A bus name, either unique (`:1.42`) or well-known (`org.freedesktop.DBus`).
+#[repr(transparent)]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BusName([u8]);
+
+/// A unique connection name assigned by the bus, such as `:1.42`.
+#[repr(transparent)]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct UniqueName([u8]);
+
+/// A well-known bus name requested by a connection, such as `org.freedesktop.DBus`.
+#[repr(transparent)]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct WellKnownName([u8]);
+
+/// An interface name, such as `org.freedesktop.DBus.Properties`.
+#[repr(transparent)]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct InterfaceName([u8]);
+
+/// A method or signal name, such as `GetAll`.
+#[repr(transparent)]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct MemberName([u8]);
+
+/// An error name, such as `org.freedesktop.DBus.Error.Failed`.
+#[repr(transparent)]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ErrorName([u8]);
+
+/// Checks the `.`-separated element rules shared by bus, interface and error names.
+const fn is_valid_dotted(bytes: &[u8], allow_hyphen: bool, allow_leading_digit: bool) -> bool {
+    if bytes.is_empty() || bytes.len() > 255 {
+        return false;
+    }
+    let mut elements = 1;
+    let mut element_len = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        let c = bytes[i];
+        if c == b'.' {
+            if element_len == 0 {
+                return false;
+            }
+            elements += 1;
+            element_len = 0;
+        } else if c.is_ascii_digit() {
+            if element_len == 0 && !allow_leading_digit {
+                return false;
+            }
+            element_len += 1;
+        } else if c.is_ascii_alphabetic() || c == b'_' || (allow_hyphen && c == b'-') {
+            element_len += 1;
+        } else {
+            return false;
+        }
+        i += 1;
+    }
+    element_len != 0 && elements >= 2
+}
+
+const fn is_unique_name(bytes: &[u8]) -> bool {
+    match bytes {
+        [b':', rest @ ..] => bytes.len() <= 255 && is_valid_dotted(rest, true, true),
+        _ => false,
+    }
+}
+
+const fn is_well_known_name(bytes: &[u8]) -> bool {
+    is_valid_dotted(bytes, true, false)
+}
+
+const fn is_bus_name(bytes: &[u8]) -> bool {
+    is_unique_name(bytes) || is_well_known_name(bytes)
+}
+
+const fn is_interface_name(bytes: &[u8]) -> bool {
+    is_valid_dotted(bytes, false, false)
+}
+
+const fn is_member_name(bytes: &[u8]) -> bool {
+    if bytes.is_empty() || bytes.len() > 255 || bytes[0].is_ascii_digit() {
+        return false;
+    }
+    let mut i = 0;
+    while i < bytes.len() {
+        if !bytes[i].is_ascii_alphanumeric() && bytes[i] != b'_' {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+macro_rules! impl_name {
+    ($($t:ty = $validate:ident),* $(,)?) => {
+        $(impl $t {
+            /// Returns the name if `bytes` satisfies the D-Bus naming rules for this kind of name.
+            pub const fn validate(bytes: &[u8]) -> Result<&Self, Error> {
+                if !$validate(bytes) {
+                    Err(Error::InvalidArgs)?
+                }
+                Ok(unsafe { Self::from_bytes_unchecked(bytes) })
+            }
+            /// Like [`Self::validate`], but panics on an invalid name; meant for literals in
+            /// const context, where the panic becomes a compile error. Names only known at run
+            /// time go through [`Self::validate`] or `TryFrom` instead.
+            pub const fn from_static(s: &'static str) -> &'static Self {
+                match Self::validate(s.as_bytes()) {
+                    Ok(x) => x,
+                    Err(_) => panic!(concat!("invalid ", stringify!($t))),
+                }
+            }
+            /// # Safety
+            /// `bytes` must be a valid name of this kind.
+            pub const unsafe fn from_bytes_unchecked(bytes: &[u8]) -> &Self {
+                unsafe { mem::transmute::<&[u8], &Self>(bytes) }
+            }
+            pub const fn as_string(&self) -> &String {
+                String::from_bytes(self.as_bytes())
+            }
+        }
+        impl<'a> TryFrom<&'a str> for &'a $t {
+            type Error = Error;
+
+            fn try_from(s: &'a str) -> Result<Self, Error> {
+                <$t>::validate(s.as_bytes())
+            }
+        }
+        impl<'a> TryFrom<&'a String> for &'a $t {
+            type Error = Error;
+
+            fn try_from(s: &'a String) -> Result<Self, Error> {
+                <$t>::validate(s.as_bytes())
+            }
+        })*
+        impl_string!(@common $($t),*);
+    };
+}
+
+impl_name!(
+    BusName = is_bus_name,
+    UniqueName = is_unique_name,
+    WellKnownName = is_well_known_name,
+    InterfaceName = is_interface_name,
+    MemberName = is_member_name,
+    ErrorName = is_interface_name,
+);
+
+impl BusName {
+    pub const fn as_unique(&self) -> Option<&UniqueName> {
+        match self.as_bytes() {
+            [b':', ..] => Some(unsafe { UniqueName::from_bytes_unchecked(self.as_bytes()) }),
+            _ => None,
+        }
+    }
+    pub const fn as_well_known(&self) -> Option<&WellKnownName> {
+        match self.as_bytes() {
+            [b':', ..] => None,
+            bytes => Some(unsafe { WellKnownName::from_bytes_unchecked(bytes) }),
+        }
+    }
+}
+
+impl<'a> const From<&'a UniqueName> for &'a BusName {
+    fn from(s: &'a UniqueName) -> Self {
+        unsafe { BusName::from_bytes_unchecked(s.as_bytes()) }
+    }
+}
+
+impl<'a> const From<&'a WellKnownName> for &'a BusName {
+    fn from(s: &'a WellKnownName) -> Self {
+        unsafe { BusName::from_bytes_unchecked(s.as_bytes()) }
+    }
+}
+
+#[test]
+fn test_object_path_prefix() {
+    let path = ObjectPath::from_str("/org/foo/bar");
+    assert!(path.starts_with_path(ObjectPath::from_str("/")));
+    assert!(path.starts_with_path(ObjectPath::from_str("/org/foo")));
+    assert!(path.starts_with_path(path));
+    assert!(!path.starts_with_path(ObjectPath::from_str("/org/fo")));
+}
+
+#[test]
+fn test_bus_name() {
+    assert!(UniqueName::validate(b":1.42").is_ok());
+    assert!(UniqueName::validate(b"org.freedesktop.DBus").is_err());
+    assert!(WellKnownName::validate(b"org.freedesktop.DBus").is_ok());
+    assert!(WellKnownName::validate(b"org.1freedesktop").is_err());
+    assert!(BusName::validate(b"org").is_err());
+    assert!(BusName::validate(b"org..DBus").is_err());
+    assert!(BusName::from_static(":1.42").as_unique().is_some());
+    assert!(
+        BusName::from_static("org.a-b.c_d")
+            .as_well_known()
+            .is_some()
+    );
+}
+
+#[test]
+fn test_member_name() {
+    assert!(InterfaceName::validate(b"org.freedesktop.DBus").is_ok());
+    assert!(InterfaceName::validate(b"org.free-desktop").is_err());
+    assert!(MemberName::validate(b"GetAll").is_ok());
+    assert!(MemberName::validate(b"Get.All").is_err());
+    assert!(MemberName::validate(b"1Get").is_err());
+    assert!(ErrorName::validate(b"org.freedesktop.DBus.Error.Failed").is_ok());
+}
+
+#[test]
+fn test_signature_validate() {
+    for valid in ["", "y", "a{sv}as(ia(yv))", "aa{ya{s(t)}}", "(((((y)))))"] {
+        assert!(Signature::validate(valid.as_bytes()).is_ok(), "{valid}");
+    }
+    for invalid in [
+        "z", "a", "()", "(y", "y)", "{sv}", "a({sv})", "a{vs}", "a{s}", "a{syy}", "a{s(y)",
+    ] {
+        assert_eq!(
+            Signature::validate(invalid.as_bytes()),
+            Err(Error::InvalidArgs),
+            "{invalid}"
+        );
+    }
+    let arrays = |n| [&[b'a'; 33][..n], b"y"].concat();
+    assert!(Signature::validate(&arrays(32)).is_ok());
+    assert!(Signature::validate(&arrays(33)).is_err());
+    let deep = [[b'('; 33], [b'y'; 33], [b')'; 33]].concat();
+    assert!(Signature::validate(&deep[1..deep.len() - 1]).is_ok());
+    assert!(Signature::validate(&deep).is_err());
+    assert!(Signature::validate(&[b'y'; 255]).is_ok());
+    assert!(Signature::validate(&[b'y'; 256]).is_err());
+    const _: &Signature = match Signature::validate(b"a{sv}") {
+        Ok(x) => x,
+        Err(_) => panic!(),
+    };
+}
+
+#[test]
+fn test_object_path_validate() {
+    for valid in ["/", "/a", "/org/freedesktop/DBus", "/_1/x_Y"] {
+        assert!(ObjectPath::validate(valid.as_bytes()).is_ok(), "{valid}");
+    }
+    let invalid = |x: &str| ObjectPath::validate(x.as_bytes()).err();
+    assert_eq!(invalid(""), Some(ObjectPathError::NoLeadingSlash));
+    assert_eq!(invalid("a/b"), Some(ObjectPathError::NoLeadingSlash));
+    assert_eq!(invalid("/a/"), Some(ObjectPathError::EmptyElement(3)));
+    assert_eq!(invalid("//a"), Some(ObjectPathError::EmptyElement(1)));
+    assert_eq!(
+        invalid("/a/b-c"),
+        Some(ObjectPathError::InvalidByte { byte: b'-', at: 4 })
+    );
+
+    assert_eq!(ObjectPath::from_str("/").parent(), None);
+    assert_eq!(
+        ObjectPath::from_str("/a").parent(),
+        Some(ObjectPath::from_str("/"))
+    );
+    assert_eq!(
+        ObjectPath::from_str("/a/b").parent(),
+        Some(ObjectPath::from_str("/a"))
+    );
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_object_path_buf() {
+    let mut path = ObjectPathBuf::new();
+    assert_eq!(path.as_path(), ObjectPath::from_str("/"));
+    path.push_element("org")
+        .unwrap()
+        .push_element("a_1")
+        .unwrap();
+    assert_eq!(*path, *ObjectPath::from_str("/org/a_1"));
+    assert_eq!(
+        path.push_element("b.c").err(),
+        Some(ObjectPathError::InvalidByte { byte: b'.', at: 10 })
+    );
+    assert_eq!(
+        path.push_element("").err(),
+        Some(ObjectPathError::EmptyElement(9))
+    );
+    assert_eq!(path.parent(), Some(ObjectPath::from_str("/org")));
+    assert!(path.pop() && path.pop());
+    assert_eq!(path, ObjectPathBuf::new());
+    assert!(!path.pop());
+    assert_eq!(
+        ObjectPathBuf::try_from(ObjectPath::from_str("/x/")).err(),
+        Some(ObjectPathError::EmptyElement(3))
+    );
+    assert_eq!(
+        &*path.push_element("x").unwrap().clone().into_boxed_path(),
+        ObjectPath::from_str("/x")
+    );
+}
+
+#[test]
+fn test_str_conversions() {
+    let member = Some(MemberName::from_static("Ping"));
+    // `member == Some("Ping")` does not compile; see `PartialEq<&str>`.
+    assert!(member.is_some_and(|x| x == "Ping"));
+    assert_eq!(member, Some(MemberName::from_static("Ping")));
+    assert_ne!(member, Some(MemberName::from_static("Pong")));
+    assert_eq!(*ObjectPath::from_str("/a"), "/a");
+    assert_eq!(*"ii", *Signature::from_str("ii"));
+    assert_eq!(<&str>::try_from(String::from_str("é")), Ok("é"));
+    assert!(<&str>::try_from(String::from_bytes(b"\xff")).is_err());
+}
+
 #[test]
 #[cfg(feature = "alloc")]
 fn string_clone() {
@@ -1,6 +1,8 @@
 #![macro_use]
 
-use crate::signature::{self, MultiSignature, Signature};
+use core::{fmt, marker::PhantomData};
+
+use crate::signature::{self, MultiSignature, Signature, SignatureProxy};
 
 #[derive(Clone, Copy)]
 pub struct Variant<T: ?Sized>(pub T);
@@ -24,6 +26,164 @@ unsafe impl<K: Signature, V: Signature> Signature for Entry<K, V> {
     const ALIGNMENT: usize = 8;
 }
 
+/// A unix fd argument (`h`): the index of the fd in those attached to the message.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct UnixFd(pub u32);
+
+unsafe impl MultiSignature for UnixFd {
+    type Data = u8;
+    const DATA: Self::Data = b'h';
+}
+unsafe impl Signature for UnixFd {
+    const ALIGNMENT: usize = 4;
+}
+
+/// An `f32` carried on the wire as a `d` (double).
+///
+/// Marshalling widens losslessly; unmarshalling rounds to the nearest `f32`, overflowing to
+/// infinity for doubles outside the `f32` range.
+#[derive(Debug, Default, Clone, Copy, PartialEq, PartialOrd)]
+pub struct F32AsDouble(pub f32);
+
+unsafe impl MultiSignature for F32AsDouble {
+    type Data = u8;
+    const DATA: Self::Data = b'd';
+}
+unsafe impl Signature for F32AsDouble {
+    const ALIGNMENT: usize = 8;
+}
+
+/// An integer `T` representing `T / SCALE`, carried on the wire as a `d` (double).
+///
+/// Unmarshalling rounds half away from zero and fails with
+/// [`Error::InvalidArgs`](crate::unmarshal::Error::InvalidArgs) if the scaled value is not
+/// finite or does not fit in `T`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FixedPoint<T, const SCALE: u32>(pub T);
+
+unsafe impl<T, const SCALE: u32> MultiSignature for FixedPoint<T, SCALE> {
+    type Data = u8;
+    const DATA: Self::Data = b'd';
+}
+unsafe impl<T, const SCALE: u32> Signature for FixedPoint<T, SCALE> {
+    const ALIGNMENT: usize = 8;
+}
+
+/// Conversions between a domain type `T` and the type it is carried as on the wire, plugged in
+/// with [`With`].
+pub trait Codec<T> {
+    type Wire: Signature;
+    fn encode(value: &T) -> Self::Wire;
+    /// Fails with an [`Error`](crate::unmarshal::Error), typically
+    /// [`InvalidArgs`](crate::unmarshal::Error::InvalidArgs), if `wire` has no `T`.
+    fn decode(wire: Self::Wire) -> Result<T, crate::unmarshal::Error>;
+}
+
+/// A `T` carried on the wire as a `C::Wire`, converted by the [`Codec`] `C`, so that argument
+/// structs can hold domain types without a newtype per field.
+pub struct With<T, C>(pub T, pub PhantomData<fn() -> C>);
+
+impl<T, C> With<T, C> {
+    pub const fn new(value: T) -> Self {
+        Self(value, PhantomData)
+    }
+}
+
+impl<T: Clone, C> Clone for With<T, C> {
+    fn clone(&self) -> Self {
+        Self::new(self.0.clone())
+    }
+}
+
+impl<T: Copy, C> Copy for With<T, C> {}
+
+impl<T: fmt::Debug, C> fmt::Debug for With<T, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("With").field(&self.0).finish()
+    }
+}
+
+impl<T: PartialEq, C> PartialEq for With<T, C> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl<T, C: Codec<T>> SignatureProxy for With<T, C> {
+    type Proxy = C::Wire;
+}
+
+/// A [`Uuid`](uuid::Uuid) carried on the wire as its 16 bytes (`ay`).
+#[cfg(feature = "uuid")]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct UuidBytes(pub uuid::Uuid);
+
+#[cfg(feature = "uuid")]
+impl signature::SignatureProxy for UuidBytes {
+    type Proxy = [u8];
+}
+
+/// A [`Uuid`](uuid::Uuid) carried on the wire as its hyphenated form (`s`).
+///
+/// Marshalling writes lowercase; unmarshalling accepts any form [`uuid::Uuid::try_parse`] does.
+#[cfg(feature = "uuid")]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct UuidString(pub uuid::Uuid);
+
+#[cfg(feature = "uuid")]
+impl signature::SignatureProxy for UuidString {
+    type Proxy = str;
+}
+
+/// Address family tag of an IPv4 address in [`IpAddrBytes`] and [`SocketAddrBytes`] (`AF_INET`).
+#[cfg(feature = "net")]
+pub const AF_INET: u8 = 2;
+/// Address family tag of an IPv6 address in [`IpAddrBytes`] and [`SocketAddrBytes`] (`AF_INET6`).
+#[cfg(feature = "net")]
+pub const AF_INET6: u8 = 10;
+
+/// An [`IpAddr`](core::net::IpAddr) carried on the wire in its textual form (`s`).
+#[cfg(feature = "net")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct IpAddrString(pub core::net::IpAddr);
+
+#[cfg(feature = "net")]
+impl signature::SignatureProxy for IpAddrString {
+    type Proxy = str;
+}
+
+/// An [`IpAddr`](core::net::IpAddr) carried on the wire as its family ([`AF_INET`] or
+/// [`AF_INET6`]) and octets (`(yay)`).
+#[cfg(feature = "net")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct IpAddrBytes(pub core::net::IpAddr);
+
+#[cfg(feature = "net")]
+impl signature::SignatureProxy for IpAddrBytes {
+    type Proxy = crate::struct_type!(u8, &'static [u8]);
+}
+
+/// A [`SocketAddr`](core::net::SocketAddr) carried on the wire in its textual form (`s`).
+#[cfg(feature = "net")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SocketAddrString(pub core::net::SocketAddr);
+
+#[cfg(feature = "net")]
+impl signature::SignatureProxy for SocketAddrString {
+    type Proxy = str;
+}
+
+/// A [`SocketAddr`](core::net::SocketAddr) carried on the wire as its family, octets and port
+/// (`(yayq)`), like [`IpAddrBytes`] followed by the port.
+#[cfg(feature = "net")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SocketAddrBytes(pub core::net::SocketAddr);
+
+#[cfg(feature = "net")]
+impl signature::SignatureProxy for SocketAddrBytes {
+    type Proxy = crate::struct_type!(u8, &'static [u8], u16);
+}
+
 mod private {
     pub trait StructConstructor {}
 }
@@ -55,6 +215,64 @@ unsafe impl<T: MultiSignature + StructConstructor> Signature for Struct<T> {
     const ALIGNMENT: usize = 8;
 }
 
+/// The value a field takes when its key is missing, e.g. in a
+/// [`define_dict!`](crate::define_dict) struct: zero, `false`, the empty string, signature or
+/// array, or the root object path.
+pub trait DbusDefault {
+    fn dbus_default() -> Self;
+}
+
+macro_rules! impl_dbus_default {
+    ($($t:ty = $value:expr),* $(,)?) => {
+        $(impl DbusDefault for $t {
+            fn dbus_default() -> Self {
+                $value
+            }
+        })*
+    };
+}
+
+impl_dbus_default!(
+    u8 = 0,
+    bool = false,
+    i16 = 0,
+    u16 = 0,
+    i32 = 0,
+    u32 = 0,
+    i64 = 0,
+    u64 = 0,
+    f64 = 0.0,
+    &str = "",
+    &crate::String = crate::String::from_str(""),
+    &crate::ObjectPath = crate::ObjectPath::from_str("/"),
+    &crate::Signature = crate::Signature::from_str(""),
+    Empty = Empty,
+);
+
+impl<T> DbusDefault for &[T] {
+    fn dbus_default() -> Self {
+        &[]
+    }
+}
+
+impl<T: DbusDefault> DbusDefault for Variant<T> {
+    fn dbus_default() -> Self {
+        Variant(T::dbus_default())
+    }
+}
+
+impl<Xs: DbusDefault, X: DbusDefault> DbusDefault for Append<Xs, X> {
+    fn dbus_default() -> Self {
+        Append(Xs::dbus_default(), X::dbus_default())
+    }
+}
+
+impl<T: DbusDefault + StructConstructor> DbusDefault for Struct<T> {
+    fn dbus_default() -> Self {
+        Struct(T::dbus_default())
+    }
+}
+
 #[macro_export]
 macro_rules! multiple_type {
     ($x:ty, $($xs:ty),* $(,)?) => {
@@ -75,6 +293,94 @@ macro_rules! struct_type {
     };
 }
 
+/// A case convention for the keys of a [`define_dict!`](crate::define_dict) struct, applied to
+/// its snake_case field names when the struct is marked `#[rename_all = "..."]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenameAll {
+    /// `"lowercase"`
+    Lower,
+    /// `"UPPERCASE"`
+    Upper,
+    /// `"PascalCase"`
+    Pascal,
+    /// `"camelCase"`
+    Camel,
+    /// `"snake_case"`
+    Snake,
+    /// `"SCREAMING_SNAKE_CASE"`
+    ScreamingSnake,
+    /// `"kebab-case"`
+    Kebab,
+    /// `"SCREAMING-KEBAB-CASE"`
+    ScreamingKebab,
+}
+
+impl RenameAll {
+    /// The convention named as in serde's `rename_all`. Panics, failing compilation when
+    /// evaluated in a constant, on any other name.
+    pub const fn from_name(name: &str) -> Self {
+        match name.as_bytes() {
+            b"lowercase" => RenameAll::Lower,
+            b"UPPERCASE" => RenameAll::Upper,
+            b"PascalCase" => RenameAll::Pascal,
+            b"camelCase" => RenameAll::Camel,
+            b"snake_case" => RenameAll::Snake,
+            b"SCREAMING_SNAKE_CASE" => RenameAll::ScreamingSnake,
+            b"kebab-case" => RenameAll::Kebab,
+            b"SCREAMING-KEBAB-CASE" => RenameAll::ScreamingKebab,
+            _ => panic!("unknown rename_all convention"),
+        }
+    }
+    /// The length of `field` once renamed.
+    pub const fn renamed_len(self, field: &str) -> usize {
+        let mut len = field.len();
+        if let RenameAll::Pascal | RenameAll::Camel = self {
+            let mut i = 0;
+            while i < field.len() {
+                len -= (field.as_bytes()[i] == b'_') as usize;
+                i += 1;
+            }
+        }
+        len
+    }
+    /// Renames the snake_case `field`, whose renamed length `N` must be [`RenameAll::renamed_len`].
+    pub const fn rename<const N: usize>(self, field: &str) -> [u8; N] {
+        let field = field.as_bytes();
+        let mut out = [0; N];
+        let (mut i, mut j) = (0, 0);
+        let mut word_start = true;
+        while i < field.len() {
+            let x = field[i];
+            i += 1;
+            if x == b'_' {
+                word_start = true;
+                match self {
+                    RenameAll::Pascal | RenameAll::Camel => continue,
+                    RenameAll::Kebab | RenameAll::ScreamingKebab => out[j] = b'-',
+                    _ => out[j] = b'_',
+                }
+            } else {
+                let upper = match self {
+                    RenameAll::Upper | RenameAll::ScreamingSnake | RenameAll::ScreamingKebab => {
+                        true
+                    }
+                    RenameAll::Pascal => word_start,
+                    RenameAll::Camel => word_start && j > 0,
+                    _ => false,
+                };
+                out[j] = if upper {
+                    x.to_ascii_uppercase()
+                } else {
+                    x.to_ascii_lowercase()
+                };
+                word_start = false;
+            }
+            j += 1;
+        }
+        out
+    }
+}
+
 #[macro_export]
 macro_rules! multiple_new {
     ($x:expr, $($xs:expr),* $(,)?) => {
@@ -115,32 +421,77 @@ macro_rules! struct_match {
     };
 }
 
+/// Defines a struct of optional fields carried on the wire as an `a{sv}` dict, keyed by field
+/// name unless a field is marked `#[key = "OtherName"]`.
+///
+/// Unmarshalling skips keys that do not name a field, so a struct may declare only the
+/// properties it cares about. A field marked `#[default]` is a plain value instead of an
+/// `Option`: it takes its [`DbusDefault`] when the key is missing and is always marshalled.
+///
+/// Any other field attribute, such as a doc comment, is kept on the generated field.
+///
+/// Marking the struct `#[rename_all = "PascalCase"]`, or any other [`RenameAll`] convention,
+/// derives the keys of the remaining fields from their names. It must be the first attribute.
 #[macro_export]
 macro_rules! define_dict {
-    ($(#[$meta:meta])* $pub:vis struct $name:ident($entry:ident, $key:ident, $value:ident $(,)?) $(<$a:lifetime>)? {
-        $($field_pub:vis $field:ident: $type:ty),* $(,)?
+    (#[rename_all = $case:literal] $($rest:tt)*) => {
+        $crate::define_dict!(@define [$case] $($rest)*);
+    };
+    (@define $case:tt $(#[$meta:meta])* $pub:vis struct $name:ident($entry:ident, $key:ident, $value:ident $(,)?) $(<$a:lifetime>)? {
+        $($(#[$($attr:tt)*])* $field_pub:vis $field:ident: $type:ty),* $(,)?
     }) => {
+        $crate::define_dict!(
+            @fields [$case [$(#[$meta])*] $pub $name $entry $key $value [$($a)?]] []
+            $([$([$($attr)*])*] $field_pub $field: $type,)*
+        );
+    };
+    // Sorts the attributes of each field into `#[default]`, `#[key = ...]` and the rest, which
+    // are kept on the generated field.
+    (@fields $struct:tt $done:tt [$($attr:tt)*] $field_pub:vis $field:ident: $type:ty, $($rest:tt)*) => {
+        $crate::define_dict!(@attrs $struct $done [] [] [] [$($attr)*] $field_pub $field: $type, $($rest)*);
+    };
+    (@attrs $struct:tt $done:tt [$($flag:ident)*] $rename:tt $kept:tt [[default] $($attr:tt)*] $($rest:tt)*) => {
+        $crate::define_dict!(@attrs $struct $done [$($flag)* default] $rename $kept [$($attr)*] $($rest)*);
+    };
+    (@attrs $struct:tt $done:tt $flags:tt [] $kept:tt [[key = $rename:literal] $($attr:tt)*] $($rest:tt)*) => {
+        $crate::define_dict!(@attrs $struct $done $flags [$rename] $kept [$($attr)*] $($rest)*);
+    };
+    (@attrs $struct:tt $done:tt $flags:tt $rename:tt [$($kept:tt)*] [[$($other:tt)*] $($attr:tt)*] $($rest:tt)*) => {
+        $crate::define_dict!(@attrs $struct $done $flags $rename [$($kept)* [$($other)*]] [$($attr)*] $($rest)*);
+    };
+    (@attrs $struct:tt [$($done:tt)*] $flags:tt $rename:tt $kept:tt [] $field_pub:vis $field:ident: $type:ty, $($rest:tt)*) => {
+        $crate::define_dict!(@fields $struct [$($done)* {$flags $rename $kept $field_pub $field: $type}] $($rest)*);
+    };
+    (@fields [$case:tt [$(#[$meta:meta])*] $pub:vis $name:ident $entry:ident $key:ident $value:ident [$($a:lifetime)?]] [$({
+        [$($attr:ident)*] [$($rename:literal)?] [$([$($kept:tt)*])*] $field_pub:vis $field:ident: $type:ty
+    })*]) => {
         $(#[$meta])*
         $pub struct $name<$($a)?> {
-            $($field_pub $field: Option<$type>,)*
+            $($(#[$($kept)*])* $field_pub $field: $crate::define_dict!(@type [$type] $($attr)*),)*
         }
         impl<$($a)?> $crate::signature::SignatureProxy for $name<$($a)?> {
             type Proxy = [$crate::Entry<&'static str, $crate::Variant<()>>];
         }
         impl<$($a)?> $crate::marshal::Marshal for $name<$($a)?> where Self: Clone {
             fn marshal<W: $crate::marshal::Write + ?Sized>(self, w: &mut W) {
-                let insert_pos = w.skip_aligned(4);
+                let insert_pos = w.skip_aligned::<u32>();
+                w.align_to(8);
                 let begin = w.position();
-                $(if let Some(value) = self.$field {
+                $(if let Some(value) = $crate::define_dict!(@get self.$field; $($attr)*) {
                     w.align_to(8);
-                    w.write(stringify!($field));
-                    w.write(Variant(value));
+                    w.write($crate::define_dict!(@key $case $field $($rename)?));
+                    w.write($crate::Variant(value));
                 })*
                 let len = w.position() - begin;
-                w.insert(len as u32, insert_pos);
+                w.insert_len(len, $crate::marshal::MAX_ARRAY_LEN, insert_pos);
+            }
+        }
+        impl<$($a)?> $crate::DbusDefault for $name<$($a)?> {
+            fn dbus_default() -> Self {
+                Self { $($field: $crate::define_dict!(@missing $($attr)*)),* }
             }
         }
-        crate::define_dict!(@unmarshal $name $entry $key $value $($a)? $($field $type)*);
+        $crate::define_dict!(@unmarshal $case $name $entry $key $value $($a)? $([$($attr)*] [$($rename)?] $field $type)*);
         #[allow(non_camel_case_types)]
         enum $key {
             $($field),*
@@ -149,61 +500,161 @@ macro_rules! define_dict {
             $($field: $type,)*
         }
     };
-    (@unmarshal $name:ident $entry:ident $key:ident $value:ident $lifetime:lifetime $($field:ident $type:ty)*) => {
+    (@key [$($case:literal)?] $field:ident $rename:literal) => {
+        $rename
+    };
+    (@key [$case:literal] $field:ident) => {{
+        const NAME: &str = stringify!($field);
+        const RENAME: $crate::RenameAll = $crate::RenameAll::from_name($case);
+        const BYTES: [u8; RENAME.renamed_len(NAME)] = RENAME.rename(NAME);
+        const KEY: &str = match str::from_utf8(&BYTES) {
+            Ok(key) => key,
+            Err(_) => unreachable!(),
+        };
+        KEY
+    }};
+    (@key [] $field:ident) => {
+        stringify!($field)
+    };
+    (@type [$type:ty] default $($attr:ident)*) => {
+        $type
+    };
+    (@type [$type:ty] $other:ident $($attr:ident)*) => {
+        $crate::define_dict!(@type [$type] $($attr)*)
+    };
+    (@type [$type:ty]) => {
+        Option<$type>
+    };
+    (@get $value:expr; default $($attr:ident)*) => {
+        Some($value)
+    };
+    (@get $value:expr; $other:ident $($attr:ident)*) => {
+        $crate::define_dict!(@get $value; $($attr)*)
+    };
+    (@get $value:expr;) => {
+        $value
+    };
+    (@missing default $($attr:ident)*) => {
+        $crate::DbusDefault::dbus_default()
+    };
+    (@missing $other:ident $($attr:ident)*) => {
+        $crate::define_dict!(@missing $($attr)*)
+    };
+    (@missing) => {
+        None
+    };
+    (@set $value:expr; default $($attr:ident)*) => {
+        $value
+    };
+    (@set $value:expr; $other:ident $($attr:ident)*) => {
+        $crate::define_dict!(@set $value; $($attr)*)
+    };
+    (@set $value:expr;) => {
+        Some($value)
+    };
+    (@unmarshal $case:tt $name:ident $entry:ident $key:ident $value:ident $lifetime:lifetime $([$($attr:ident)*] [$($rename:literal)?] $field:ident $type:ty)*) => {
         impl<'a> $crate::unmarshal::Unmarshal<'a> for $name<'a> {
             fn unmarshal(r: &mut $crate::unmarshal::Reader<'a>) -> $crate::unmarshal::Result<Self> {
-                let mut res = Self { $($field: None),* };
+                let mut res = Self { $($field: $crate::define_dict!(@missing $($attr)*)),* };
                 let it: $crate::unmarshal::ArrayIter<'a, $entry> = r.read()?;
                 for entry in it {
-                    let Entry(key, val) = entry?;
-                    match key {
-                        $(Key::$field => res.$field = Some(unsafe { val.$field }),)*
+                    match entry?.0 {
+                        $(Some(($key::$field, val)) => {
+                            res.$field = $crate::define_dict!(@set unsafe { val.$field }; $($attr)*)
+                        })*
+                        None => {}
                     }
                 }
                 Ok(res)
             }
         }
-        struct $entry<'a>($key, $value<'a>);
+        struct $entry<'a>(Option<($key, $value<'a>)>);
         impl $crate::signature::SignatureProxy for $entry<'_> {
             type Proxy = $crate::Entry<&'static str, $crate::Variant<()>>;
         }
         impl<$lifetime> $crate::unmarshal::Unmarshal<$lifetime> for $entry<$lifetime> {
             fn unmarshal(r: &mut $crate::unmarshal::Reader<$lifetime>) -> $crate::unmarshal::Result<Self> {
                 let key: &$crate::String = r.read()?;
-                match unsafe { str::from_utf8_unchecked(key) } {
-                    $(stringify!($field) => {
-                        let val: Variant<$type> = r.read()?;
-                        Ok(Self($key::$field, $value {
-                            $field: val.0
-                        }))
-                    })*
-                    _ => Err($crate::unmarshal::Error::InvalidArgs)?
-                }
+                let key = unsafe { str::from_utf8_unchecked(key) };
+                $(if key == $crate::define_dict!(@key $case $field $($rename)?) {
+                    let val: $crate::Variant<$type> = r.read()?;
+                    return Ok(Self(Some(($key::$field, $value {
+                        $field: val.0
+                    }))));
+                })*
+                r.skip(b"v")?;
+                Ok(Self(None))
             }
         }
     };
-    (@unmarshal $name:ident $entry:ident $key:ident $value:ident $($field:ident $type:ty)*) => {
-        impl $crate::unmarshal::Unmarshal<'_> for $name {
-            fn unmarshal(_r: &mut $crate::unmarshal::Reader) -> $crate::unmarshal::Result<Self> {
-                todo!();
+    (@unmarshal $case:tt $name:ident $entry:ident $key:ident $value:ident $([$($attr:ident)*] [$($rename:literal)?] $field:ident $type:ty)*) => {
+        impl<'a> $crate::unmarshal::Unmarshal<'a> for $name {
+            fn unmarshal(r: &mut $crate::unmarshal::Reader<'a>) -> $crate::unmarshal::Result<Self> {
+                let mut res = Self { $($field: $crate::define_dict!(@missing $($attr)*)),* };
+                let it: $crate::unmarshal::ArrayIter<'a, $entry> = r.read()?;
+                for entry in it {
+                    match entry?.0 {
+                        $(Some(($key::$field, val)) => {
+                            res.$field = $crate::define_dict!(@set unsafe { val.$field }; $($attr)*)
+                        })*
+                        None => {}
+                    }
+                }
+                Ok(res)
             }
         }
-        struct $entry($key, $value);
+        struct $entry(Option<($key, $value)>);
         impl $crate::signature::SignatureProxy for $entry {
             type Proxy = $crate::Entry<&'static str, $crate::Variant<()>>;
         }
         impl $crate::unmarshal::Unmarshal<'_> for $entry {
             fn unmarshal(r: &mut $crate::unmarshal::Reader<'_>) -> $crate::unmarshal::Result<Self> {
                 let key: &$crate::String = r.read()?;
-                match unsafe { str::from_utf8_unchecked(key) } {
-                    $(stringify!($field) => {
-                        let val: Variant<$type> = r.read()?;
-                        Ok(Self($key::$field, $value {
-                            $field: val.0
-                        }))
-                    })*
-                    _ => Err($crate::unmarshal::Error::InvalidArgs)?
-                }
+                let key = unsafe { str::from_utf8_unchecked(key) };
+                $(if key == $crate::define_dict!(@key $case $field $($rename)?) {
+                    let val: $crate::Variant<$type> = r.read()?;
+                    return Ok(Self(Some(($key::$field, $value {
+                        $field: val.0
+                    }))));
+                })*
+                r.skip(b"v")?;
+                Ok(Self(None))
+            }
+        }
+    };
+    ($($rest:tt)*) => {
+        $crate::define_dict!(@define [] $($rest)*);
+    };
+}
+
+/// Defines a single-field tuple struct that has the signature and wire format of its field.
+#[macro_export]
+macro_rules! define_transparent {
+    ($(#[$meta:meta])* $pub:vis struct $name:ident $(<$a:lifetime>)? ($field_pub:vis $type:ty $(,)?);) => {
+        $(#[$meta])*
+        #[repr(transparent)]
+        $pub struct $name<$($a)?>($field_pub $type);
+        impl<$($a)?> $crate::signature::SignatureProxy for $name<$($a)?> {
+            type Proxy = $type;
+        }
+        impl<$($a)?> $crate::marshal::Marshal for $name<$($a)?> where Self: Clone {
+            fn marshal<W: $crate::marshal::Write + ?Sized>(self, w: &mut W) {
+                w.write(self.0)
+            }
+        }
+        $crate::define_transparent!(@unmarshal $name $($a)?);
+    };
+    (@unmarshal $name:ident $lifetime:lifetime) => {
+        impl<$lifetime> $crate::unmarshal::Unmarshal<$lifetime> for $name<$lifetime> {
+            fn unmarshal(r: &mut $crate::unmarshal::Reader<$lifetime>) -> $crate::unmarshal::Result<Self> {
+                r.read().map(Self)
+            }
+        }
+    };
+    (@unmarshal $name:ident) => {
+        impl $crate::unmarshal::Unmarshal<'_> for $name {
+            fn unmarshal(r: &mut $crate::unmarshal::Reader<'_>) -> $crate::unmarshal::Result<Self> {
+                r.read().map(Self)
             }
         }
     };
@@ -231,3 +682,107 @@ fn test_dict() {
         }
     }
 }
+
+#[test]
+fn test_dict_default() {
+    define_dict! {
+        #[derive(Debug, Clone, Copy, PartialEq)]
+        struct Unit(UnitEntry, UnitKey, UnitValue)<'a> {
+            #[key = "Id"]
+            #[default]
+            id: &'a crate::String,
+            #[default]
+            restarts: u32,
+            active: bool,
+        }
+    }
+    let missing = crate::marshal::marshal(&[Entry("active", Variant(true))][..]);
+    let unit: Unit = crate::unmarshal::Reader::new(&missing).read().unwrap();
+    assert_eq!(
+        unit,
+        Unit {
+            id: crate::String::from_str(""),
+            restarts: 0,
+            active: Some(true),
+        }
+    );
+    assert_eq!(
+        Unit::dbus_default(),
+        Unit {
+            active: None,
+            ..unit
+        }
+    );
+
+    let id = crate::String::from_str("a.service");
+    let unit = Unit {
+        id,
+        restarts: 2,
+        active: None,
+    };
+    let data = crate::marshal::marshal(unit);
+    let keys: crate::unmarshal::ArrayIter<Entry<&crate::String, crate::unmarshal::DynVariant>> =
+        crate::unmarshal::Reader::new(&data).read().unwrap();
+    assert_eq!(keys.count(), 2);
+    assert_eq!(crate::unmarshal::Reader::new(&data).read(), Ok(unit));
+}
+
+#[test]
+fn test_dict_rename_all() {
+    define_dict! {
+        #[rename_all = "kebab-case"]
+        #[derive(Debug, Clone, Copy, PartialEq)]
+        struct Unit(UnitEntry, UnitKey, UnitValue)<'a> {
+            #[key = "Id"]
+            id: &'a crate::String,
+            /// Doc comments and other attributes stay on the field.
+            #[allow(clippy::doc_markdown)]
+            restart_sec: u32,
+            active: bool,
+        }
+    }
+    let unit = Unit {
+        id: Some(crate::String::from_str("a.service")),
+        restart_sec: Some(5),
+        active: Some(true),
+    };
+    let data = crate::marshal::marshal(unit);
+    let keys: crate::unmarshal::ArrayIter<Entry<&crate::String, crate::unmarshal::DynVariant>> =
+        crate::unmarshal::Reader::new(&data).read().unwrap();
+    let keys: Vec<_> = keys.map(|x| x.unwrap().0.as_bytes()).collect();
+    assert_eq!(keys, [&b"Id"[..], b"restart-sec", b"active"]);
+    assert_eq!(crate::unmarshal::Reader::new(&data).read(), Ok(unit));
+
+    let rename = |case, field| {
+        let case = RenameAll::from_name(case);
+        let out = case.rename::<16>(field);
+        alloc::string::String::from_utf8(out[..case.renamed_len(field)].to_vec()).unwrap()
+    };
+    assert_eq!(rename("PascalCase", "restart_sec"), "RestartSec");
+    assert_eq!(rename("camelCase", "restart_sec"), "restartSec");
+    assert_eq!(rename("SCREAMING_SNAKE_CASE", "restart_sec"), "RESTART_SEC");
+    assert_eq!(rename("SCREAMING-KEBAB-CASE", "restart_sec"), "RESTART-SEC");
+    assert_eq!(rename("UPPERCASE", "id"), "ID");
+}
+
+#[test]
+fn test_transparent() {
+    define_transparent! {
+        #[derive(Clone, Copy, Debug, PartialEq)]
+        struct Celsius(f64);
+    }
+    define_transparent! {
+        #[derive(Clone, Copy, Debug, PartialEq)]
+        struct Label<'a>(&'a crate::String);
+    }
+    crate::assert_signature_eq!(Celsius, "d");
+    crate::assert_signature_eq!([Label], "as");
+
+    let value = crate::struct_new!(Celsius(21.5), Label(crate::String::from_str("room")));
+    let data = crate::marshal::marshal(value);
+    let crate::struct_match!(t, l) = crate::unmarshal::Reader::new(&data)
+        .read::<crate::struct_type!(Celsius, Label)>()
+        .unwrap();
+    assert_eq!(t, Celsius(21.5));
+    assert_eq!(l, Label(crate::String::from_str("room")));
+}
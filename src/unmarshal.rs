@@ -1,10 +1,19 @@
-use core::{marker::PhantomData, mem, result, slice};
+use core::{
+    marker::PhantomData,
+    mem,
+    num::NonZero,
+    ops::ControlFlow,
+    result, slice,
+    sync::atomic::{AtomicUsize, Ordering},
+};
 
+use arrayvec::ArrayVec;
 use thiserror::Error;
 
 use crate::{
     aligned,
-    signature::{Node, Signature, SignatureProxy},
+    metrics::Metrics,
+    signature::{self, MultiSignature, Signature, SignatureProxy},
     strings,
     types::*,
 };
@@ -19,38 +28,213 @@ pub enum Error {
     InvalidHeader,
     #[error("unsupported endian")]
     UnsupportedEndian,
+    #[error("nesting depth exceeded")]
+    NestingDepthExceeded,
+    #[error("decode budget exceeded")]
+    BudgetExceeded,
+    #[error("missing header field {0:?}")]
+    MissingField(crate::FieldId),
+    #[error("{0:?} limit exceeded")]
+    LimitExceeded(Limit),
 }
 
 impl Error {
     pub const fn name(self) -> &'static str {
         match self {
-            Error::InvalidArgs => "org.freedesktop.DBus.Error.InvalidArgs",
+            Error::InvalidArgs | Error::NestingDepthExceeded | Error::MissingField(_) => {
+                "org.freedesktop.DBus.Error.InvalidArgs"
+            }
             Error::NotEnoughData | Error::InvalidHeader | Error::UnsupportedEndian => {
                 "org.freedesktop.DBus.Error.InternalError"
             }
+            Error::BudgetExceeded | Error::LimitExceeded(_) => {
+                "org.freedesktop.DBus.Error.LimitsExceeded"
+            }
         }
     }
 }
 
 pub type Result<T> = result::Result<T, Error>;
 
+/// Which of the [`Limits`] a [`Reader`] hit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Limit {
+    StringLen,
+    ObjectPathLen,
+    SignatureLen,
+    StringBytes,
+    MessageLen,
+}
+
+/// Bounds on the strings a [`Reader`] accepts, checked against the length prefix before the
+/// string itself is looked at. Bus, interface, member and error names count as strings.
+///
+/// The message length bound is for a whole connection, whose broker may advertise a smaller
+/// maximum than the specification: [`MessageIterator`](crate::MessageIterator) and
+/// [`StreamDecoder`](crate::StreamDecoder) check it on the way in, and
+/// [`try_marshal_limited`](crate::marshal::try_marshal_limited) on the way out.
+#[derive(Clone, Copy, Debug)]
+pub struct Limits<'a> {
+    pub max_message_len: usize,
+    pub max_string_len: usize,
+    pub max_object_path_len: usize,
+    pub max_signature_len: usize,
+    /// What is left of the bytes all strings together may take, shared by every reader given
+    /// the same counter, so one counter per message bounds the whole message.
+    pub string_bytes: Option<&'a AtomicUsize>,
+}
+
+impl<'a> Limits<'a> {
+    /// The limits of the specification: no string longer than a message, and no signature
+    /// longer than 255 bytes.
+    pub const fn new() -> Self {
+        Self {
+            max_message_len: crate::marshal::MAX_MESSAGE_LEN,
+            max_string_len: crate::marshal::MAX_MESSAGE_LEN,
+            max_object_path_len: crate::marshal::MAX_MESSAGE_LEN,
+            max_signature_len: 255,
+            string_bytes: None,
+        }
+    }
+    pub const fn with_max_message_len(self, max_message_len: usize) -> Self {
+        Self {
+            max_message_len,
+            ..self
+        }
+    }
+    pub const fn with_max_string_len(self, max_string_len: usize) -> Self {
+        Self {
+            max_string_len,
+            ..self
+        }
+    }
+    pub const fn with_max_object_path_len(self, max_object_path_len: usize) -> Self {
+        Self {
+            max_object_path_len,
+            ..self
+        }
+    }
+    pub const fn with_max_signature_len(self, max_signature_len: usize) -> Self {
+        Self {
+            max_signature_len,
+            ..self
+        }
+    }
+    pub const fn with_string_bytes(self, string_bytes: &'a AtomicUsize) -> Self {
+        Self {
+            string_bytes: Some(string_bytes),
+            ..self
+        }
+    }
+    /// Fails with [`Error::LimitExceeded`] if a message of `len` bytes is too long.
+    pub const fn check_message_len(&self, len: usize) -> Result<()> {
+        if len > self.max_message_len {
+            return Err(Error::LimitExceeded(Limit::MessageLen));
+        }
+        Ok(())
+    }
+    fn check(&self, limit: Limit, len: usize) -> Result<()> {
+        let max = match limit {
+            Limit::StringLen => self.max_string_len,
+            Limit::ObjectPathLen => self.max_object_path_len,
+            Limit::SignatureLen => self.max_signature_len,
+            Limit::StringBytes | Limit::MessageLen => unreachable!(),
+        };
+        if len > max {
+            Err(Error::LimitExceeded(limit))?
+        }
+        if let Some(left) = self.string_bytes {
+            left.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |x| x.checked_sub(len))
+                .map_err(|_| Error::LimitExceeded(Limit::StringBytes))?;
+        }
+        Ok(())
+    }
+}
+
+impl Default for Limits<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[derive(Clone, Copy)]
 pub struct Reader<'a> {
     begin: *const u8,
     len: usize,
     count: usize,
+    strict: bool,
+    canonical_floats: bool,
+    byte_swap: bool,
+    limits: Limits<'a>,
+    metrics: Option<&'a dyn Metrics>,
     marker: PhantomData<&'a [u8]>,
 }
 
+// SAFETY: a `Reader` is a shared borrow of `[u8]` stored as a pointer and length; it never
+// writes through the pointer, so it may be sent and shared like `&'a [u8]`.
+unsafe impl Send for Reader<'_> {}
+unsafe impl Sync for Reader<'_> {}
+
+static_assertions::assert_impl_all!(Iter<'static>: Send, Sync);
+static_assertions::assert_impl_all!(ArrayIter<'static, u8>: Send, Sync);
+
 impl<'a> Reader<'a> {
     pub fn new(data: &'a [u8]) -> Self {
         Self {
             begin: data.as_ptr(),
             len: data.len(),
             count: 0,
+            strict: false,
+            canonical_floats: false,
+            byte_swap: false,
+            limits: Limits::new(),
+            metrics: None,
             marker: PhantomData,
         }
     }
+    /// In strict mode, values the spec marks as invalid but that have an obvious lenient
+    /// reading (such as booleans other than 0 or 1) are rejected instead.
+    pub const fn with_strict(self, strict: bool) -> Self {
+        Self { strict, ..self }
+    }
+    pub const fn is_strict(&self) -> bool {
+        self.strict
+    }
+    /// When set, any NaN other than [`CANONICAL_NAN`](crate::marshal::CANONICAL_NAN), including
+    /// every signaling NaN, is rejected.
+    pub const fn with_canonical_floats(self, canonical_floats: bool) -> Self {
+        Self {
+            canonical_floats,
+            ..self
+        }
+    }
+    /// Reads integers in the byte order opposite to this host's, for data from a peer of the
+    /// other [`Endian`](crate::Endian).
+    pub const fn with_byte_swap(self, byte_swap: bool) -> Self {
+        Self { byte_swap, ..self }
+    }
+    pub const fn is_byte_swapped(&self) -> bool {
+        self.byte_swap
+    }
+    /// Checks every string read from now on against `limits`, failing with
+    /// [`Error::LimitExceeded`] when one is too long.
+    pub const fn with_limits(self, limits: Limits<'a>) -> Self {
+        Self { limits, ..self }
+    }
+    pub const fn limits(&self) -> &Limits<'a> {
+        &self.limits
+    }
+    /// Reports the padding skipped from now on to `metrics`, as do the readers split off this
+    /// one and the iterators walking it.
+    pub const fn with_metrics(self, metrics: &'a dyn Metrics) -> Self {
+        Self {
+            metrics: Some(metrics),
+            ..self
+        }
+    }
+    pub const fn metrics(&self) -> Option<&'a dyn Metrics> {
+        self.metrics
+    }
     fn seek_unchecked(&mut self, n: usize) {
         self.count += n;
     }
@@ -73,38 +257,224 @@ impl<'a> Reader<'a> {
         Ok(aligned)
     }
     pub fn align_to(&mut self, align: usize) -> Result<()> {
-        self.count = self.aligned(align)?;
+        let aligned = self.aligned(align)?;
+        if let Some(metrics) = self.metrics.filter(|_| aligned > self.count) {
+            metrics.padding(aligned - self.count);
+        }
+        self.count = aligned;
         Ok(())
     }
+    /// The number of bytes read since the reader was created.
+    pub const fn position(&self) -> usize {
+        self.count
+    }
     pub fn remaining(&self) -> &'a [u8] {
         unsafe { slice::from_raw_parts(self.begin.add(self.count), self.len - self.count) }
     }
     pub fn read<T: Unmarshal<'a>>(&mut self) -> Result<T> {
         T::unmarshal(self)
     }
+    /// Reads a `T` after checking it against `declared`, the signature the next value is
+    /// declared to have, such as a message's signature header field. If `declared` is `v` and
+    /// `T` is not a variant, the variant's own signature must match `T` and its contents are
+    /// read. Fails with [`Error::InvalidArgs`] on a mismatch, leaving the reader where it was.
+    ///
+    /// [`Reader::read`] trusts the caller to know the type; this is for handlers that would
+    /// rather check than trust.
+    pub fn read_checked<T: Unmarshal<'a> + MultiSignature>(
+        &mut self,
+        declared: &strings::Signature,
+    ) -> Result<T> {
+        let mut r = *self;
+        if declared != T::SIGNATURE
+            && (declared.as_bytes() != b"v" || r.read::<&strings::Signature>()? != T::SIGNATURE)
+        {
+            Err(Error::InvalidArgs)?
+        }
+        let value = r.read()?;
+        *self = r;
+        Ok(value)
+    }
     pub fn read_byte(&mut self) -> Result<u8> {
         let res = *self.remaining().get(0).ok_or(Error::NotEnoughData)?;
         self.seek_unchecked(1);
         Ok(res)
     }
+    /// Reads one value per argument of `f`, in order, and calls `f` with them.
+    pub fn read_args<Args, F: ArgsFn<'a, Args>>(&mut self, f: F) -> Result<F::Output> {
+        f.call(self)
+    }
     pub fn read_bytes(&mut self, len: usize) -> Result<&'a [u8]> {
         let res = self.remaining().get(..len).ok_or(Error::NotEnoughData)?;
         self.seek_unchecked(len);
         Ok(res)
     }
-    fn next_string_like(&mut self) -> Result<&'a [u8]> {
+    /// Reads an `s` or `o` into `buf` for diagnostics, without allocating or assuming UTF-8.
+    /// Bytes other than printable ASCII are escaped as `\xNN` and backslashes as `\\`; if the
+    /// escaped string does not fit, it is cut short and ends in `...`. Returns the written part
+    /// of `buf`.
+    pub fn read_string_into<'b>(&mut self, buf: &'b mut [u8]) -> Result<&'b str> {
+        let s = self.next_string_like(Limit::StringLen)?;
+        Ok(escape_into(s, buf))
+    }
+    /// Skips one value of the single complete type at the front of `signature`, returning the
+    /// rest of the signature.
+    pub fn skip<'s>(&mut self, signature: &'s [u8]) -> Result<&'s [u8]> {
+        self.skip_nested(signature, 0)
+    }
+    /// [`Reader::skip`] inside `depth` containers.
+    fn skip_nested<'s>(&mut self, signature: &'s [u8], depth: usize) -> Result<&'s [u8]> {
+        let (&code, rest) = signature.split_first().ok_or(Error::InvalidArgs)?;
+        if matches!(code, b'v' | b'(' | b'{') && depth >= signature::MAX_NESTING_DEPTH {
+            Err(Error::NestingDepthExceeded)?
+        }
+        match code {
+            b'y' => drop(self.read::<u8>()?),
+            b'n' | b'q' => drop(self.read::<u16>()?),
+            b'b' | b'i' | b'u' | b'h' => drop(self.read::<u32>()?),
+            b'x' | b't' | b'd' => drop(self.read::<u64>()?),
+            b's' => drop(self.next_string_like(Limit::StringLen)?),
+            b'o' => drop(self.next_string_like(Limit::ObjectPathLen)?),
+            b'g' => drop(self.read::<&strings::Signature>()?),
+            b'v' => {
+                let inner: &strings::Signature = self.read()?;
+                if !self.skip_nested(inner, depth + 1)?.is_empty() {
+                    Err(Error::InvalidArgs)?
+                }
+            }
+            b'a' => {
+                let len: u32 = self.read()?;
+                let elem_len = signature::complete_type_len(rest).ok_or(Error::InvalidArgs)?;
+                self.align_to(signature::code_alignment(rest[0]).unwrap())?;
+                self.seek(len as _)?;
+                return Ok(&rest[elem_len..]);
+            }
+            b'(' | b'{' => {
+                let close = if code == b'(' { b')' } else { b'}' };
+                self.align_to(8)?;
+                let mut rest = rest;
+                while rest.first() != Some(&close) {
+                    rest = self.skip_nested(rest, depth + 1)?;
+                }
+                return Ok(&rest[1..]);
+            }
+            _ => Err(Error::InvalidArgs)?,
+        }
+        Ok(rest)
+    }
+    /// The part of `start` this reader has consumed since it was copied from it.
+    fn consumed_since(&self, start: &Self) -> Self {
+        Self {
+            len: self.count,
+            ..*start
+        }
+    }
+    fn next_string_like(&mut self, limit: Limit) -> Result<&'a [u8]> {
         let len = self.read::<u32>()? as usize;
-        let res = self.remaining().get(..len).ok_or(Error::NotEnoughData)?;
-        self.seek_unchecked(len + 1); // sentinel 0
+        self.limits.check(limit, len)?;
+        self.next_nul_terminated(len)
+    }
+    fn next_nul_terminated(&mut self, len: usize) -> Result<&'a [u8]> {
+        let (&nul, res) = self
+            .remaining()
+            .get(..len + 1)
+            .ok_or(Error::NotEnoughData)?
+            .split_last()
+            .unwrap();
+        if nul != 0 {
+            Err(Error::InvalidArgs)?
+        }
+        self.seek_unchecked(len + 1);
         Ok(res)
     }
 }
 
+/// Marks a string cut short by [`Reader::read_string_into`].
+const TRUNCATED: &[u8] = b"...";
+
+fn escape_into<'b>(s: &[u8], buf: &'b mut [u8]) -> &'b str {
+    fn escape(c: u8, scratch: &mut [u8; 4]) -> &[u8] {
+        const HEX: &[u8; 16] = b"0123456789abcdef";
+        match c {
+            b'\\' => b"\\\\",
+            0x20..=0x7e => {
+                scratch[0] = c;
+                &scratch[..1]
+            }
+            _ => {
+                *scratch = [b'\\', b'x', HEX[c as usize >> 4], HEX[c as usize & 15]];
+                scratch
+            }
+        }
+    }
+    let mut scratch = [0; 4];
+    let total: usize = s.iter().map(|&c| escape(c, &mut scratch).len()).sum();
+    let room = if total <= buf.len() {
+        buf.len()
+    } else {
+        buf.len().saturating_sub(TRUNCATED.len())
+    };
+    let mut len = 0;
+    for &c in s {
+        let escaped = escape(c, &mut scratch);
+        if len + escaped.len() > room {
+            break;
+        }
+        buf[len..len + escaped.len()].copy_from_slice(escaped);
+        len += escaped.len();
+    }
+    if total > buf.len() {
+        let marker = &TRUNCATED[..TRUNCATED.len().min(buf.len() - len)];
+        buf[len..len + marker.len()].copy_from_slice(marker);
+        len += marker.len();
+    }
+    str::from_utf8(&buf[..len]).unwrap()
+}
+
 pub trait Unmarshal<'a>: Sized {
     /// read without checking signature
     fn unmarshal(r: &mut Reader<'a>) -> Result<Self>;
 }
 
+/// A function whose arguments are read one after another by [`Reader::read_args`].
+///
+/// `Args` is the tuple of argument types; it only serves to tell the impls for each arity apart.
+pub trait ArgsFn<'a, Args> {
+    type Output;
+    /// The argument types, concatenated.
+    type Signature: MultiSignature;
+    fn call(self, r: &mut Reader<'a>) -> Result<Self::Output>;
+}
+
+macro_rules! impl_args_fn {
+    ($($arg:ident),*) => {
+        impl<'a, F, R, $($arg),*> ArgsFn<'a, ($($arg,)*)> for F
+        where
+            F: FnOnce($($arg),*) -> R,
+            $($arg: Unmarshal<'a> + Signature,)*
+        {
+            type Output = R;
+            type Signature = crate::multiple_type!($($arg),*);
+
+            #[allow(non_snake_case, unused_variables)]
+            fn call(self, r: &mut Reader<'a>) -> Result<R> {
+                $(let $arg = r.read()?;)*
+                Ok(self($($arg),*))
+            }
+        }
+    };
+}
+
+impl_args_fn!();
+impl_args_fn!(A);
+impl_args_fn!(A, B);
+impl_args_fn!(A, B, C);
+impl_args_fn!(A, B, C, D);
+impl_args_fn!(A, B, C, D, E);
+impl_args_fn!(A, B, C, D, E, G);
+impl_args_fn!(A, B, C, D, E, G, H);
+impl_args_fn!(A, B, C, D, E, G, H, I);
+
 macro_rules! impl_unmarshal {
     ($($t: ty),* $(,)?) => {
         $(impl Unmarshal<'_> for $t {
@@ -116,49 +486,211 @@ macro_rules! impl_unmarshal {
                     .ok_or(Error::NotEnoughData)?;
                 let res = Self::from_ne_bytes(bytes.as_array().copied().unwrap());
                 r.seek_unchecked(mem::size_of::<Self>());
-                Ok(res)
+                Ok(if r.byte_swap { res.swap_bytes() } else { res })
             }
         })*
     };
 }
 
-impl_unmarshal!(u8, i16, u16, i32, u32, i64, u64, f64);
+impl_unmarshal!(u8, i16, u16, i32, u32, i64, u64);
+
+impl Unmarshal<'_> for f64 {
+    fn unmarshal(r: &mut Reader) -> Result<Self> {
+        let x = f64::from_bits(r.read()?);
+        if r.canonical_floats
+            && x.is_nan()
+            && x.to_bits() != crate::marshal::CANONICAL_NAN.to_bits()
+        {
+            Err(Error::InvalidArgs)?
+        }
+        Ok(x)
+    }
+}
+
+macro_rules! impl_non_zero {
+    ($($t: ty),* $(,)?) => {
+        $(impl Unmarshal<'_> for NonZero<$t> {
+            fn unmarshal(r: &mut Reader) -> Result<Self> {
+                NonZero::new(r.read()?).ok_or(Error::InvalidArgs)
+            }
+        })*
+    };
+}
+
+impl_non_zero!(u8, i16, u16, i32, u32, i64, u64);
+
+impl Unmarshal<'_> for UnixFd {
+    fn unmarshal(r: &mut Reader) -> Result<Self> {
+        u32::unmarshal(r).map(Self)
+    }
+}
+
+impl Unmarshal<'_> for F32AsDouble {
+    fn unmarshal(r: &mut Reader) -> Result<Self> {
+        f64::unmarshal(r).map(|x| Self(x as f32))
+    }
+}
+
+macro_rules! impl_fixed_point {
+    ($($t: ty),* $(,)?) => {
+        $(impl<const SCALE: u32> Unmarshal<'_> for FixedPoint<$t, SCALE> {
+            fn unmarshal(r: &mut Reader) -> Result<Self> {
+                let scaled = f64::unmarshal(r)? * SCALE as f64;
+                let rounded = if scaled < 0.0 { scaled - 0.5 } else { scaled + 0.5 };
+                if !(<$t>::MIN as f64 - 1.0 < rounded && rounded < <$t>::MAX as f64 + 1.0) {
+                    Err(Error::InvalidArgs)?
+                }
+                Ok(Self(rounded as $t))
+            }
+        })*
+    };
+}
+
+impl_fixed_point!(i16, u16, i32, u32);
+
+impl<'a, T, C: Codec<T>> Unmarshal<'a> for With<T, C>
+where
+    C::Wire: Unmarshal<'a>,
+{
+    fn unmarshal(r: &mut Reader<'a>) -> Result<Self> {
+        C::decode(r.read()?).map(Self::new)
+    }
+}
+
+/// Reads an `ay`.
+#[cfg(any(feature = "uuid", feature = "net"))]
+fn read_byte_array<'a>(r: &mut Reader<'a>) -> Result<&'a [u8]> {
+    let len: u32 = r.read()?;
+    r.read_bytes(len as _)
+}
+
+/// Reads an `s` and parses it.
+#[cfg(any(feature = "uuid", feature = "net"))]
+fn read_parsed<T: core::str::FromStr>(r: &mut Reader) -> Result<T> {
+    let s = r.next_string_like(Limit::StringLen)?;
+    str::from_utf8(s)
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .ok_or(Error::InvalidArgs)
+}
+
+#[cfg(feature = "uuid")]
+impl Unmarshal<'_> for UuidBytes {
+    fn unmarshal(r: &mut Reader) -> Result<Self> {
+        let bytes = read_byte_array(r)?;
+        uuid::Uuid::from_slice(bytes)
+            .map(Self)
+            .map_err(|_| Error::InvalidArgs)
+    }
+}
+
+#[cfg(feature = "uuid")]
+impl Unmarshal<'_> for UuidString {
+    fn unmarshal(r: &mut Reader) -> Result<Self> {
+        read_parsed(r).map(Self)
+    }
+}
+
+#[cfg(feature = "net")]
+fn read_ip_bytes(r: &mut Reader) -> Result<core::net::IpAddr> {
+    r.align_to(8)?;
+    let family: u8 = r.read()?;
+    let octets = read_byte_array(r)?;
+    Ok(match (family, octets.len()) {
+        (AF_INET, 4) => <[u8; 4]>::try_from(octets).unwrap().into(),
+        (AF_INET6, 16) => <[u8; 16]>::try_from(octets).unwrap().into(),
+        _ => Err(Error::InvalidArgs)?,
+    })
+}
+
+#[cfg(feature = "net")]
+impl Unmarshal<'_> for IpAddrString {
+    fn unmarshal(r: &mut Reader) -> Result<Self> {
+        read_parsed(r).map(Self)
+    }
+}
+
+#[cfg(feature = "net")]
+impl Unmarshal<'_> for IpAddrBytes {
+    fn unmarshal(r: &mut Reader) -> Result<Self> {
+        read_ip_bytes(r).map(Self)
+    }
+}
+
+#[cfg(feature = "net")]
+impl Unmarshal<'_> for SocketAddrString {
+    fn unmarshal(r: &mut Reader) -> Result<Self> {
+        read_parsed(r).map(Self)
+    }
+}
+
+#[cfg(feature = "net")]
+impl Unmarshal<'_> for SocketAddrBytes {
+    fn unmarshal(r: &mut Reader) -> Result<Self> {
+        let ip = read_ip_bytes(r)?;
+        let port = r.read()?;
+        Ok(Self(core::net::SocketAddr::new(ip, port)))
+    }
+}
 
 impl Unmarshal<'_> for bool {
     fn unmarshal(r: &mut Reader) -> Result<Self> {
-        u32::unmarshal(r).map(|x| x != 0)
+        match u32::unmarshal(r)? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ if r.strict => Err(Error::InvalidArgs),
+            _ => Ok(true),
+        }
     }
 }
 
 impl<'a> Unmarshal<'a> for &'a strings::String {
     fn unmarshal(r: &mut Reader<'a>) -> Result<Self> {
-        r.next_string_like().map(strings::String::from_bytes)
+        r.next_string_like(Limit::StringLen)
+            .map(strings::String::from_bytes)
     }
 }
 
 impl<'a> Unmarshal<'a> for &'a strings::ObjectPath {
     fn unmarshal(r: &mut Reader<'a>) -> Result<Self> {
-        r.next_string_like().map(strings::ObjectPath::from_bytes)
+        r.next_string_like(Limit::ObjectPathLen)
+            .map(strings::ObjectPath::from_bytes)
     }
 }
 
+macro_rules! impl_name {
+    ($($t:ty),* $(,)?) => {
+        $(impl<'a> Unmarshal<'a> for &'a $t {
+            fn unmarshal(r: &mut Reader<'a>) -> Result<Self> {
+                r.next_string_like(Limit::StringLen)
+                    .and_then(<$t>::validate)
+            }
+        })*
+    };
+}
+
+impl_name!(
+    strings::BusName,
+    strings::UniqueName,
+    strings::WellKnownName,
+    strings::InterfaceName,
+    strings::MemberName,
+    strings::ErrorName,
+);
+
 impl<'a> Unmarshal<'a> for &'a strings::Signature {
     fn unmarshal(r: &mut Reader<'a>) -> Result<Self> {
         let len = r.read::<u8>()? as usize;
-        let res = r
-            .remaining()
-            .get(..len)
-            .ok_or(Error::NotEnoughData)
-            .map(strings::Signature::from_bytes)?;
-        r.seek_unchecked(len + 1);
-        Ok(res)
+        r.limits.check(Limit::SignatureLen, len)?;
+        r.next_nul_terminated(len)
+            .map(strings::Signature::from_bytes)
     }
 }
 
 impl<'a, T: Unmarshal<'a> + Signature> Unmarshal<'a> for Variant<T> {
     fn unmarshal(r: &mut Reader<'a>) -> Result<Self> {
         let sig: &strings::Signature = r.read()?;
-        if sig != T::DATA.signature() {
+        if sig != T::SIGNATURE {
             Err(Error::InvalidArgs)?
         }
         let inner = r.read()?;
@@ -197,25 +729,63 @@ pub struct ArrayIter<'a, T> {
     marker: PhantomData<T>,
 }
 
+impl<T> Clone for ArrayIter<'_, T> {
+    fn clone(&self) -> Self {
+        Self {
+            reader: self.reader,
+            marker: PhantomData,
+        }
+    }
+}
+
 impl<'a, T: Signature> SignatureProxy for ArrayIter<'a, T> {
     type Proxy = [T];
 }
 
 impl<'a, T: Signature + Unmarshal<'a>> ArrayIter<'a, T> {
-    fn next(&mut self) -> iter::IterResult<T> {
+    fn try_next(&mut self) -> iter::IterResult<T> {
         if self.reader.remaining().is_empty() {
             Err(iter::IterErr::EndOfIteration)?
         }
         self.reader.align_to(T::ALIGNMENT)?;
         Ok(self.reader.read()?)
     }
+    /// The number of elements left, computed from the remaining bytes without decoding them,
+    /// or `None` if the size of `T` depends on the value. Fails with [`Error::InvalidArgs`] if
+    /// the bytes do not hold a whole number of elements.
+    pub fn len(&self) -> Result<Option<usize>> {
+        let Some(size) = T::FIXED_SIZE else {
+            return Ok(None);
+        };
+        if self.is_empty() {
+            return Ok(Some(0));
+        }
+        let stride = aligned(size, T::ALIGNMENT);
+        let rest = self.reader.len - self.reader.aligned(T::ALIGNMENT)?;
+        match rest.checked_sub(size) {
+            Some(x) if x % stride == 0 => Ok(Some(x / stride + 1)),
+            _ => Err(Error::InvalidArgs),
+        }
+    }
+    pub fn is_empty(&self) -> bool {
+        self.reader.remaining().is_empty()
+    }
+    /// The elements as an array, failing with [`Error::InvalidArgs`] unless there are exactly
+    /// `N` of them.
+    pub fn collect_array<const N: usize>(self) -> Result<[T; N]> {
+        let mut res = ArrayVec::<T, N>::new();
+        for x in self {
+            res.try_push(x?).map_err(|_| Error::InvalidArgs)?;
+        }
+        res.into_inner().map_err(|_| Error::InvalidArgs)
+    }
 }
 
 impl<'a, T: Signature + Unmarshal<'a>> Iterator for ArrayIter<'a, T> {
     type Item = Result<T>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        flatten(self.next())
+        flatten(self.try_next())
     }
 }
 
@@ -230,5 +800,499 @@ impl<'a, T: Unmarshal<'a> + Signature> Unmarshal<'a> for ArrayIter<'a, T> {
     }
 }
 
+#[cfg(feature = "alloc")]
+impl<'a, T: Unmarshal<'a> + Signature> Unmarshal<'a> for alloc::vec::Vec<T> {
+    fn unmarshal(r: &mut Reader<'a>) -> Result<Self> {
+        let iter: ArrayIter<'a, T> = r.read()?;
+        let mut res = alloc::vec::Vec::with_capacity(iter.len()?.unwrap_or(0));
+        for x in iter {
+            res.push(x?);
+        }
+        Ok(res)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'a, T: Unmarshal<'a> + Signature> Unmarshal<'a> for alloc::boxed::Box<[T]> {
+    fn unmarshal(r: &mut Reader<'a>) -> Result<Self> {
+        r.read::<alloc::vec::Vec<T>>()
+            .map(alloc::vec::Vec::into_boxed_slice)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'a, K, V> Unmarshal<'a> for alloc::collections::BTreeMap<K, V>
+where
+    K: Unmarshal<'a> + Signature + Ord,
+    V: Unmarshal<'a> + Signature,
+{
+    fn unmarshal(r: &mut Reader<'a>) -> Result<Self> {
+        let entries: ArrayIter<'a, Entry<K, V>> = r.read()?;
+        entries.map(|x| x.map(|Entry(k, v)| (k, v))).collect()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a, K, V, S> Unmarshal<'a> for std::collections::HashMap<K, V, S>
+where
+    K: Unmarshal<'a> + Signature + Eq + core::hash::Hash,
+    V: Unmarshal<'a> + Signature,
+    S: core::hash::BuildHasher + Default,
+{
+    fn unmarshal(r: &mut Reader<'a>) -> Result<Self> {
+        let entries: ArrayIter<'a, Entry<K, V>> = r.read()?;
+        entries.map(|x| x.map(|Entry(k, v)| (k, v))).collect()
+    }
+}
+
+impl<'a, V: Unmarshal<'a> + Signature> ArrayIter<'a, Entry<&'a strings::ObjectPath, V>> {
+    /// Entries whose path is `prefix` or lies below it.
+    pub fn under<'p>(
+        self,
+        prefix: &'p strings::ObjectPath,
+    ) -> impl Iterator<Item = Result<Entry<&'a strings::ObjectPath, V>>> + use<'a, 'p, V> {
+        self.filter(move |x| match x {
+            Ok(Entry(path, _)) => path.starts_with_path(prefix),
+            Err(_) => true,
+        })
+    }
+    /// The value for exactly `path`, if present.
+    pub fn get(self, path: &strings::ObjectPath) -> Result<Option<V>> {
+        for x in self {
+            let Entry(k, v) = x?;
+            if k == path {
+                return Ok(Some(v));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Decodes the dict array `a{KV}` at the start of `bytes` one entry at a time, passing each to
+/// `f` until it breaks. Nothing is allocated and no entry is decoded after the break.
+pub fn for_each_entry<'a, K, V, B>(
+    bytes: &'a [u8],
+    mut f: impl FnMut(K, V) -> ControlFlow<B>,
+) -> Result<ControlFlow<B>>
+where
+    K: Unmarshal<'a> + Signature,
+    V: Unmarshal<'a> + Signature,
+{
+    let entries: ArrayIter<Entry<K, V>> = Reader::new(bytes).read()?;
+    for entry in entries {
+        let Entry(k, v) = entry?;
+        if let ControlFlow::Break(x) = f(k, v) {
+            return Ok(ControlFlow::Break(x));
+        }
+    }
+    Ok(ControlFlow::Continue(()))
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_object_path_dict() {
+    let path = strings::ObjectPath::from_str;
+    let data = crate::marshal::marshal(
+        &[
+            Entry(path("/a"), &[Entry("x", Variant(1u8))][..]),
+            Entry(path("/a/b"), &[][..]),
+            Entry(path("/ab"), &[][..]),
+        ][..],
+    );
+    type Dict<'a> = ArrayIter<
+        'a,
+        Entry<&'a strings::ObjectPath, ArrayIter<'a, Entry<&'a strings::String, DynVariant<'a>>>>,
+    >;
+    crate::assert_signature_eq!(Dict, "a{oa{sv}}");
+    let dict: Dict = Reader::new(&data).read().unwrap();
+    let paths: alloc::vec::Vec<_> = dict
+        .clone()
+        .under(path("/a"))
+        .map(|x| x.unwrap().0)
+        .collect();
+    assert_eq!(paths, [path("/a"), path("/a/b")]);
+    let mut props = dict.get(path("/a")).unwrap().unwrap();
+    let Entry(name, value) = props.next().unwrap().unwrap();
+    assert_eq!(name, strings::String::from_str("x"));
+    assert_eq!(value.try_get::<u8>(), Ok(1));
+}
+
+#[test]
+fn test_for_each_entry() {
+    let data = crate::marshal::marshal(&[Entry("a", 1u32), Entry("b", 2), Entry("c", 3)][..]);
+    let mut sum = 0;
+    let found = for_each_entry(&data, |k: &strings::String, v: u32| {
+        sum += v;
+        if k == strings::String::from_str("b") {
+            ControlFlow::Break(v)
+        } else {
+            ControlFlow::Continue(())
+        }
+    });
+    assert_eq!(found, Ok(ControlFlow::Break(2)));
+    assert_eq!(sum, 3);
+    assert_eq!(
+        for_each_entry(&data, |_: &strings::String, _: u32| {
+            ControlFlow::<()>::Continue(())
+        }),
+        Ok(ControlFlow::Continue(()))
+    );
+    assert_eq!(
+        for_each_entry(&data[..10], |_: &strings::String, _: u32| {
+            ControlFlow::<()>::Continue(())
+        }),
+        Err(Error::NotEnoughData)
+    );
+}
+
+#[test]
+fn test_collect_array() {
+    let data = crate::marshal::marshal(&["a", "b"][..]);
+    let strings = || {
+        Reader::new(&data)
+            .read::<ArrayIter<&strings::String>>()
+            .unwrap()
+    };
+    let [a, b] = strings().collect_array().unwrap();
+    assert_eq!((a.as_bytes(), b.as_bytes()), (&b"a"[..], &b"b"[..]));
+    assert_eq!(strings().collect_array::<1>(), Err(Error::InvalidArgs));
+    assert_eq!(strings().collect_array::<3>(), Err(Error::InvalidArgs));
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_array_len() {
+    type Elem = Struct<crate::multiple_type!(u64, u8)>;
+    let elem = Struct(crate::multiple_new!(1u64, 2u8));
+    let data = crate::marshal::marshal(&[elem; 3][..]);
+    let mut elems = Reader::new(&data).read::<ArrayIter<Elem>>().unwrap();
+    assert_eq!(elems.len(), Ok(Some(3)));
+    elems.next().unwrap().unwrap();
+    assert_eq!(elems.len(), Ok(Some(2)));
+    assert_eq!(elems.by_ref().count(), 2);
+    assert_eq!(elems.len(), Ok(Some(0)));
+
+    let data = crate::marshal::marshal(&["a"][..]);
+    let strings = Reader::new(&data).read::<ArrayIter<&strings::String>>();
+    assert_eq!(strings.unwrap().len(), Ok(None));
+
+    let mut data = 5u32.to_ne_bytes().to_vec();
+    data.extend([0; 5]);
+    let numbers = Reader::new(&data).read::<ArrayIter<u32>>().unwrap();
+    assert_eq!(numbers.len(), Err(Error::InvalidArgs));
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_owned_arrays() {
+    use alloc::{boxed::Box, vec, vec::Vec};
+
+    let nested = vec![vec![1u64, 2], vec![], vec![3]];
+    let data = crate::marshal::marshal(&nested);
+    assert_eq!(Reader::new(&data).read::<Vec<Vec<u64>>>(), Ok(nested));
+
+    let names: Box<[&strings::String]> = ["a", "bc"].map(strings::String::from_str).into();
+    let data = crate::marshal::marshal(crate::multiple_new!(1u8, &names));
+    let mut r = Reader::new(&data);
+    assert_eq!(r.read::<u8>(), Ok(1));
+    assert_eq!(r.read::<Box<[&strings::String]>>(), Ok(names));
+    assert!(r.remaining().is_empty());
+
+    let truncated = crate::marshal::marshal(&vec![1u32, 2]);
+    assert_eq!(
+        Reader::new(&truncated[..truncated.len() - 1])
+            .read::<Vec<u32>>()
+            .err(),
+        Some(Error::NotEnoughData)
+    );
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_maps() {
+    use alloc::collections::BTreeMap;
+
+    let map = BTreeMap::from([("b", Variant(2u32)), ("a", Variant(1u32))]);
+    let data = crate::marshal::marshal(&map);
+    crate::assert_signature_eq!(BTreeMap<&str, Variant<u32>>, "a{sv}");
+    let read: BTreeMap<&strings::String, Variant<u32>> = Reader::new(&data).read().unwrap();
+    assert_eq!(
+        read.iter()
+            .map(|(k, v)| (k.as_bytes(), v.0))
+            .collect::<alloc::vec::Vec<_>>(),
+        [(&b"a"[..], 1), (b"b", 2)]
+    );
+
+    // Later entries replace earlier ones with the same key.
+    let data = crate::marshal::marshal(&[Entry(1u8, 10u16), Entry(1, 11), Entry(2, 20)][..]);
+    let read: BTreeMap<u8, u16> = Reader::new(&data).read().unwrap();
+    assert_eq!(read, BTreeMap::from([(1, 11), (2, 20)]));
+
+    #[cfg(feature = "std")]
+    {
+        use std::collections::HashMap;
+
+        let map = HashMap::from([(1u8, 10u16), (2, 20)]);
+        let data = crate::marshal::marshal(&map);
+        let read: HashMap<u8, u16> = Reader::new(&data).read().unwrap();
+        assert_eq!(read, map);
+        assert_eq!(
+            Reader::new(&data).read::<BTreeMap<u8, u16>>().unwrap(),
+            BTreeMap::from([(1, 10), (2, 20)])
+        );
+    }
+}
+
+#[test]
+fn test_read_checked() {
+    let data = crate::marshal::marshal(crate::multiple_new!(1u32, Variant(2u8)));
+    let signature = |x| strings::Signature::from_str(x);
+
+    let mut r = Reader::new(&data);
+    assert_eq!(
+        r.read_checked::<u8>(signature("u")),
+        Err(Error::InvalidArgs)
+    );
+    assert_eq!(r.position(), 0);
+    assert_eq!(r.read_checked::<u32>(signature("u")), Ok(1));
+    assert_eq!(
+        r.clone()
+            .read_checked::<Variant<u8>>(signature("v"))
+            .map(|x| x.0),
+        Ok(2)
+    );
+    assert_eq!(
+        r.read_checked::<u16>(signature("v")),
+        Err(Error::InvalidArgs)
+    );
+    assert_eq!(r.read_checked::<u8>(signature("v")), Ok(2));
+    assert!(r.remaining().is_empty());
+
+    let mut r = Reader::new(&data);
+    type Body = crate::multiple_type!(u32, Variant<u8>);
+    assert!(r.read_checked::<Body>(signature("uv")).is_ok());
+}
+
+#[test]
+fn test_fixed_point() {
+    let data = crate::marshal::marshal(FixedPoint::<i32, 100>(-1234));
+    assert_eq!(Reader::new(&data).read::<f64>(), Ok(-12.34));
+    assert_eq!(Reader::new(&data).read(), Ok(FixedPoint::<i32, 100>(-1234)));
+    assert_eq!(
+        Reader::new(&data).read::<FixedPoint<u16, 1>>(),
+        Err(Error::InvalidArgs)
+    );
+    let data = crate::marshal::marshal(F32AsDouble(0.1));
+    assert_eq!(Reader::new(&data).read(), Ok(F32AsDouble(0.1)));
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_with_codec() {
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    enum Level {
+        Low,
+        High,
+    }
+    struct LevelCodec;
+    impl Codec<Level> for LevelCodec {
+        type Wire = u8;
+        fn encode(value: &Level) -> u8 {
+            *value as u8
+        }
+        fn decode(wire: u8) -> Result<Level> {
+            match wire {
+                0 => Ok(Level::Low),
+                1 => Ok(Level::High),
+                _ => Err(Error::InvalidArgs),
+            }
+        }
+    }
+    type Args = crate::multiple_type!(u32, With<Level, LevelCodec>);
+    crate::assert_signature_eq!(Args, "uy");
+    let args: Args = crate::multiple_new!(7, With::new(Level::High));
+    let data = crate::marshal::marshal(args);
+    assert_eq!(data[4], 1);
+    assert_eq!(
+        Reader::new(&data).read_args(|n: u32, level: With<Level, LevelCodec>| (n, level.0)),
+        Ok((7, Level::High))
+    );
+    assert_eq!(
+        Reader::new(&[2]).read::<With<Level, LevelCodec>>(),
+        Err(Error::InvalidArgs)
+    );
+}
+
+#[test]
+fn test_canonical_floats() {
+    let signaling = f64::from_bits(0x7ff0_0000_0000_0001);
+    let data = crate::marshal::marshal(signaling);
+    assert!(Reader::new(&data).read::<f64>().unwrap().is_nan());
+    assert_eq!(
+        Reader::new(&data).with_canonical_floats(true).read::<f64>(),
+        Err(Error::InvalidArgs)
+    );
+    let data = crate::marshal::marshal_canonical(signaling);
+    let x: f64 = Reader::new(&data)
+        .with_canonical_floats(true)
+        .read()
+        .unwrap();
+    assert_eq!(x.to_bits(), crate::marshal::CANONICAL_NAN.to_bits());
+}
+
+#[test]
+fn test_read_args() {
+    let data = crate::marshal::marshal(crate::multiple_new!(7u32, "x", &[1u8, 2][..]));
+    let sum = Reader::new(&data)
+        .read_args(|a: u32, b: &strings::String, c: ArrayIter<u8>| {
+            assert_eq!(b, strings::String::from_str("x"));
+            a + c.map(|x| x.unwrap() as u32).sum::<u32>()
+        })
+        .unwrap();
+    assert_eq!(sum, 10);
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_read_string_into() {
+    let data = crate::marshal::marshal(strings::String::from_bytes(b"a\\b\n\xff"));
+    let mut buf = [0; 32];
+    assert_eq!(
+        Reader::new(&data).read_string_into(&mut buf),
+        Ok(r"a\\b\x0a\xff")
+    );
+    let mut buf = [0; 8];
+    assert_eq!(
+        Reader::new(&data).read_string_into(&mut buf),
+        Ok(r"a\\b...")
+    );
+    let mut buf = [0; 2];
+    assert_eq!(Reader::new(&data).read_string_into(&mut buf), Ok(".."));
+}
+
+#[test]
+fn test_strict() {
+    let data = 2u32.to_ne_bytes();
+    assert_eq!(Reader::new(&data).read(), Ok(true));
+    assert_eq!(
+        Reader::new(&data).with_strict(true).read::<bool>(),
+        Err(Error::InvalidArgs)
+    );
+    let data = 0u32.to_ne_bytes();
+    assert_eq!(
+        Reader::new(&data).read::<NonZero<u32>>(),
+        Err(Error::InvalidArgs)
+    );
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_skip_depth() {
+    // `depth` variants, each holding the next, around a byte.
+    let nested = |depth| {
+        let mut data = b"\x01v\0".repeat(depth - 1);
+        data.extend(b"\x01y\0\x07");
+        data
+    };
+    let data = nested(signature::MAX_NESTING_DEPTH);
+    assert_eq!(Reader::new(&data).skip(b"vy"), Ok(&b"y"[..]));
+    let data = nested(signature::MAX_NESTING_DEPTH + 1);
+    assert_eq!(
+        Reader::new(&data).skip(b"v"),
+        Err(Error::NestingDepthExceeded)
+    );
+    let data = nested(100_000);
+    assert_eq!(
+        Reader::new(&data).skip(b"v"),
+        Err(Error::NestingDepthExceeded)
+    );
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_limits() {
+    let data = crate::marshal::marshal(crate::multiple_new!(
+        "hello",
+        strings::ObjectPath::from_str("/a/b"),
+        strings::Signature::from_str("ii")
+    ));
+    let read = |limits| {
+        let mut r = Reader::new(&data).with_limits(limits);
+        r.read::<&strings::String>()?;
+        r.read::<&strings::ObjectPath>()?;
+        r.read::<&strings::Signature>().map(drop)
+    };
+    assert!(read(Limits::new()).is_ok());
+    assert_eq!(
+        read(Limits::new().with_max_string_len(4)),
+        Err(Error::LimitExceeded(Limit::StringLen))
+    );
+    assert_eq!(
+        read(Limits::new().with_max_object_path_len(3)),
+        Err(Error::LimitExceeded(Limit::ObjectPathLen))
+    );
+    assert_eq!(
+        read(Limits::new().with_max_signature_len(1)),
+        Err(Error::LimitExceeded(Limit::SignatureLen))
+    );
+    let left = AtomicUsize::new(10);
+    assert_eq!(
+        read(Limits::new().with_string_bytes(&left)),
+        Err(Error::LimitExceeded(Limit::StringBytes))
+    );
+    assert_eq!(left.load(Ordering::Relaxed), 1);
+}
+
+#[test]
+#[cfg(all(feature = "uuid", feature = "net"))]
+fn test_adapters() {
+    use core::net::{IpAddr, SocketAddr};
+
+    let id = uuid::Uuid::from_u128(0x67e55044_10b1_426f_9247_bb680e5fe0c8);
+    let data = crate::marshal::marshal(UuidString(id));
+    assert_eq!(
+        Reader::new(&data).read(),
+        Ok(strings::String::from_str(
+            "67e55044-10b1-426f-9247-bb680e5fe0c8"
+        ))
+    );
+    assert_eq!(Reader::new(&data).read(), Ok(UuidString(id)));
+    let data = crate::marshal::marshal(UuidBytes(id));
+    assert_eq!(Reader::new(&data).read(), Ok(UuidBytes(id)));
+    crate::assert_signature_eq!(UuidBytes, "ay");
+
+    let ip: IpAddr = "fe80::1".parse().unwrap();
+    let data = crate::marshal::marshal(IpAddrString(ip));
+    assert_eq!(Reader::new(&data).read(), Ok(IpAddrString(ip)));
+    let data = crate::marshal::marshal(IpAddrBytes(ip));
+    assert_eq!(Reader::new(&data).read(), Ok(IpAddrBytes(ip)));
+    crate::assert_signature_eq!(IpAddrBytes, "(yay)");
+
+    let addr: SocketAddr = "192.0.2.1:53".parse().unwrap();
+    let data = crate::marshal::marshal(SocketAddrString(addr));
+    assert_eq!(
+        Reader::new(&data).read(),
+        Ok(strings::String::from_str("192.0.2.1:53"))
+    );
+    assert_eq!(Reader::new(&data).read(), Ok(SocketAddrString(addr)));
+    let data = crate::marshal::marshal(SocketAddrBytes(addr));
+    assert_eq!(Reader::new(&data).read(), Ok(SocketAddrBytes(addr)));
+    crate::assert_signature_eq!(SocketAddrBytes, "(yayq)");
+
+    let data = crate::marshal::marshal(crate::struct_new!(AF_INET6, &[127u8, 0, 0, 1][..]));
+    assert_eq!(
+        Reader::new(&data).read::<IpAddrBytes>(),
+        Err(Error::InvalidArgs)
+    );
+}
+
+pub use dict::*;
+pub use dynamic::*;
 pub use iter::*;
+pub use source::*;
+pub use variant::*;
+mod dict;
+mod dynamic;
 mod iter;
+mod source;
+mod variant;
@@ -0,0 +1,111 @@
+use core::mem::MaybeUninit;
+
+use crate::{
+    signature::Signature,
+    types::Entry,
+    unmarshal::{ArrayIter, Error, Reader, Result, Unmarshal},
+};
+
+/// What to do when a dict array contains the same key more than once.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicateKeys {
+    /// Fail with [`Error::InvalidArgs`].
+    #[default]
+    Reject,
+    KeepFirst,
+    KeepLast,
+    /// Keep every entry in wire order.
+    KeepAll,
+}
+
+impl DuplicateKeys {
+    /// Applies the policy against `entries`, returning the entry if it still has to be appended.
+    fn apply<K: PartialEq, V>(
+        self,
+        entries: &mut [(K, V)],
+        entry: (K, V),
+    ) -> Result<Option<(K, V)>> {
+        if self == DuplicateKeys::KeepAll {
+            return Ok(Some(entry));
+        }
+        let Some(existing) = entries.iter_mut().find(|(k, _)| *k == entry.0) else {
+            return Ok(Some(entry));
+        };
+        match self {
+            DuplicateKeys::Reject => Err(Error::InvalidArgs),
+            DuplicateKeys::KeepLast => {
+                *existing = entry;
+                Ok(None)
+            }
+            DuplicateKeys::KeepFirst | DuplicateKeys::KeepAll => Ok(None),
+        }
+    }
+}
+
+impl<'a> Reader<'a> {
+    /// Reads an `a{kv}` array into `out`, returning the filled prefix.
+    ///
+    /// Fails with [`Error::InvalidArgs`] if there are more distinct entries than `out` holds.
+    pub fn read_dict_slice<'b, K, V>(
+        &mut self,
+        out: &'b mut [MaybeUninit<(K, V)>],
+        policy: DuplicateKeys,
+    ) -> Result<&'b mut [(K, V)]>
+    where
+        K: Unmarshal<'a> + Signature + PartialEq,
+        V: Unmarshal<'a> + Signature,
+    {
+        let iter: ArrayIter<'a, Entry<K, V>> = self.read()?;
+        let mut len = 0;
+        for x in iter {
+            let Entry(k, v) = x?;
+            let filled = unsafe { out[..len].assume_init_mut() };
+            if let Some(entry) = policy.apply(filled, (k, v))? {
+                out.get_mut(len).ok_or(Error::InvalidArgs)?.write(entry);
+                len += 1;
+            }
+        }
+        Ok(unsafe { out[..len].assume_init_mut() })
+    }
+
+    /// Reads an `a{kv}` array into a vector of pairs.
+    #[cfg(feature = "alloc")]
+    pub fn read_dict_vec<K, V>(&mut self, policy: DuplicateKeys) -> Result<alloc::vec::Vec<(K, V)>>
+    where
+        K: Unmarshal<'a> + Signature + PartialEq,
+        V: Unmarshal<'a> + Signature,
+    {
+        let iter: ArrayIter<'a, Entry<K, V>> = self.read()?;
+        let mut res = alloc::vec::Vec::new();
+        for x in iter {
+            let Entry(k, v) = x?;
+            if let Some(entry) = policy.apply(&mut res, (k, v))? {
+                res.push(entry);
+            }
+        }
+        Ok(res)
+    }
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_read_dict() {
+    let data = crate::marshal::marshal(&[Entry(1u8, 2u32), Entry(3, 4), Entry(1, 5)][..]);
+    let read = |policy| Reader::new(&data).read_dict_vec::<u8, u32>(policy);
+    assert_eq!(read(DuplicateKeys::Reject), Err(Error::InvalidArgs));
+    assert_eq!(
+        read(DuplicateKeys::KeepFirst),
+        Ok(alloc::vec![(1, 2), (3, 4)])
+    );
+    assert_eq!(
+        read(DuplicateKeys::KeepLast),
+        Ok(alloc::vec![(1, 5), (3, 4)])
+    );
+    assert_eq!(read(DuplicateKeys::KeepAll).map(|x| x.len()), Ok(3));
+
+    let mut buf = [MaybeUninit::uninit(); 2];
+    let res = Reader::new(&data).read_dict_slice::<u8, u32>(&mut buf, DuplicateKeys::KeepLast);
+    assert_eq!(res.as_deref(), Ok(&[(1, 5), (3, 4)][..]));
+    let res = Reader::new(&data).read_dict_slice::<u8, u32>(&mut buf, DuplicateKeys::KeepAll);
+    assert_eq!(res.err(), Some(Error::InvalidArgs));
+}
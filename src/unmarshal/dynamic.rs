@@ -0,0 +1,333 @@
+#[cfg(feature = "alloc")]
+use alloc::{boxed::Box, vec::Vec};
+
+#[cfg(feature = "extensions")]
+use crate::signature::Extension;
+#[cfg(feature = "alloc")]
+use crate::unmarshal::Error;
+use crate::{
+    strings,
+    unmarshal::{Iter, Reader, Result, Token},
+};
+
+/// Receives the values read by [`Reader::read_dynamic_with`], depth first.
+pub trait Visitor<'a> {
+    /// Every token other than [`Token::Array`], which is reported through
+    /// [`Visitor::array_open`] and [`Visitor::array_close`] around its elements instead.
+    fn token(&mut self, token: Token<'a>) -> Result<()>;
+    fn array_open(&mut self, element: &'a strings::Signature) -> Result<()> {
+        let _ = element;
+        Ok(())
+    }
+    fn array_close(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+fn walk<'a, V: Visitor<'a> + ?Sized>(iter: &mut Iter<'a>, visitor: &mut V) -> Result<()> {
+    for token in iter {
+        match token? {
+            Token::Array(array) => {
+                visitor.array_open(array.signature())?;
+                walk(&mut array.iter(), visitor)?;
+                visitor.array_close()?;
+            }
+            token => visitor.token(token)?,
+        }
+    }
+    Ok(())
+}
+
+impl<'a> Reader<'a> {
+    /// Reads values of a signature only known at runtime, e.g. that of a variant, passing them
+    /// to `visitor` without allocating.
+    pub fn read_dynamic_with<V: Visitor<'a> + ?Sized>(
+        &mut self,
+        signature: &'a strings::Signature,
+        visitor: &mut V,
+    ) -> Result<()> {
+        let mut iter = Iter::from_reader(signature, *self, &[])?;
+        walk(&mut iter, visitor)?;
+        *self = *iter.reader();
+        Ok(())
+    }
+    /// Reads a value of the single complete type `signature`, known only at runtime.
+    #[cfg(feature = "alloc")]
+    pub fn read_dynamic(&mut self, signature: &'a strings::Signature) -> Result<Value<'a>> {
+        let mut builder = Builder {
+            stack: Vec::new(),
+            values: Vec::new(),
+        };
+        self.read_dynamic_with(signature, &mut builder)?;
+        match <[_; 1]>::try_from(builder.values) {
+            Ok([value]) => Ok(value),
+            Err(_) => Err(Error::InvalidArgs),
+        }
+    }
+}
+
+/// A value decoded by [`Reader::read_dynamic`].
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value<'a> {
+    U8(u8),
+    Bool(bool),
+    I16(i16),
+    U16(u16),
+    I32(i32),
+    U32(u32),
+    I64(i64),
+    U64(u64),
+    F64(f64),
+    UnixFd(u32),
+    String(&'a strings::String),
+    Object(&'a strings::ObjectPath),
+    Signature(&'a strings::Signature),
+    /// The signature of the elements, and the elements.
+    Array(&'a strings::Signature, Vec<Value<'a>>),
+    Struct(Vec<Value<'a>>),
+    Entry(Box<(Value<'a>, Value<'a>)>),
+    /// The signature of the contained value, and the value.
+    Variant(&'a strings::Signature, Box<Value<'a>>),
+    #[cfg(feature = "extensions")]
+    Extension(&'a Extension, &'a [u8]),
+}
+
+#[cfg(feature = "alloc")]
+impl Value<'_> {
+    /// The signature of the value, derived from its contents.
+    pub fn signature(&self) -> Box<strings::Signature> {
+        let mut buf = Vec::new();
+        self.write_signature(&mut buf);
+        alloc::borrow::ToOwned::to_owned(strings::Signature::from_bytes(&buf))
+    }
+    fn write_signature(&self, buf: &mut Vec<u8>) {
+        let code = match self {
+            Value::U8(_) => b'y',
+            Value::Bool(_) => b'b',
+            Value::I16(_) => b'n',
+            Value::U16(_) => b'q',
+            Value::I32(_) => b'i',
+            Value::U32(_) => b'u',
+            Value::I64(_) => b'x',
+            Value::U64(_) => b't',
+            Value::F64(_) => b'd',
+            Value::UnixFd(_) => b'h',
+            Value::String(_) => b's',
+            Value::Object(_) => b'o',
+            Value::Signature(_) => b'g',
+            Value::Variant(..) => b'v',
+            #[cfg(feature = "extensions")]
+            Value::Extension(extension, _) => extension.code,
+            Value::Array(element, _) => {
+                buf.push(b'a');
+                return buf.extend_from_slice(element);
+            }
+            Value::Struct(values) => {
+                buf.push(b'(');
+                values.iter().for_each(|x| x.write_signature(buf));
+                return buf.push(b')');
+            }
+            Value::Entry(entry) => {
+                buf.push(b'{');
+                entry.0.write_signature(buf);
+                entry.1.write_signature(buf);
+                return buf.push(b'}');
+            }
+        };
+        buf.push(code)
+    }
+    /// Checks that the value is well-formed and of type `signature`, so that marshalling it
+    /// produces data that reads back with that signature. Array elements and variant contents
+    /// must match their declared signatures, structs must not be empty and dict entry keys must
+    /// be basic types.
+    pub fn check(&self, signature: &strings::Signature) -> Result<()> {
+        self.validate()?;
+        if *self.signature() != *signature {
+            Err(Error::InvalidArgs)?
+        }
+        Ok(())
+    }
+    fn validate(&self) -> Result<()> {
+        match self {
+            Value::Array(element, values) => {
+                if crate::signature::complete_type_len(element) != Some(element.len()) {
+                    Err(Error::InvalidArgs)?
+                }
+                values.iter().try_for_each(|x| x.check(element))
+            }
+            Value::Struct(values) if values.is_empty() => Err(Error::InvalidArgs),
+            Value::Struct(values) => values.iter().try_for_each(Value::validate),
+            Value::Entry(entry) => match entry.0 {
+                Value::Array(..) | Value::Struct(_) | Value::Entry(_) | Value::Variant(..) => {
+                    Err(Error::InvalidArgs)
+                }
+                _ => entry.1.validate(),
+            },
+            Value::Variant(signature, value) => value.check(signature),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+enum Frame<'a> {
+    Array(&'a strings::Signature),
+    Struct,
+    Entry,
+    Variant(&'a strings::Signature),
+}
+
+/// Assembles [`Value`]s, keeping the values of each open container on a stack.
+#[cfg(feature = "alloc")]
+struct Builder<'a> {
+    stack: Vec<(Frame<'a>, Vec<Value<'a>>)>,
+    values: Vec<Value<'a>>,
+}
+
+#[cfg(feature = "alloc")]
+impl<'a> Builder<'a> {
+    fn open(&mut self, frame: Frame<'a>) -> Result<()> {
+        let outer = core::mem::take(&mut self.values);
+        self.stack.push((frame, outer));
+        Ok(())
+    }
+    fn close(&mut self) -> Result<()> {
+        let (frame, outer) = self.stack.pop().ok_or(Error::InvalidArgs)?;
+        let mut values = core::mem::replace(&mut self.values, outer);
+        let value = match frame {
+            Frame::Array(signature) => Value::Array(signature, values),
+            Frame::Struct => Value::Struct(values),
+            Frame::Entry => {
+                let (Some(value), Some(key), None) = (values.pop(), values.pop(), values.pop())
+                else {
+                    Err(Error::InvalidArgs)?
+                };
+                Value::Entry(Box::new((key, value)))
+            }
+            Frame::Variant(signature) => {
+                let value = values.pop().ok_or(Error::InvalidArgs)?;
+                Value::Variant(signature, Box::new(value))
+            }
+        };
+        self.values.push(value);
+        Ok(())
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'a> Visitor<'a> for Builder<'a> {
+    fn token(&mut self, token: Token<'a>) -> Result<()> {
+        let value = match token {
+            Token::U8(x) => Value::U8(x),
+            Token::Bool(x) => Value::Bool(x),
+            Token::I16(x) => Value::I16(x),
+            Token::U16(x) => Value::U16(x),
+            Token::I32(x) => Value::I32(x),
+            Token::U32(x) => Value::U32(x),
+            Token::I64(x) => Value::I64(x),
+            Token::U64(x) => Value::U64(x),
+            Token::F64(x) => Value::F64(x),
+            Token::UnixFd(x) => Value::UnixFd(x),
+            Token::String(x) => Value::String(x),
+            Token::Object(x) => Value::Object(x),
+            Token::Signature(x) => Value::Signature(x),
+            #[cfg(feature = "extensions")]
+            Token::Extension(extension, bytes) => Value::Extension(extension, bytes),
+            Token::StructOpen => return self.open(Frame::Struct),
+            Token::EntryOpen => return self.open(Frame::Entry),
+            Token::VariantOpen(signature) => return self.open(Frame::Variant(signature)),
+            Token::StructClose | Token::EntryClose | Token::VariantClose => return self.close(),
+            Token::Array(_) => unreachable!("arrays are reported through array_open"),
+        };
+        self.values.push(value);
+        Ok(())
+    }
+    fn array_open(&mut self, element: &'a strings::Signature) -> Result<()> {
+        self.open(Frame::Array(element))
+    }
+    fn array_close(&mut self) -> Result<()> {
+        self.close()
+    }
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_read_dynamic() {
+    let data = crate::marshal::marshal(crate::multiple_new!(
+        7u8,
+        crate::struct_new!(
+            &[crate::Entry("a", crate::Variant(2u32))][..],
+            &[0u64; 0][..]
+        ),
+    ));
+    let mut r = Reader::new(&data);
+    assert_eq!(r.read::<u8>(), Ok(7));
+    let sig = strings::Signature::from_str;
+    let value = r.read_dynamic(sig("(a{sv}at)")).unwrap();
+    assert!(r.remaining().is_empty());
+    assert_eq!(
+        value,
+        Value::Struct(alloc::vec![
+            Value::Array(
+                sig("{sv}"),
+                alloc::vec![Value::Entry(Box::new((
+                    Value::String(strings::String::from_str("a")),
+                    Value::Variant(sig("u"), Box::new(Value::U32(2))),
+                )))]
+            ),
+            Value::Array(sig("t"), alloc::vec![]),
+        ])
+    );
+
+    struct Count(usize);
+    impl Visitor<'_> for Count {
+        fn token(&mut self, _: Token<'_>) -> Result<()> {
+            self.0 += 1;
+            Ok(())
+        }
+    }
+    let mut count = Count(0);
+    Reader::new(&data)
+        .read_dynamic_with(sig("y(a{sv}at)"), &mut count)
+        .unwrap();
+    assert_eq!(count.0, 9);
+    assert_eq!(
+        Reader::new(&data).read_dynamic(sig("yy")),
+        Err(Error::InvalidArgs)
+    );
+}
+
+#[test]
+#[cfg(feature = "alloc")]
+fn test_marshal_value() {
+    let sig = strings::Signature::from_str;
+    let value = Value::Struct(alloc::vec![
+        Value::U8(1),
+        Value::Array(
+            sig("{sv}"),
+            alloc::vec![Value::Entry(Box::new((
+                Value::String(strings::String::from_str("a")),
+                Value::Variant(sig("t"), Box::new(Value::U64(2))),
+            )))]
+        ),
+    ]);
+    assert_eq!(*value.signature(), *sig("(ya{sv})"));
+    assert_eq!(value.check(sig("(ya{sv})")), Ok(()));
+    assert_eq!(value.check(sig("(ya{sv}y)")), Err(Error::InvalidArgs));
+
+    let data = crate::marshal::marshal(&value);
+    assert_eq!(
+        *data,
+        *crate::marshal::marshal(crate::struct_new!(
+            1u8,
+            &[crate::Entry("a", crate::Variant(2u64))][..]
+        ))
+    );
+    assert_eq!(Reader::new(&data).read_dynamic(sig("(ya{sv})")), Ok(value));
+
+    let mismatched = Value::Array(sig("u"), alloc::vec![Value::U8(1)]);
+    assert_eq!(mismatched.check(sig("au")), Err(Error::InvalidArgs));
+    let variant = Value::Variant(sig("s"), Box::new(Value::U8(1)));
+    assert_eq!(variant.check(sig("v")), Err(Error::InvalidArgs));
+}
@@ -1,6 +1,18 @@
-use core::result;
+use core::{
+    fmt, result,
+    sync::atomic::{AtomicUsize, Ordering},
+};
 
-use crate::unmarshal::Error;
+use arrayvec::ArrayVec;
+
+#[cfg(feature = "extensions")]
+use crate::signature::find_extension;
+use crate::{
+    metrics::Metrics,
+    signature::{Extension, MAX_NESTING_DEPTH, code_alignment_with, complete_type_len_with},
+    strings,
+    unmarshal::{Error, Reader},
+};
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub(super) enum IterErr {
@@ -25,502 +37,501 @@ pub(super) fn flatten<T>(x: IterResult<T>) -> Option<Result<T>> {
     }
 }
 
-// #[derive(Debug)]
-// pub enum Token<'a> {
-//     U8(u8),
-//     Bool(bool),
-//     I16(i16),
-//     U16(u16),
-//     I32(i32),
-//     U32(u32),
-//     I64(i64),
-//     U64(u64),
-//     F64(f64),
-//     String(&'a strings::String),
-//     Object(&'a strings::ObjectPath),
-//     Signature(&'a strings::Signature),
-//     Array {
-//         signature: &'a [u8],
-//         /// begin of element, after length and first align padding
-//         data: &'a [u8],
-//     },
-//     VariantOpen,
-//     VariantClose,
-//     StructOpen,
-//     StructClose,
-//     EntryOpen,
-//     EntryClose,
-// }
-//
-// macro_rules! define_token_kind {
-//     ($($name:ident = $value:literal),* $(,)?) => {
-//         #[allow(dead_code)]
-//         #[derive(Debug, Clone, Copy, PartialEq)]
-//         #[repr(u8)]
-//         enum TokenKind {
-//             $($name = $value,)*
-//         }
-//
-//         impl TokenKind {
-//             #[allow(dead_code)]
-//             const fn validate(byte: u8) -> Result<Self> {
-//                 const MASK: u128 = $(1 << $value)|*;
-//                 if MASK & (1 << byte) != 0 {
-//                     Ok(unsafe { mem::transmute(byte) })
-//                 } else {
-//                     Err(Error::SignatureInvalidChar)
-//                 }
-//             }
-//         }
-//     };
-// }
-//
-// define_token_kind! {
-//     U8 = b'y',
-//     Bool = b'b',
-//     I16 = b'n',
-//     U16 = b'q',
-//     I32 = b'i',
-//     U32 = b'u',
-//     I64 = b'x',
-//     U64 = b't',
-//     F64 = b'd',
-//     String = b's',
-//     Object = b'o',
-//     Signature = b'g',
-//     Variant = b'v',
-//     Array = b'a',
-//     StructOpen = b'(',
-//     StructClose = b')',
-//     EntryOpen = b'{',
-//     EntryClose = b'}',
-// }
-//
-// impl TokenKind {
-//     #[allow(dead_code)]
-//     fn alignment(self) -> usize {
-//         match self {
-//             Self::U8 | Self::Signature | Self::Variant => 1,
-//             Self::I16 | Self::U16 => 2,
-//             Self::I32 | Self::U32 | Self::Bool | Self::String | Self::Object | Self::Array => 4,
-//             Self::I64 | Self::U64 | Self::F64 | Self::StructOpen | Self::EntryOpen => 8,
-//             Self::StructClose | Self::EntryClose => unreachable!(),
-//         }
-//     }
-// }
-//
-// enum Nesting<'a> {
-//     Array(*const u8),
-//     Struct,
-//     Entry(u8),
-//     Variant(SignatureIter<'a>),
-// }
-//
-// const MAX_NESTING: usize = 32;
-//
-// struct SignatureIter<'a> {
-//     ptr: *const u8,
-//     end: *const u8,
-//     marker: PhantomData<&'a [u8]>,
-// }
-//
-// #[derive(PartialEq, Debug)]
-// struct SignatureToken<'a> {
-//     kind: TokenKind,
-//     payload: &'a [u8],
-// }
-//
-// impl<'a> SignatureIter<'a> {
-//     fn next_byte(&mut self, stack: &mut NestingStack) -> IterResult<&'a u8> {
-//         if self.ptr == self.end {
-//             match stack.last() {
-//                 None | Some(Nesting::Variant(_)) => Err(IterErr::EndOfIteration)?,
-//                 _ => Err(Error::NestingMismatched)?,
-//             }
-//         }
-//         let byte = unsafe { &*self.ptr };
-//         self.ptr = unsafe { self.ptr.add(1) };
-//         Ok(byte)
-//     }
-//     fn close_array(
-//         &mut self,
-//         ptr: *const u8,
-//         stack: &mut NestingStack,
-//         array_depth: &mut usize,
-//     ) -> IterResult<SignatureToken<'a>> {
-//         match stack.last_mut() {
-//             Some(&mut Nesting::Array(ptr)) => {
-//                 stack.pop();
-//                 *array_depth -= 1;
-//                 return self.close_array(ptr, stack, array_depth);
-//             }
-//             Some(Nesting::Struct) => {
-//                 if *array_depth != 0 {
-//                     return self.next(stack, array_depth);
-//                 }
-//             }
-//             Some(Nesting::Entry(x)) => {
-//                 if *x == 2 {
-//                     Err(Error::InvalidEntrySize)?
-//                 }
-//                 *x += 1;
-//                 if *array_depth != 0 {
-//                     return self.next(stack, array_depth);
-//                 }
-//             }
-//             None | Some(Nesting::Variant(_)) => {}
-//         }
-//         Ok(SignatureToken {
-//             kind: TokenKind::Array,
-//             payload: unsafe { slice::from_ptr_range(ptr.add(1)..self.ptr) },
-//         })
-//     }
-//     fn at_value(
-//         &mut self,
-//         byte: u8,
-//         stack: &mut NestingStack,
-//         array_depth: &mut usize,
-//     ) -> IterResult<SignatureToken<'a>> {
-//         if let Some(x) = stack.last_mut() {
-//             match x {
-//                 &mut Nesting::Array(ptr) => {
-//                     stack.pop();
-//                     *array_depth -= 1;
-//                     return self.close_array(ptr, stack, array_depth);
-//                 }
-//                 Nesting::Entry(depth) => {
-//                     if *depth != 2 {
-//                         *depth += 1;
-//                     } else {
-//                         Err(Error::InvalidEntrySize)?
-//                     }
-//                 }
-//                 _ => {}
-//             }
-//         };
-//         if *array_depth == 0 {
-//             let kind = unsafe { mem::transmute(byte) };
-//             Ok(SignatureToken { kind, payload: &[] })
-//         } else {
-//             return self.next(stack, array_depth);
-//         }
-//     }
-//     fn next(
-//         &mut self,
-//         stack: &mut NestingStack,
-//         array_depth: &mut usize,
-//     ) -> IterResult<SignatureToken<'a>> {
-//         let byte = self.next_byte(stack)?;
-//         match byte {
-//             b'y' | b'b' | b'n' | b'q' | b'i' | b'u' | b'x' | b't' | b'd' | b's' | b'o' | b'g'
-//             | b'v' => self.at_value(*byte, stack, array_depth),
-//             b'a' => {
-//                 *array_depth += 1;
-//                 stack
-//                     .try_push(Nesting::Array(byte))
-//                     .map_err(|_| Error::NestingDepthExceeded)?;
-//                 self.next(stack, array_depth)
-//             }
-//             b'{' => {
-//                 stack
-//                     .try_push(Nesting::Entry(0))
-//                     .map_err(|_| Error::NestingDepthExceeded)?;
-//                 if *array_depth != 0 {
-//                     return self.next(stack, array_depth);
-//                 }
-//                 Ok(SignatureToken {
-//                     kind: TokenKind::EntryOpen,
-//                     payload: &[],
-//                 })
-//             }
-//             b'(' => {
-//                 stack
-//                     .try_push(Nesting::Struct)
-//                     .map_err(|_| Error::NestingDepthExceeded)?;
-//                 if *array_depth != 0 {
-//                     return self.next(stack, array_depth);
-//                 }
-//                 Ok(SignatureToken {
-//                     kind: TokenKind::StructOpen,
-//                     payload: &[],
-//                 })
-//             }
-//             b'}' => match stack.pop() {
-//                 Some(Nesting::Entry(2)) => self.at_value(*byte, stack, array_depth),
-//                 Some(Nesting::Entry(_)) => Err(Error::InvalidEntrySize)?,
-//                 _ => Err(Error::NestingMismatched)?,
-//             },
-//             b')' => match stack.pop() {
-//                 Some(Nesting::Struct) => self.at_value(*byte, stack, array_depth),
-//                 _ => Err(Error::NestingMismatched)?,
-//             },
-//             _ => Err(Error::SignatureInvalidChar)?,
-//         }
-//     }
-//     fn new(data: &'a [u8]) -> Self {
-//         Self {
-//             ptr: data.as_ptr(),
-//             end: unsafe { data.as_ptr().add(data.len()) },
-//             marker: PhantomData,
-//         }
-//     }
-// }
-//
-// type NestingStack<'a> = ArrayVec<Nesting<'a>, MAX_NESTING>;
-//
-// pub struct Iter<'a> {
-//     reader: Reader<'a>,
-//     signature: SignatureIter<'a>,
-//     nesting_stack: NestingStack<'a>,
-//     array_depth: usize,
-// }
-//
-// impl<'a> Iter<'a> {
-//     pub fn new(signature: &'a [u8], data: &'a [u8]) -> Self {
-//         Self {
-//             reader: Reader::new(data),
-//             signature: SignatureIter::new(signature),
-//             nesting_stack: ArrayVec::new(),
-//             array_depth: 0,
-//         }
-//     }
-//     fn iter(&mut self) -> IterResult<Token<'a>> {
-//         let SignatureToken { kind, payload } = {
-//             if let Ok(x) = self
-//                 .signature
-//                 .next(&mut self.nesting_stack, &mut self.array_depth)
-//             {
-//                 x
-//             } else {
-//                 self.signature = match self.nesting_stack.pop().ok_or(IterErr::EndOfIteration)? {
-//                     Nesting::Variant(x) => x,
-//                     _ => unreachable!(),
-//                 };
-//                 return Ok(Token::VariantClose);
-//             }
-//         };
-//         debug_assert_eq!(self.array_depth, 0);
-//         Ok(match kind {
-//             TokenKind::U8 => Token::U8(self.reader.read::<u8>()?),
-//             TokenKind::Bool => Token::Bool(self.reader.read::<bool>()?),
-//             TokenKind::I16 => Token::I16(self.reader.read::<i16>()?),
-//             TokenKind::U16 => Token::U16(self.reader.read::<u16>()?),
-//             TokenKind::I32 => Token::I32(self.reader.read::<i32>()?),
-//             TokenKind::U32 => Token::U32(self.reader.read::<u32>()?),
-//             TokenKind::I64 => Token::I64(self.reader.read::<i64>()?),
-//             TokenKind::U64 => Token::U64(self.reader.read::<u64>()?),
-//             TokenKind::F64 => Token::F64(self.reader.read::<f64>()?),
-//             TokenKind::String => Token::String(self.reader.read::<&strings::String>()?),
-//             TokenKind::Object => Token::Object(self.reader.read::<&strings::ObjectPath>()?),
-//             TokenKind::Signature => Token::Signature(self.reader.read::<&strings::Signature>()?),
-//             TokenKind::Array => {
-//                 let len = self.reader.read::<u32>()? as usize;
-//                 if let TokenKind::U64
-//                 | TokenKind::I64
-//                 | TokenKind::F64
-//                 | TokenKind::StructOpen
-//                 | TokenKind::EntryOpen = unsafe { mem::transmute(*payload.get_unchecked(0)) }
-//                 {
-//                     self.reader.seek(4)?; // align to 8
-//                 };
-//                 Token::Array {
-//                     signature: payload,
-//                     data: self.reader.read_bytes(len)?,
-//                 }
-//             }
-//             TokenKind::StructOpen => {
-//                 self.reader.align_to(8)?;
-//                 Token::StructOpen
-//             }
-//             TokenKind::EntryOpen => {
-//                 self.reader.align_to(8)?;
-//                 Token::EntryOpen
-//             }
-//             TokenKind::StructClose => Token::StructClose,
-//             TokenKind::EntryClose => Token::EntryClose,
-//             TokenKind::Variant => {
-//                 let sig = self.reader.read::<&strings::Signature>()?;
-//                 let mut sig = SignatureIter::new(sig);
-//                 mem::swap(&mut sig, &mut self.signature);
-//                 self.nesting_stack
-//                     .try_push(Nesting::Variant(sig))
-//                     .map_err(|_| Error::NestingDepthExceeded)?;
-//                 Token::VariantOpen
-//             }
-//         })
-//     }
-// }
-//
-// impl<'a> Iterator for Iter<'a> {
-//     type Item = Result<Token<'a>>;
-//
-//     fn next(&mut self) -> Option<Self::Item> {
-//         flatten(self.iter())
-//     }
-// }
-//
-// // struct ArrayIter<'a> {
-// //     signature: &'a [u8],
-// //     data: &'a [u8],
-// // }
-// //
-// // impl<'a> Iterator for ArrayIter<'a> {
-// //     type Item = Option<Iter<'a>>;
-// //     fn next(&mut self) -> Option<Self::Item> {
-// //         let
-// //     }
-// // }
-//
-// #[test]
-// fn test_iter() {
-//     let data = [
-//         34, 0, 0, 0, 0, 0, 0, 0, 1, 1, 121, 0, 1, 0, 0, 0, 2, 1, 115, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-//         0, 0, 0, 3, 4, 40, 121, 121, 41, 0, 0, 2, 4,
-//     ];
-//     let mut it = Iter::new(b"a{yv}", &data);
-//     while let Some(x) = it.next() {
-//         match x.unwrap() {
-//             Token::Array {
-//                 signature,
-//                 mut data,
-//             } => {
-//                 let mut padding = 0;
-//                 let sig: TokenKind = unsafe { mem::transmute(*signature.get_unchecked(0)) };
-//                 let align = sig.alignment();
-//                 loop {
-//                     data = &data[padding..];
-//                     let mut it = Iter::new(signature, data);
-//                     dbg!(data);
-//                     while let Some(x) = it.next() {
-//                         dbg!(x.unwrap());
-//                     }
-//                     data = it.reader.remaining();
-//                     padding = crate::align_padding(it.reader.count, align);
-//                     if data.is_empty() {
-//                         break;
-//                     }
-//                 }
-//             }
-//             x => {
-//                 dbg!(x);
-//             }
-//         }
-//     }
-//     // panic!()
-// }
-//
-// #[test]
-// fn test_signatre_parse() {
-//     let mut iter = SignatureIter::new(b"aii");
-//     let mut stack = ArrayVec::new();
-//     let mut depth = 0;
-//     assert_eq!(
-//         iter.next(&mut stack, &mut depth),
-//         Ok(SignatureToken {
-//             kind: TokenKind::Array,
-//             payload: b"i"
-//         })
-//     );
-//     assert_eq!(
-//         iter.next(&mut stack, &mut depth),
-//         Ok(SignatureToken {
-//             kind: TokenKind::I32,
-//             payload: &[],
-//         })
-//     );
-//     assert_eq!(
-//         iter.next(&mut stack, &mut depth),
-//         Err(IterErr::EndOfIteration)
-//     );
-//     assert!(stack.is_empty());
-//     assert_eq!(depth, 0);
-//
-//     let mut iter = SignatureIter::new(b"((ai))");
-//     assert_eq!(
-//         iter.next(&mut stack, &mut depth),
-//         Ok(SignatureToken {
-//             kind: TokenKind::StructOpen,
-//             payload: &[],
-//         })
-//     );
-//     assert_eq!(
-//         iter.next(&mut stack, &mut depth),
-//         Ok(SignatureToken {
-//             kind: TokenKind::StructOpen,
-//             payload: &[],
-//         })
-//     );
-//     assert_eq!(
-//         iter.next(&mut stack, &mut depth),
-//         Ok(SignatureToken {
-//             kind: TokenKind::Array,
-//             payload: b"i"
-//         })
-//     );
-//     assert_eq!(
-//         iter.next(&mut stack, &mut depth),
-//         Ok(SignatureToken {
-//             kind: TokenKind::StructClose,
-//             payload: &[],
-//         })
-//     );
-//     assert_eq!(
-//         iter.next(&mut stack, &mut depth),
-//         Ok(SignatureToken {
-//             kind: TokenKind::StructClose,
-//             payload: &[],
-//         })
-//     );
-//     assert_eq!(
-//         iter.next(&mut stack, &mut depth),
-//         Err(IterErr::EndOfIteration)
-//     );
-//
-//     let mut iter = SignatureIter::new(b"a(aii)");
-//     assert_eq!(
-//         iter.next(&mut stack, &mut depth),
-//         Ok(SignatureToken {
-//             kind: TokenKind::Array,
-//             payload: b"(aii)"
-//         })
-//     );
-//     assert_eq!(
-//         iter.next(&mut stack, &mut depth),
-//         Err(IterErr::EndOfIteration)
-//     );
-//     assert!(stack.is_empty());
-//     assert_eq!(depth, 0);
-//
-//     let mut iter = SignatureIter::new(b"aai");
-//     assert_eq!(
-//         iter.next(&mut stack, &mut depth),
-//         Ok(SignatureToken {
-//             kind: TokenKind::Array,
-//             payload: b"ai"
-//         })
-//     );
-//     assert_eq!(
-//         iter.next(&mut stack, &mut depth),
-//         Err(IterErr::EndOfIteration)
-//     );
-//     assert!(stack.is_empty());
-//     assert_eq!(depth, 0);
-//
-//     let mut iter = SignatureIter::new(b"(a{ai(ai)}ai)");
-//     loop {
-//         match iter.next(&mut stack, &mut depth) {
-//             Ok(x) => {
-//                 dbg!(x);
-//             }
-//             Err(e) => {
-//                 dbg!(&e);
-//                 if e != IterErr::EndOfIteration {
-//                     panic!()
-//                 }
-//                 break;
-//             }
-//         }
-//     }
-//     assert!(stack.is_empty());
-//     assert_eq!(depth, 0);
-// }
+/// One value, or one container boundary, of a body walked by [`Iter`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Token<'a> {
+    U8(u8),
+    Bool(bool),
+    I16(i16),
+    U16(u16),
+    I32(i32),
+    U32(u32),
+    I64(i64),
+    U64(u64),
+    F64(f64),
+    UnixFd(u32),
+    String(&'a strings::String),
+    Object(&'a strings::ObjectPath),
+    Signature(&'a strings::Signature),
+    Array(ArrayData<'a>),
+    VariantOpen(&'a strings::Signature),
+    VariantClose,
+    StructOpen,
+    StructClose,
+    EntryOpen,
+    EntryClose,
+    /// A value of a registered [`Extension`] type.
+    #[cfg(feature = "extensions")]
+    Extension(&'a Extension, &'a [u8]),
+}
+
+/// The undecoded elements of an array; walk them with [`ArrayData::iter`].
+#[derive(Clone, Copy)]
+pub struct ArrayData<'a> {
+    signature: &'a strings::Signature,
+    reader: Reader<'a>,
+    fuel: Option<&'a AtomicUsize>,
+    extensions: &'a [Extension],
+    /// The containers the elements are nested in, this array included.
+    depth: usize,
+}
+
+impl<'a> ArrayData<'a> {
+    /// The signature of a single element.
+    pub const fn signature(&self) -> &'a strings::Signature {
+        self.signature
+    }
+    /// The alignment of a single element.
+    pub fn alignment(&self) -> usize {
+        code_alignment_with(self.signature[0], self.extensions).unwrap()
+    }
+    /// The encoded elements, starting after the length and its alignment padding.
+    pub fn bytes(&self) -> &'a [u8] {
+        self.reader.remaining()
+    }
+    /// An upper bound on the number of elements, exact when they are of a fixed-size basic
+    /// type: every element but the last takes at least its alignment in bytes.
+    pub fn max_len(&self) -> usize {
+        self.bytes().len().div_ceil(self.alignment())
+    }
+    /// Formats the decoded tokens of the elements, descending into nested arrays. Nothing is
+    /// decoded until the result is formatted, and decoding errors are shown in place.
+    pub fn pretty(&self) -> impl fmt::Debug + use<'a> {
+        let array = *self;
+        fmt::from_fn(move |f| {
+            let mut list = f.debug_list();
+            for token in array.iter() {
+                match token {
+                    Ok(Token::Array(array)) => list.entry(&array.pretty()),
+                    Ok(token) => list.entry(&token),
+                    Err(e) => return list.entry(&e).finish(),
+                };
+            }
+            list.finish()
+        })
+    }
+    /// Tokens of every element in turn, drawing on the same fuel as the iterator that yielded
+    /// the array.
+    pub fn iter(&self) -> Iter<'a> {
+        Iter {
+            reader: self.reader,
+            signature: &[],
+            repeat: Some(self.signature),
+            variants: ArrayVec::new(),
+            fuel: self.fuel,
+            extensions: self.extensions,
+            depth: self.depth,
+        }
+    }
+}
+
+impl PartialEq for ArrayData<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.signature == other.signature && self.bytes() == other.bytes()
+    }
+}
+
+/// Number of leading bytes shown by the [`Debug`](fmt::Debug) output of [`ArrayData`].
+const DEBUG_PREVIEW: usize = 16;
+
+impl fmt::Debug for ArrayData<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bytes = self.bytes();
+        let preview = fmt::from_fn(|f| {
+            for (i, x) in bytes.iter().take(DEBUG_PREVIEW).enumerate() {
+                write!(f, "{}{x:02x}", if i == 0 { "" } else { " " })?;
+            }
+            if let Some(more) = bytes.len().checked_sub(DEBUG_PREVIEW).filter(|&x| x > 0) {
+                write!(f, " .. (+{more})")?;
+            }
+            Ok(())
+        });
+        f.debug_struct("ArrayData")
+            .field("signature", &self.signature)
+            .field("max_len", &self.max_len())
+            .field("bytes", &preview)
+            .finish()
+    }
+}
+
+/// The depth inside one more container than `depth`, failing past [`MAX_NESTING_DEPTH`].
+fn nested(depth: usize) -> Result<usize> {
+    if depth >= MAX_NESTING_DEPTH {
+        Err(Error::NestingDepthExceeded)?
+    }
+    Ok(depth + 1)
+}
+
+/// Checks that `signature` is a sequence of complete types.
+fn validate_types(mut signature: &[u8], extensions: &[Extension]) -> Result<()> {
+    while !signature.is_empty() {
+        let len = complete_type_len_with(signature, extensions).ok_or(Error::InvalidArgs)?;
+        signature = &signature[len..];
+    }
+    Ok(())
+}
+
+/// Walks a body of a runtime signature, yielding a [`Token`] per value.
+///
+/// Arrays are yielded whole as [`Token::Array`]; use [`ArrayData::iter`] to descend into them.
+#[derive(Clone)]
+pub struct Iter<'a> {
+    reader: Reader<'a>,
+    signature: &'a [u8],
+    /// Element signature to restart from while data remains, when walking array elements.
+    repeat: Option<&'a strings::Signature>,
+    /// Signatures to resume once the current variant is done.
+    variants: ArrayVec<&'a [u8], MAX_NESTING_DEPTH>,
+    /// Tokens left to yield, shared with the iterators of nested arrays.
+    fuel: Option<&'a AtomicUsize>,
+    extensions: &'a [Extension],
+    /// The containers the next value is nested in: arrays, structs, dict entries and variants,
+    /// including those of the iterators this one descends from.
+    depth: usize,
+}
+
+impl<'a> Iter<'a> {
+    pub fn new(signature: &'a strings::Signature, data: &'a [u8]) -> Result<Self> {
+        Self::new_with(signature, data, &[])
+    }
+    /// Like [`Iter::new`], but also accepts the type codes of `extensions`, in this iterator
+    /// and the iterators of its arrays. Fails with [`Error::InvalidArgs`] if an extension has
+    /// a size of zero or an alignment that is not a power of two.
+    #[cfg(feature = "extensions")]
+    pub fn with_extensions(
+        signature: &'a strings::Signature,
+        data: &'a [u8],
+        extensions: &'a [Extension],
+    ) -> Result<Self> {
+        // Arrays of zero-sized values never advance, and alignment needs a power of two.
+        if extensions
+            .iter()
+            .any(|x| x.size == 0 || !x.alignment.is_power_of_two())
+        {
+            Err(Error::InvalidArgs)?
+        }
+        Self::new_with(signature, data, extensions)
+    }
+    fn new_with(
+        signature: &'a strings::Signature,
+        data: &'a [u8],
+        extensions: &'a [Extension],
+    ) -> Result<Self> {
+        Self::from_reader(signature, Reader::new(data), extensions)
+    }
+    /// Like [`Iter::new`], but walks from the position of `reader`, keeping its alignment and
+    /// byte order.
+    pub fn with_reader(signature: &'a strings::Signature, reader: Reader<'a>) -> Result<Self> {
+        Self::from_reader(signature, reader, &[])
+    }
+    /// Walks from the position of `reader`, keeping its alignment.
+    pub(super) fn from_reader(
+        signature: &'a strings::Signature,
+        reader: Reader<'a>,
+        extensions: &'a [Extension],
+    ) -> Result<Self> {
+        validate_types(signature, extensions)?;
+        Ok(Self {
+            reader,
+            signature,
+            repeat: None,
+            variants: ArrayVec::new(),
+            fuel: None,
+            extensions,
+            depth: 0,
+        })
+    }
+    /// Limits the number of tokens yielded to what is left in `fuel`, counting the tokens of
+    /// arrays walked through [`ArrayData::iter`] as well. Once it runs out, iteration fails
+    /// with [`Error::BudgetExceeded`].
+    pub fn with_fuel(self, fuel: &'a AtomicUsize) -> Self {
+        Self {
+            fuel: Some(fuel),
+            ..self
+        }
+    }
+    /// Reports padding, variant nesting and errors to `metrics`, in this iterator and the
+    /// iterators of its arrays.
+    pub fn with_metrics(mut self, metrics: &'a dyn Metrics) -> Self {
+        self.reader = self.reader.with_metrics(metrics);
+        self
+    }
+    /// The reader positioned after the last token yielded.
+    pub const fn reader(&self) -> &Reader<'a> {
+        &self.reader
+    }
+    /// Decodes every remaining token, descending into arrays so that their elements are
+    /// checked as well, and leaves the reader after the last one.
+    pub fn validate(&mut self) -> Result<()> {
+        for token in self {
+            if let Token::Array(array) = token? {
+                array.iter().validate()?;
+            }
+        }
+        Ok(())
+    }
+    fn try_next(&mut self) -> IterResult<Token<'a>> {
+        let token = self.next_token().inspect_err(|e| {
+            if let (IterErr::Error(e), Some(metrics)) = (e, self.reader.metrics()) {
+                metrics.error(e);
+            }
+        })?;
+        if let Some(fuel) = self.fuel {
+            fuel.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |x| x.checked_sub(1))
+                .map_err(|_| Error::BudgetExceeded)?;
+        }
+        Ok(token)
+    }
+    fn next_token(&mut self) -> IterResult<Token<'a>> {
+        let Some((&code, rest)) = self.signature.split_first() else {
+            if let Some(outer) = self.variants.pop() {
+                self.signature = outer;
+                self.depth -= 1;
+                return Ok(Token::VariantClose);
+            }
+            match self.repeat {
+                Some(element) if !self.reader.remaining().is_empty() => {
+                    self.reader
+                        .align_to(code_alignment_with(element[0], self.extensions).unwrap())?;
+                    self.signature = element;
+                    return self.next_token();
+                }
+                _ => Err(IterErr::EndOfIteration)?,
+            }
+        };
+        self.signature = rest;
+        let r = &mut self.reader;
+        Ok(match code {
+            b'y' => Token::U8(r.read()?),
+            b'b' => Token::Bool(r.read()?),
+            b'n' => Token::I16(r.read()?),
+            b'q' => Token::U16(r.read()?),
+            b'i' => Token::I32(r.read()?),
+            b'u' => Token::U32(r.read()?),
+            b'x' => Token::I64(r.read()?),
+            b't' => Token::U64(r.read()?),
+            b'd' => Token::F64(r.read()?),
+            b'h' => Token::UnixFd(r.read()?),
+            b's' => Token::String(r.read()?),
+            b'o' => Token::Object(r.read()?),
+            b'g' => Token::Signature(r.read()?),
+            b'a' => {
+                let depth = nested(self.depth)?;
+                let element_len = complete_type_len_with(rest, self.extensions).unwrap();
+                let (element, rest) = rest.split_at(element_len);
+                self.signature = rest;
+                let len: u32 = r.read()?;
+                r.align_to(code_alignment_with(element[0], self.extensions).unwrap())?;
+                Token::Array(ArrayData {
+                    signature: strings::Signature::from_bytes(element),
+                    reader: r.seek(len as _)?,
+                    fuel: self.fuel,
+                    extensions: self.extensions,
+                    depth,
+                })
+            }
+            b'(' => {
+                self.depth = nested(self.depth)?;
+                r.align_to(8)?;
+                Token::StructOpen
+            }
+            b'{' => {
+                self.depth = nested(self.depth)?;
+                r.align_to(8)?;
+                Token::EntryOpen
+            }
+            b')' => {
+                self.depth -= 1;
+                Token::StructClose
+            }
+            b'}' => {
+                self.depth -= 1;
+                Token::EntryClose
+            }
+            b'v' => {
+                self.depth = nested(self.depth)?;
+                let signature: &strings::Signature = r.read()?;
+                if complete_type_len_with(signature, self.extensions) != Some(signature.len()) {
+                    Err(Error::InvalidArgs)?
+                }
+                // The depth limit keeps the open variants within the capacity.
+                self.variants.push(rest);
+                if let Some(metrics) = r.metrics() {
+                    metrics.nesting(self.variants.len());
+                }
+                self.signature = signature;
+                Token::VariantOpen(signature)
+            }
+            _ => {
+                #[cfg(feature = "extensions")]
+                if let Some(extension) = find_extension(code, self.extensions) {
+                    r.align_to(extension.alignment)?;
+                    return Ok(Token::Extension(extension, r.read_bytes(extension.size)?));
+                }
+                Err(Error::InvalidArgs)?
+            }
+        })
+    }
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = Result<Token<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        flatten(self.try_next())
+    }
+}
+
+#[test]
+fn test_iter() {
+    let value = crate::struct_new!(
+        1u8,
+        &[crate::Entry("a", crate::Variant(2u32))][..],
+        crate::Variant(crate::struct_new!(3i64)),
+    );
+    let data = crate::marshal::marshal(value);
+    let mut it = Iter::new(strings::Signature::from_str("(ya{sv}v)"), &data)
+        .unwrap()
+        .map(|x| x.unwrap());
+    assert_eq!(it.next(), Some(Token::StructOpen));
+    assert_eq!(it.next(), Some(Token::U8(1)));
+    let Some(Token::Array(array)) = it.next() else {
+        panic!()
+    };
+    assert_eq!(array.signature(), strings::Signature::from_str("{sv}"));
+    let sig = strings::Signature::from_str;
+    assert_eq!(it.next(), Some(Token::VariantOpen(sig("(x)"))));
+    assert_eq!(it.next(), Some(Token::StructOpen));
+    assert_eq!(it.next(), Some(Token::I64(3)));
+    assert_eq!(it.next(), Some(Token::StructClose));
+    assert_eq!(it.next(), Some(Token::VariantClose));
+    assert_eq!(it.next(), Some(Token::StructClose));
+    assert_eq!(it.next(), None);
+
+    let mut it = array.iter().map(|x| x.unwrap());
+    assert_eq!(it.next(), Some(Token::EntryOpen));
+    assert_eq!(
+        it.next(),
+        Some(Token::String(strings::String::from_str("a")))
+    );
+    assert_eq!(it.next(), Some(Token::VariantOpen(sig("u"))));
+    assert_eq!(it.next(), Some(Token::U32(2)));
+    assert_eq!(it.next(), Some(Token::VariantClose));
+    assert_eq!(it.next(), Some(Token::EntryClose));
+    assert_eq!(it.next(), None);
+}
+
+#[test]
+fn test_array_debug() {
+    let data = crate::marshal::marshal(&[&[1u16, 2][..], &[0x1234u16; 10][..]][..]);
+    let mut it = Iter::new(strings::Signature::from_str("aaq"), &data).unwrap();
+    let Some(Ok(Token::Array(array))) = it.next() else {
+        panic!()
+    };
+    assert_eq!(array.max_len(), 8);
+    assert_eq!(
+        format!("{array:?}"),
+        "ArrayData { signature: \"aq\", max_len: 8, bytes: 04 00 00 00 01 00 02 00 \
+         14 00 00 00 34 12 34 12 .. (+16) }"
+    );
+    let inner = array.iter().next().unwrap().unwrap();
+    let Token::Array(inner) = inner else { panic!() };
+    assert_eq!(inner.max_len(), 2);
+    assert_eq!(
+        format!("{:?}", array.pretty()),
+        "[[U16(1), U16(2)], [U16(4660), U16(4660), U16(4660), U16(4660), U16(4660), \
+         U16(4660), U16(4660), U16(4660), U16(4660), U16(4660)]]"
+    );
+}
+
+#[test]
+fn test_fuel() {
+    fn walk(iter: Iter) -> Result<()> {
+        for token in iter {
+            if let Token::Array(array) = token? {
+                walk(array.iter())?;
+            }
+        }
+        Ok(())
+    }
+    let data = crate::marshal::marshal(&[&[1u8, 2, 3][..], &[4u8][..]][..]);
+    let signature = strings::Signature::from_str("aay");
+    let iter = |fuel| Iter::new(signature, &data).unwrap().with_fuel(fuel);
+
+    let fuel = AtomicUsize::new(7);
+    assert_eq!(walk(iter(&fuel)), Ok(()));
+    assert_eq!(fuel.load(Ordering::Relaxed), 0);
+    assert_eq!(walk(iter(&AtomicUsize::new(6))), Err(Error::BudgetExceeded));
+}
+
+#[test]
+fn test_nesting_depth() {
+    fn walk(iter: Iter) -> Result<()> {
+        for token in iter {
+            if let Token::Array(array) = token? {
+                walk(array.iter())?;
+            }
+        }
+        Ok(())
+    }
+    // `levels` arrays of variants, each holding the next array, around an empty one.
+    let nested = |levels: usize| {
+        let mut data = Vec::new();
+        for i in 0..levels {
+            data.extend((8 * (levels - i) as u32).to_ne_bytes());
+            data.extend(b"\x02av\0");
+        }
+        data.extend(0u32.to_ne_bytes());
+        data
+    };
+    let signature = strings::Signature::from_str("av");
+    let walk_nested = |levels| walk(Iter::new(signature, &nested(levels)).unwrap());
+    // Each level opens an array and a variant, and the innermost array one more.
+    assert_eq!(walk_nested((MAX_NESTING_DEPTH - 1) / 2), Ok(()));
+    assert_eq!(
+        walk_nested(MAX_NESTING_DEPTH / 2),
+        Err(Error::NestingDepthExceeded)
+    );
+    assert_eq!(walk_nested(10_000), Err(Error::NestingDepthExceeded));
+}
+
+#[test]
+#[cfg(feature = "extensions")]
+fn test_extensions() {
+    let extensions = [Extension {
+        code: b'm',
+        alignment: 8,
+        size: 16,
+    }];
+    let signature = strings::Signature::from_str("yam");
+    let mut data = [0u8; 24];
+    data[0] = 1;
+    data[4] = 16;
+    data[8..].fill(7);
+    assert_eq!(Iter::new(signature, &data).err(), Some(Error::InvalidArgs));
+
+    let mut it = Iter::with_extensions(signature, &data, &extensions)
+        .unwrap()
+        .map(|x| x.unwrap());
+    assert_eq!(it.next(), Some(Token::U8(1)));
+    let Some(Token::Array(array)) = it.next() else {
+        panic!()
+    };
+    assert_eq!(array.alignment(), 8);
+    assert_eq!(
+        array.iter().map(|x| x.unwrap()).collect::<Vec<_>>(),
+        [Token::Extension(&extensions[0], &[7; 16])]
+    );
+
+    for (alignment, size) in [(8, 0), (0, 16), (3, 16)] {
+        let extensions = [Extension {
+            code: b'm',
+            alignment,
+            size,
+        }];
+        assert_eq!(
+            Iter::with_extensions(signature, &data, &extensions).err(),
+            Some(Error::InvalidArgs)
+        );
+    }
+}
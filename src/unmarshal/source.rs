@@ -0,0 +1,76 @@
+use core::mem::MaybeUninit;
+
+use crate::unmarshal::{Error, Reader, Result};
+
+/// Backing storage a [`Reader`] can be built over.
+///
+/// Unmarshalled values borrow from the input, so a reader always works on one contiguous
+/// slice. Sources that may be split (ring buffers, chained captures) copy into a caller
+/// provided scratch buffer only when the data actually spans a boundary.
+pub trait Source {
+    fn contiguous<'a>(&'a self, scratch: &'a mut [MaybeUninit<u8>]) -> Result<&'a [u8]>;
+}
+
+impl Source for [u8] {
+    fn contiguous<'a>(&'a self, _: &'a mut [MaybeUninit<u8>]) -> Result<&'a [u8]> {
+        Ok(self)
+    }
+}
+
+impl<const N: usize> Source for [u8; N] {
+    fn contiguous<'a>(&'a self, _: &'a mut [MaybeUninit<u8>]) -> Result<&'a [u8]> {
+        Ok(self)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Source for alloc::boxed::Box<[u8]> {
+    fn contiguous<'a>(&'a self, _: &'a mut [MaybeUninit<u8>]) -> Result<&'a [u8]> {
+        Ok(self)
+    }
+}
+
+/// Two slices read back to back, e.g. the two halves of a wrapped ring buffer.
+#[derive(Debug, Clone, Copy)]
+pub struct Chain<'a>(pub &'a [u8], pub &'a [u8]);
+
+impl Source for Chain<'_> {
+    fn contiguous<'a>(&'a self, scratch: &'a mut [MaybeUninit<u8>]) -> Result<&'a [u8]> {
+        match *self {
+            Chain(xs, []) | Chain([], xs) => Ok(xs),
+            Chain(xs, ys) => {
+                let len = xs.len() + ys.len();
+                let buf = scratch.get_mut(..len).ok_or(Error::NotEnoughData)?;
+                let (head, tail) = buf.split_at_mut(xs.len());
+                head.write_copy_of_slice(xs);
+                tail.write_copy_of_slice(ys);
+                Ok(unsafe { buf.assume_init_ref() })
+            }
+        }
+    }
+}
+
+impl<'a> Reader<'a> {
+    /// Creates a reader over any [`Source`], using `scratch` if the source has to be joined.
+    pub fn from_source<S: Source + ?Sized>(
+        source: &'a S,
+        scratch: &'a mut [MaybeUninit<u8>],
+    ) -> Result<Self> {
+        source.contiguous(scratch).map(Reader::new)
+    }
+}
+
+#[test]
+fn test_chain() {
+    let mut scratch = [MaybeUninit::uninit(); 8];
+    let data = Chain(&[1, 0], &[0, 0, 2]);
+    let mut r = Reader::from_source(&data, &mut scratch).unwrap();
+    assert_eq!(r.read::<u32>(), Ok(1));
+    assert_eq!(r.read::<u8>(), Ok(2));
+
+    let mut scratch = [MaybeUninit::uninit(); 2];
+    assert_eq!(
+        Reader::from_source(&data, &mut scratch).err(),
+        Some(Error::NotEnoughData)
+    );
+}
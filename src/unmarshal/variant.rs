@@ -0,0 +1,240 @@
+use crate::{
+    marshal::{Marshal, Write, transcode},
+    signature::{Signature, SignatureProxy, complete_type_len, max_alignment},
+    strings,
+    types::Variant,
+    unmarshal::{ArrayIter, Error, Iter, Reader, Result, Unmarshal},
+};
+
+/// A variant whose contained type is only known at runtime.
+///
+/// Unmarshalling checks that the value is well-formed for its signature but decodes nothing;
+/// use [`DynVariant::try_get`] to decode it as a concrete type.
+#[derive(Clone, Copy)]
+pub struct DynVariant<'a> {
+    signature: &'a strings::Signature,
+    reader: Reader<'a>,
+}
+
+impl<'a> DynVariant<'a> {
+    pub const fn signature(&self) -> &'a strings::Signature {
+        self.signature
+    }
+    /// The encoded value, without the leading signature.
+    pub fn bytes(&self) -> &'a [u8] {
+        self.reader.remaining()
+    }
+    /// Where the value starts, counted from the start of the reader it was read from, which
+    /// determines how the value is aligned.
+    pub const fn offset(&self) -> usize {
+        self.reader.position()
+    }
+    /// Whether [`DynVariant::bytes`] are in the other byte order than this machine's.
+    pub const fn is_byte_swapped(&self) -> bool {
+        self.reader.is_byte_swapped()
+    }
+    pub fn try_get<T: Unmarshal<'a> + Signature>(&self) -> Result<T> {
+        if self.signature != T::SIGNATURE {
+            Err(Error::InvalidArgs)?
+        }
+        self.reader.clone().read()
+    }
+}
+
+impl core::fmt::Debug for DynVariant<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("DynVariant")
+            .field("signature", &self.signature)
+            .field("bytes", &crate::show_bytes(self.bytes()))
+            .finish()
+    }
+}
+
+impl SignatureProxy for DynVariant<'_> {
+    type Proxy = Variant<()>;
+}
+
+impl<'a> Unmarshal<'a> for DynVariant<'a> {
+    fn unmarshal(r: &mut Reader<'a>) -> Result<Self> {
+        let signature: &strings::Signature = r.read()?;
+        let start = *r;
+        if !r.skip(signature)?.is_empty() {
+            Err(Error::InvalidArgs)?
+        }
+        Ok(Self {
+            signature,
+            reader: r.consumed_since(&start),
+        })
+    }
+}
+
+/// A variant held opaquely, e.g. by a proxy or a property cache, borrowing its encoded value
+/// instead of decoding it.
+///
+/// Marshalling copies the value verbatim when it lands at an offset with the same alignment as
+/// where it was read from, and re-encodes it with the padding fixed up otherwise, as well as
+/// when it was read in the other byte order.
+#[derive(Clone, Copy)]
+pub struct RawVariant<'a> {
+    signature: &'a strings::Signature,
+    reader: Reader<'a>,
+}
+
+impl<'a> RawVariant<'a> {
+    /// A variant of `signature` holding `bytes`, which are marshalled as if starting at an
+    /// 8-aligned offset. Fails unless `bytes` is exactly one value of `signature`.
+    pub fn new(signature: &'a strings::Signature, bytes: &'a [u8]) -> Result<Self> {
+        if complete_type_len(signature.as_bytes()) != Some(signature.as_bytes().len()) {
+            Err(Error::InvalidArgs)?
+        }
+        let mut iter = Iter::new(signature, bytes)?;
+        iter.validate()?;
+        if !iter.reader().remaining().is_empty() {
+            Err(Error::InvalidArgs)?
+        }
+        Ok(Self {
+            signature,
+            reader: Reader::new(bytes),
+        })
+    }
+    pub const fn signature(&self) -> &'a strings::Signature {
+        self.signature
+    }
+    /// The encoded value, without the leading signature.
+    pub fn bytes(&self) -> &'a [u8] {
+        self.reader.remaining()
+    }
+    /// Where the value starts, counted from the start of the reader it was read from, which
+    /// determines how the value is aligned.
+    pub const fn offset(&self) -> usize {
+        self.reader.position()
+    }
+}
+
+impl core::fmt::Debug for RawVariant<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("RawVariant")
+            .field("signature", &self.signature)
+            .field("offset", &self.offset())
+            .field("bytes", &crate::show_bytes(self.bytes()))
+            .finish()
+    }
+}
+
+/// Decodes the whole value, which [`DynVariant`] only skips over, so that marshalling can
+/// re-encode it.
+impl<'a> TryFrom<DynVariant<'a>> for RawVariant<'a> {
+    type Error = Error;
+
+    fn try_from(x: DynVariant<'a>) -> Result<Self> {
+        Iter::from_reader(x.signature, x.reader, &[])?.validate()?;
+        Ok(Self {
+            signature: x.signature,
+            reader: x.reader,
+        })
+    }
+}
+
+impl SignatureProxy for RawVariant<'_> {
+    type Proxy = Variant<()>;
+}
+
+impl<'a> Unmarshal<'a> for RawVariant<'a> {
+    fn unmarshal(r: &mut Reader<'a>) -> Result<Self> {
+        r.read::<DynVariant>()?.try_into()
+    }
+}
+
+impl Marshal for RawVariant<'_> {
+    fn marshal<W: Write + ?Sized>(self, w: &mut W) {
+        w.write(self.signature);
+        let alignment = max_alignment(self.signature);
+        if w.position() % alignment == self.offset() % alignment && !self.reader.is_byte_swapped() {
+            w.write_bytes(self.bytes());
+        } else {
+            // The value was decoded in full when it was read or constructed.
+            let iter = Iter::from_reader(self.signature, self.reader, &[]).unwrap();
+            transcode(iter, w, &mut |token| token).unwrap();
+        }
+    }
+}
+
+/// Iterator over an `av` array.
+pub type VariantArrayIter<'a> = ArrayIter<'a, DynVariant<'a>>;
+
+#[cfg(target_endian = "little")]
+#[test]
+fn test_variant_array() {
+    let data = crate::marshal::marshal(crate::struct_new!(
+        1u8,
+        crate::marshal::Array([Variant(2u8), Variant(3u8)].into_iter()),
+    ));
+    let mut r = Reader::new(&data);
+    let _: u8 = r.read().unwrap();
+    let mut iter = r.read::<VariantArrayIter>().unwrap().map(Result::unwrap);
+    let x = iter.next().unwrap();
+    assert_eq!(x.signature(), strings::Signature::from_str("y"));
+    assert_eq!(x.try_get::<u8>(), Ok(2));
+    assert_eq!(x.try_get::<u32>(), Err(Error::InvalidArgs));
+    assert_eq!(iter.next().unwrap().try_get::<u8>(), Ok(3));
+    assert!(iter.next().is_none());
+}
+
+#[test]
+fn test_raw_variant() {
+    use crate::{marshal::marshal, struct_new, struct_type};
+
+    let value = struct_new!(
+        1u32,
+        2u8,
+        Variant(crate::marshal::Array([3u64, 4].into_iter()))
+    );
+    let data = marshal(value);
+    let crate::struct_match!(_, _, raw) = Reader::new(&data)
+        .read::<struct_type!(u32, u8, RawVariant)>()
+        .unwrap();
+    assert_eq!(raw.signature(), strings::Signature::from_str("at"));
+    assert_eq!(raw.offset(), 9);
+
+    // Where the value keeps its alignment it is copied, elsewhere it is re-encoded.
+    assert_eq!(marshal(struct_new!(1u32, 2u8, raw)), data);
+    let shifted = marshal(struct_new!(1u8, raw));
+    assert_eq!(
+        shifted,
+        marshal(struct_new!(
+            1u8,
+            Variant(crate::marshal::Array([3u64, 4].into_iter()))
+        ))
+    );
+    let crate::struct_match!(_, raw) = Reader::new(&shifted)
+        .read::<struct_type!(u8, RawVariant)>()
+        .unwrap();
+    assert_eq!(raw.offset(), 5);
+
+    let bytes = marshal(7u16);
+    let raw = RawVariant::new(strings::Signature::from_str("q"), &bytes).unwrap();
+    assert_eq!(marshal(raw), marshal(Variant(7u16)));
+    assert_eq!(
+        RawVariant::new(strings::Signature::from_str("y"), &bytes).err(),
+        Some(Error::InvalidArgs)
+    );
+    assert_eq!(
+        RawVariant::new(strings::Signature::from_str("yy"), &bytes).err(),
+        Some(Error::InvalidArgs)
+    );
+
+    // The array length still covers the element, but its string runs past it.
+    let mut strings = marshal(&["ab"][..]);
+    strings[4] = 0xff;
+    assert_eq!(
+        RawVariant::new(strings::Signature::from_str("as"), &strings).err(),
+        Some(Error::NotEnoughData)
+    );
+    let mut variant = marshal(Variant(&["ab"][..]));
+    variant[8] = 0xff;
+    assert!(Reader::new(&variant).read::<DynVariant>().is_ok());
+    assert_eq!(
+        Reader::new(&variant).read::<RawVariant>().err(),
+        Some(Error::NotEnoughData)
+    );
+}
@@ -0,0 +1,102 @@
+//! Alignment arithmetic for custom [`Marshal`](crate::marshal::Marshal) and
+//! [`Unmarshal`](crate::unmarshal::Unmarshal) impls.
+//!
+//! The specification aligns every value to its natural size: 1 for bytes and signatures, 2 for
+//! 16-bit integers, 4 for booleans, 32-bit integers, unix fds, strings, object paths and array
+//! lengths, and 8 for 64-bit integers, doubles, structs and dict entries. Variants align to 1.
+//! Offsets count from the start of the message, and message bodies start 8-aligned, so
+//! alignment within a body can be computed from the start of the body as well. Every `align`
+//! taken here must be a power of two.
+//!
+//! Also holds [`Digits`], for writing numbers without allocating.
+
+use core::fmt;
+
+/// `offset` rounded up to the next multiple of `align`.
+pub const fn aligned(offset: usize, align: usize) -> usize {
+    (offset + align - 1) & !(align - 1)
+}
+
+/// The number of padding bytes needed to bring `offset` to a multiple of `align`.
+pub const fn align_padding(offset: usize, align: usize) -> usize {
+    aligned(offset, align) - offset
+}
+
+/// Whether `offset` is a multiple of `align`. For a pointer, pass its address.
+pub const fn is_aligned(offset: usize, align: usize) -> bool {
+    offset & (align - 1) == 0
+}
+
+/// The digits of an integer in decimal or lowercase hex, formatted without allocating.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Digits {
+    buf: [u8; 20],
+    start: usize,
+}
+
+impl Digits {
+    pub const fn decimal(n: u64) -> Self {
+        Self::new(n, 10)
+    }
+    pub const fn hex(n: u64) -> Self {
+        Self::new(n, 16)
+    }
+    const fn new(mut n: u64, radix: u64) -> Self {
+        let mut buf = [0; 20];
+        let mut start = buf.len();
+        loop {
+            let digit = (n % radix) as u8;
+            start -= 1;
+            buf[start] = if digit < 10 {
+                b'0' + digit
+            } else {
+                b'a' + digit - 10
+            };
+            n /= radix;
+            if n == 0 {
+                break;
+            }
+        }
+        Self { buf, start }
+    }
+    pub const fn as_bytes(&self) -> &[u8] {
+        self.buf.split_at(self.start).1
+    }
+    pub const fn as_str(&self) -> &str {
+        // SAFETY: every byte written is an ASCII digit or letter.
+        unsafe { core::str::from_utf8_unchecked(self.as_bytes()) }
+    }
+}
+
+impl fmt::Display for Digits {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl fmt::Debug for Digits {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Digits({self})")
+    }
+}
+
+#[test]
+fn test_digits() {
+    assert_eq!(Digits::decimal(0).as_str(), "0");
+    assert_eq!(Digits::decimal(1000).as_str(), "1000");
+    assert_eq!(Digits::decimal(u64::MAX).as_str(), "18446744073709551615");
+    assert_eq!(Digits::hex(0x31).as_bytes(), b"31");
+    assert_eq!(Digits::hex(u64::MAX).as_str(), "ffffffffffffffff");
+}
+
+#[test]
+fn test_alignment() {
+    assert_eq!(aligned(0, 8), 0);
+    assert_eq!(aligned(1, 8), 8);
+    assert_eq!(aligned(13, 4), 16);
+    assert_eq!(align_padding(13, 4), 3);
+    assert_eq!(align_padding(16, 8), 0);
+    assert!(is_aligned(24, 8));
+    assert!(!is_aligned(6, 4));
+    assert!(is_aligned(7, 1));
+}